@@ -1,78 +1,2138 @@
-use crate::parse::c_ast::{Stmt,Expr};
-use crate::assemble::assembly_ast::Instr;
+use std::collections::HashMap;
+use std::fmt;
+use crate::parse::c_ast::{Stmt, Expr, ExprKind, UnaryOp, BinaryOp, Type, Const, BitField, Initializer, InitElement};
+use crate::assemble::assembly_ast::{self as asm, Instr, Operand, Reg, CondCode, AsmType};
+use crate::validate::typechecking::{Symbol, IdentAttrs, InitialValue, StaticInit};
 
-// This is currently just basically acting as a namespace, but I want the possibility of 
-// carried state in the future without significant rewrites
+/// Fewest cases for which a switch is lowered to a jump table
+const JUMP_TABLE_MIN_CASES: usize = 4;
+/// Largest number of table entries per case for which a switch is lowered to a jump table,
+/// sparser switches use a tree of comparisons instead
+const JUMP_TABLE_MAX_ENTRIES_PER_CASE: u64 = 4;
+/// Most cases compared one after another at the leaves of a comparison tree
+const CASE_TREE_LEAF_SIZE: usize = 3;
+/// Registers holding the first integer arguments of a call, any others are passed on the stack
+const ARGUMENT_REGISTERS: [Reg; 6] = [Reg::DI, Reg::SI, Reg::DX, Reg::CX, Reg::R8, Reg::R9];
+/// Registers holding the first floating arguments of a call
+const SSE_ARGUMENT_REGISTERS: [Reg; 8] = [Reg::XMM0, Reg::XMM1, Reg::XMM2, Reg::XMM3, Reg::XMM4, Reg::XMM5, Reg::XMM6, Reg::XMM7];
+/// Registers holding the integer and floating eightbytes of a returned structure
+const RETURN_REGISTERS: [Reg; 2] = [Reg::AX, Reg::DX];
+const SSE_RETURN_REGISTERS: [Reg; 2] = [Reg::XMM0, Reg::XMM1];
+/// Placeholder operand for the result of an expression of type void, which is never used
+const VOID: Operand = Operand::Imm { value: 0 };
+/// Offset from the base pointer of the first argument passed on the stack, above the saved base
+/// pointer and return address
+const STACK_ARGUMENTS_OFFSET: i32 = 16;
+/// Size of the register save area of a variadic function, where the integer argument registers
+/// are followed by the xmm argument registers, which take 16 bytes each
+const GP_SAVE_SIZE: usize = 8 * ARGUMENT_REGISTERS.len();
+const REGISTER_SAVE_AREA_SIZE: usize = GP_SAVE_SIZE + 16 * SSE_ARGUMENT_REGISTERS.len();
+
+/// Lowers the C AST into the assembly AST
+///
+/// Intermediate values are first placed in pseudo-registers, which are then assigned stack slots,
+/// and finally any instructions with operand combinations x86 doesn't allow are rewritten.
 pub struct Assembler{
+    /// Counter used to generate unique temporary names
+    temp_counter: usize,
+    /// Counter used to generate unique label names
+    label_counter: usize,
+    /// Symbol table built by the type checker
+    symbols: HashMap<String, Symbol>,
+    /// Read only constants used so far, in the order they were first used
+    constants: Vec<Instr>,
+    /// Names of the read only constants, keyed by their bits, type and alignment
+    constant_names: HashMap<(i64, AsmType, usize), String>,
+    /// Names of the string literals, keyed by their bytes
+    string_names: HashMap<Vec<u8>, String>,
+    /// Where the function being assembled writes a structure it returns in memory, as passed by
+    /// the caller
+    return_pointer: Option<Operand>,
+    /// Where the variadic arguments of the function being assembled are, if it takes any
+    variadic: Option<VariadicFrame>,
+}
+
+/// What va_start needs to know about where a variadic function's arguments are
+#[derive(Clone)]
+struct VariadicFrame {
+    /// The temporary the prologue saves every argument register to
+    save_area: Operand,
+    /// Offsets into the save area of the first integer and floating registers which don't hold
+    /// a named parameter
+    gp_offset: usize,
+    fp_offset: usize,
+    /// Offset from the base pointer of the first stack argument after the named parameters
+    overflow_offset: i32,
 }
 
 impl Assembler{
-    pub(crate) fn new() -> Assembler{
-        Assembler{}
+    pub(crate) fn new(symbols: HashMap<String, Symbol>) -> Assembler{
+        Assembler{temp_counter: 0, label_counter: 0, symbols, constants: Vec::new(), constant_names: HashMap::new(),
+                  string_names: HashMap::new(), return_pointer: None, variadic: None}
     }
-    
-    pub fn assemble(&self, stmt: Stmt) -> Result<Box<Instr>,AssemblerError>{
-        self.assemble_stmt(stmt)
-    }
-    
-    fn assemble_stmt(&self, stmt: Stmt) -> Result<Box<Instr>, AssemblerError>{
+
+    pub fn assemble(&mut self, stmt: Stmt) -> Result<Instr,AssemblerError>{
         match stmt{
             Stmt::Program { body } => {
-                let body = self.assemble_stmt(*body)?;
-                Ok(Box::new(Instr::Program {body}))
+                let mut top_level = Vec::new();
+                for item in body {
+                    // Declarations without a body produce no code
+                    if let Stmt::FuncDef { .. } = item {
+                        top_level.push(self.assemble_function(item)?);
+                    }
+                }
+                top_level.extend(self.static_variables());
+                top_level.append(&mut self.constants);
+                Ok(Instr::Program {body: top_level})
+            }
+            _=>{Err(AssemblerError::InvalidFuncBody)}
+        }
+    }
+
+    fn assemble_function(&mut self, stmt: Stmt) -> Result<Instr, AssemblerError>{
+        let (name, params, ty, body) = match stmt {
+            Stmt::FuncDef { name, params, ty, body, .. } => (name, params, ty, body),
+            _=>{return Err(AssemblerError::InvalidFuncBody)}
+        };
+        let Type::Function { params: param_types, ret, variadic } = ty else {
+            return Err(AssemblerError::InvalidFuncBody);
+        };
+        let name = match name.kind{
+            ExprKind::Identifier { value } => {value},
+            _=>{return Err(AssemblerError::InvalidFuncName)}
+        };
+        let global = !matches!(self.symbols.get(&name), Some(Symbol { attrs: IdentAttrs::Function { global: false, .. }, .. }));
+        let mut instructions:Vec<Instr> = Vec::new();
+        // A structure returned in memory is written through a pointer the caller passes in di
+        self.return_pointer = None;
+        let integer_registers = if is_memory_class(&ret) {
+            let pointer = self.make_temporary(&Type::Pointer(ret));
+            instructions.push(Instr::Mov { ty: AsmType::Quadword, src: Operand::Register { reg: Reg::DI }, dst: pointer.clone() });
+            self.return_pointer = Some(pointer);
+            &ARGUMENT_REGISTERS[1..]
+        } else {
+            &ARGUMENT_REGISTERS[..]
+        };
+        // Any argument register may hold a variadic argument, so a variadic function saves all
+        // of them where va_arg can find them
+        let save_area = variadic.then(|| {
+            let save_area = self.make_temporary(&Type::Array { element: Box::new(Type::Long), size: Some(REGISTER_SAVE_AREA_SIZE / 8) });
+            instructions.push(Instr::Lea { src: save_area.clone(), dst: Operand::Register { reg: Reg::AX } });
+            for (i, reg) in ARGUMENT_REGISTERS.into_iter().enumerate() {
+                let dst = Operand::Memory { reg: Reg::AX, offset: 8 * i as i32 };
+                instructions.push(Instr::Mov { ty: AsmType::Quadword, src: Operand::Register { reg }, dst });
+            }
+            for (i, reg) in SSE_ARGUMENT_REGISTERS.into_iter().enumerate() {
+                let dst = Operand::Memory { reg: Reg::AX, offset: (GP_SAVE_SIZE + 16 * i) as i32 };
+                instructions.push(Instr::Mov { ty: AsmType::Double, src: Operand::Register { reg }, dst });
+            }
+            save_area
+        });
+        // Copy the parameters out of the argument registers and the caller's frame, so they can be
+        // treated like any other local
+        let locations = argument_locations(&param_types, integer_registers);
+        let registers_used: Vec<Reg> = locations.iter().flatten().flatten().copied().collect();
+        let mut stack_params = Vec::new();
+        let mut stack_offset = STACK_ARGUMENTS_OFFSET;
+        for ((param, param_ty), regs) in params.into_iter().zip(&param_types).zip(locations) {
+            let dst = Operand::Pseudo { name: param };
+            match regs {
+                Some(regs) if matches!(param_ty, Type::Struct(_)) => {
+                    self.store_eightbytes(&regs, param_ty, &Lvalue::Operand(dst), Reg::AX, &mut instructions);
+                }
+                Some(regs) => instructions.push(Instr::Mov { ty: asm_type(param_ty), src: Operand::Register { reg: regs[0] }, dst }),
+                None => {
+                    stack_params.push((Operand::Stack { offset: stack_offset }, param_ty, dst));
+                    stack_offset += 8 * param_ty.size().div_ceil(8) as i32;
+                }
+            }
+        }
+        // Copying a structure goes through si and di, so the stack is only read once every
+        // register has been saved
+        for (src, param_ty, dst) in stack_params {
+            self.store(&Lvalue::Operand(dst), param_ty, src, &mut instructions);
+        }
+        // The variadic arguments start with the first register, or stack slot, the named
+        // parameters leave unused
+        self.variadic = save_area.map(|save_area| {
+            let sse_used = registers_used.iter().filter(|reg| SSE_ARGUMENT_REGISTERS.contains(reg)).count();
+            let integer_used = ARGUMENT_REGISTERS.len() - integer_registers.len() + registers_used.len() - sse_used;
+            VariadicFrame { save_area, gp_offset: 8 * integer_used, fp_offset: GP_SAVE_SIZE + 16 * sse_used, overflow_offset: stack_offset }
+        });
+        for item in body {
+            self.assemble_stmt(item, &mut instructions)?;
+        }
+        // Falling off the end of a function returns 0
+        instructions.push(Instr::Mov { ty: AsmType::Quadword, src: Operand::Imm { value: 0 }, dst: Operand::Register { reg: Reg::AX } });
+        instructions.push(Instr::Ret);
+        // Assign the pseudo-registers to stack slots, and reserve space for them
+        let stack_size = replace_pseudos(&mut instructions, &self.symbols);
+        instructions.insert(0, Instr::AllocateStack { bytes: stack_size });
+        Ok(Instr::FuncDef {name, global, instructions: fix_instructions(instructions)})
+    }
+
+    /// Definitions of every variable with static storage defined in this translation unit, in
+    /// order of name so the output is deterministic
+    fn static_variables(&mut self) -> Vec<Instr> {
+        let mut names: Vec<String> = self.symbols.keys().cloned().collect();
+        names.sort();
+        names.into_iter().filter_map(|name| {
+            let Symbol { ty, attrs: IdentAttrs::Static { init, global } } = &self.symbols[&name] else {
+                return None;
+            };
+            let (ty, global) = (ty.clone(), *global);
+            // Tentative definitions which are never initialized are zero
+            let init = match init.clone() {
+                InitialValue::Initial(init) => self.static_init(&ty, init),
+                InitialValue::Tentative => asm::StaticInit::Scalar(0),
+                InitialValue::NoInitializer => return None,
+            };
+            Some(Instr::StaticVariable { name, global, ty: asm_type(&ty), init })
+        }).collect()
+    }
+
+    /// Initial value of static data of type ty, where string literals pointed to become constants
+    fn static_init(&mut self, ty: &Type, init: StaticInit) -> asm::StaticInit {
+        match init {
+            StaticInit::Scalar(value) => asm::StaticInit::Scalar(value),
+            StaticInit::String(value) => asm::StaticInit::String { value, size: ty.size() },
+            StaticInit::StringAddress(value) => asm::StaticInit::Address { name: self.string_constant(value), offset: 0 },
+            StaticInit::FunctionAddress(name) => asm::StaticInit::Address { name, offset: 0 },
+            StaticInit::ObjectAddress(name, offset) => asm::StaticInit::Address { name, offset },
+            StaticInit::Aggregate(values) => asm::StaticInit::Aggregate(values.into_iter()
+                .map(|(offset, ty, value)| (offset, asm_type(&ty), self.static_init(&ty, value)))
+                .collect()),
+        }
+    }
+
+    fn assemble_stmt(&mut self, stmt: Stmt, instructions: &mut Vec<Instr>) -> Result<(), AssemblerError>{
+        match stmt{
+            Stmt::Return { value: None } => {
+                instructions.push(Instr::Ret);
+                Ok(())
+            }
+            Stmt::Return { value: Some(value) } if matches!(value.ty(), Type::Struct(_)) => {
+                let ty = value.ty().clone();
+                let ret_val = self.assemble_expr(*value, instructions)?;
+                match self.return_pointer.clone() {
+                    // Copy the structure to where the caller asked, and hand the pointer back in ax
+                    Some(pointer) => {
+                        self.copy_object(&Lvalue::Operand(ret_val), &Lvalue::Dereferenced(pointer.clone()), ty.size(), instructions);
+                        instructions.push(Instr::Mov { ty: AsmType::Quadword, src: pointer, dst: Operand::Register { reg: Reg::AX } });
+                    }
+                    None => self.load_eightbytes(&Lvalue::Operand(ret_val), &ty, &return_registers(&ty), Reg::CX, instructions),
+                }
+                instructions.push(Instr::Ret);
+                Ok(())
+            }
+            Stmt::Return { value: Some(value) } => {
+                let ty = asm_type(value.ty());
+                let ret_val = self.assemble_expr(*value, instructions)?;
+                // Move the return value to the ax register, or xmm0 if it is floating
+                let reg = if ty.is_floating() {Reg::XMM0} else {Reg::AX};
+                instructions.push(Instr::Mov{ty, src: ret_val, dst: Operand::Register { reg }});
+                // Return from the function
+                instructions.push(Instr::Ret);
+                Ok(())
+            }
+            Stmt::Declaration { name, ty, init, storage, .. } => {
+                // Locals live in pseudo-registers named after the (unique) variable name, which
+                // are later given stack slots. Static locals are initialized before the program
+                // starts, and extern declarations define nothing.
+                if storage.is_some() {
+                    return Ok(());
+                }
+                let variable = Lvalue::Operand(Operand::Pseudo { name });
+                match init {
+                    Some(Initializer::Single(init)) => self.assemble_initializer(*init, &ty, &variable, instructions)?,
+                    Some(Initializer::Compound { elements, .. }) => self.assemble_compound_initializer(elements, &ty, &variable, instructions)?,
+                    None => {}
+                }
+                Ok(())
+            }
+            Stmt::Expression { expr } => {
+                // The value is discarded
+                _ = self.assemble_expr(*expr, instructions)?;
+                Ok(())
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                let else_label = self.make_label("if_else");
+                let end = self.make_label("if_end");
+                self.assemble_condition_jump(*condition, CondCode::E, else_label.clone(), instructions)?;
+                self.assemble_stmt(*then_branch, instructions)?;
+                instructions.push(Instr::Jmp { target: end.clone() });
+                instructions.push(Instr::Label { name: else_label });
+                if let Some(else_branch) = else_branch {
+                    self.assemble_stmt(*else_branch, instructions)?;
+                }
+                instructions.push(Instr::Label { name: end });
+                Ok(())
+            }
+            Stmt::Compound { body } => {
+                for item in body {
+                    self.assemble_stmt(item, instructions)?;
+                }
+                Ok(())
+            }
+            Stmt::While { condition, body, label } => {
+                let continue_label = format!("continue.{}", label);
+                let break_label = format!("break.{}", label);
+                instructions.push(Instr::Label { name: continue_label.clone() });
+                self.assemble_condition_jump(*condition, CondCode::E, break_label.clone(), instructions)?;
+                self.assemble_stmt(*body, instructions)?;
+                instructions.push(Instr::Jmp { target: continue_label });
+                instructions.push(Instr::Label { name: break_label });
+                Ok(())
+            }
+            Stmt::DoWhile { body, condition, label } => {
+                let start_label = format!("start.{}", label);
+                instructions.push(Instr::Label { name: start_label.clone() });
+                self.assemble_stmt(*body, instructions)?;
+                instructions.push(Instr::Label { name: format!("continue.{}", label) });
+                self.assemble_condition_jump(*condition, CondCode::NE, start_label, instructions)?;
+                instructions.push(Instr::Label { name: format!("break.{}", label) });
+                Ok(())
+            }
+            Stmt::For { init, condition, post, body, label } => {
+                let start_label = format!("start.{}", label);
+                let break_label = format!("break.{}", label);
+                for init in init {
+                    self.assemble_stmt(init, instructions)?;
+                }
+                instructions.push(Instr::Label { name: start_label.clone() });
+                // A missing condition is always true
+                if let Some(condition) = condition {
+                    self.assemble_condition_jump(*condition, CondCode::E, break_label.clone(), instructions)?;
+                }
+                self.assemble_stmt(*body, instructions)?;
+                instructions.push(Instr::Label { name: format!("continue.{}", label) });
+                if let Some(post) = post {
+                    _ = self.assemble_expr(*post, instructions)?;
+                }
+                instructions.push(Instr::Jmp { target: start_label });
+                instructions.push(Instr::Label { name: break_label });
+                Ok(())
             }
-            Stmt::FuncDef { name, body } => {
-                let name = match *name{
-                    Expr::Identifier { value } => {value.clone()},
-                    _=>{return Err(AssemblerError::InvalidFuncName)}
+            Stmt::Switch { condition, body, label, mut cases, default } => {
+                let break_label = format!("break.{}", label);
+                let ty = condition.ty().clone();
+                let value = self.assemble_expr(*condition, instructions)?;
+                // When no case matches control goes to the default case, or past the switch
+                let fallback = default.unwrap_or_else(|| break_label.clone());
+                // Cases are ordered as the values of the switch's type, which for unsigned types
+                // means as unsigned bits
+                if ty.is_signed() {
+                    cases.sort_by_key(|(case_value, _)| *case_value);
+                } else {
+                    cases.sort_by_key(|(case_value, _)| *case_value as u64);
+                }
+                if use_jump_table(&cases) {
+                    self.assemble_jump_table(value, &ty, &cases, fallback, &label, instructions);
+                } else {
+                    self.assemble_case_tree(&value, &ty, &cases, &fallback, instructions);
+                }
+                self.assemble_stmt(*body, instructions)?;
+                instructions.push(Instr::Label { name: break_label });
+                Ok(())
+            }
+            Stmt::Case { body, label, .. } | Stmt::Default { body, label, .. }
+            | Stmt::Labeled { body, label, .. } => {
+                instructions.push(Instr::Label { name: label });
+                self.assemble_stmt(*body, instructions)
+            }
+            Stmt::Goto { label, .. } => {
+                instructions.push(Instr::Jmp { target: label });
+                Ok(())
+            }
+            Stmt::Break { label, .. } => {
+                instructions.push(Instr::Jmp { target: format!("break.{}", label) });
+                Ok(())
+            }
+            Stmt::Continue { label, .. } => {
+                instructions.push(Instr::Jmp { target: format!("continue.{}", label) });
+                Ok(())
+            }
+            Stmt::Null | Stmt::FuncDecl { .. } | Stmt::StructDecl { .. } => Ok(()),
+            _=>{Err(AssemblerError::InvalidFuncBody)}
+        }
+    }
+
+    /// Generate the instructions computing expr, returning the operand holding its value
+    fn assemble_expr(&mut self, expr: Expr, instructions: &mut Vec<Instr>) -> Result<Operand, AssemblerError>{
+        let ty = expr.ty().clone();
+        match expr.kind{
+            // Floating values can't be immediates, so are read from memory
+            ExprKind::Constant { value: value @ (Const::Float(_) | Const::Double(_)) } => {
+                Ok(self.floating_constant(value, value.ty().size()))
+            }
+            ExprKind::Constant { value } => {
+                Ok(Operand::Imm { value: value.bits() })
+            }
+            ExprKind::Identifier { value } => {
+                Ok(Operand::Pseudo { name: value })
+            }
+            // Like an array variable, a string literal is only used through its address
+            ExprKind::String { value } => Ok(Operand::Data { name: self.string_constant(value) }),
+            // A cast to void only evaluates its operand for its side effects
+            ExprKind::Cast { target: Type::Void, expr } => {
+                self.assemble_expr(*expr, instructions)?;
+                Ok(VOID)
+            }
+            ExprKind::Cast { target, expr } => {
+                let src_ty = expr.ty().clone();
+                let value = self.assemble_expr(*expr, instructions)?;
+                Ok(self.assemble_conversion(value, &src_ty, &target, instructions))
+            }
+            ExprKind::SizeOf { .. } | ExprKind::SizeOfType { .. } | ExprKind::AlignOf { .. } => {
+                unreachable!("the type checker replaces sizeof and _Alignof with constants")
+            }
+            ExprKind::FunctionCall { function, args } => self.assemble_call(*function, args, &ty, instructions),
+            ExprKind::VaStart { ap } => {
+                let ap = self.assemble_expr(*ap, instructions)?;
+                self.assemble_va_start(ap, instructions);
+                Ok(VOID)
+            }
+            ExprKind::VaArg { ap, .. } => {
+                let ap = self.assemble_expr(*ap, instructions)?;
+                Ok(self.assemble_va_arg(ap, &ty, instructions))
+            }
+            ExprKind::VaEnd { ap } => {
+                self.assemble_expr(*ap, instructions)?;
+                Ok(VOID)
+            }
+            ExprKind::VaCopy { dst, src } => {
+                let Type::Pointer(va_list) = src.ty().clone() else {
+                    unreachable!("va_list operands have been converted to pointers");
                 };
-                let name_identifier = Box::new(Instr::Identifier { value:name });
-                let mut func_body:Vec<Box<Instr>> = Vec::new();
-                // Figure out return value
-                let ret_val = match *body {
-                    Stmt::Return { value } => {
-                        self.assemble_expr(*value)?
+                let dst = self.assemble_expr(*dst, instructions)?;
+                let src = self.assemble_expr(*src, instructions)?;
+                self.copy_object(&Lvalue::Dereferenced(src), &Lvalue::Dereferenced(dst), va_list.size(), instructions);
+                Ok(VOID)
+            }
+            ExprKind::Assignment { left, right } => {
+                let dst = self.assemble_lvalue(*left, instructions)?;
+                let value = self.assemble_expr(*right, instructions)?;
+                self.store(&dst, &ty, value.clone(), instructions);
+                match dst {
+                    Lvalue::Operand(dst) => Ok(dst),
+                    Lvalue::Dereferenced(_) => Ok(value),
+                    // The result is what the bit-field holds, which may have been truncated
+                    Lvalue::BitField { .. } => Ok(self.load(&dst, &ty, instructions)),
+                }
+            }
+            ExprKind::AddressOf { expr } => {
+                if let (ExprKind::Identifier { value }, Type::Function { .. }) = (&expr.kind, expr.ty()) {
+                    return Ok(self.function_address(value.clone(), &ty, instructions));
+                }
+                match self.assemble_lvalue(*expr, instructions)? {
+                    Lvalue::Operand(object) => {
+                        let dst = self.make_temporary(&ty);
+                        instructions.push(Instr::Lea { src: object, dst: dst.clone() });
+                        Ok(dst)
                     }
-                    _=>{return Err(AssemblerError::InvalidReturn)}
+                    // The address of *p is p
+                    Lvalue::Dereferenced(pointer) => Ok(pointer),
+                    Lvalue::BitField { .. } => unreachable!("the type checker rejects the address of a bit-field"),
+                }
+            }
+            ExprKind::Dereference { expr } => {
+                let pointer = self.assemble_expr(*expr, instructions)?;
+                Ok(self.load(&Lvalue::Dereferenced(pointer), &ty, instructions))
+            }
+            ExprKind::Subscript { array, index } => {
+                let pointer = self.assemble_subscript(*array, *index, instructions)?;
+                Ok(self.load(&Lvalue::Dereferenced(pointer), &ty, instructions))
+            }
+            ExprKind::Member { expr, member } => {
+                let member = self.assemble_member(*expr, &member, instructions)?;
+                Ok(self.load(&member, &ty, instructions))
+            }
+            ExprKind::Conditional { condition, then_expr, else_expr } => {
+                let else_label = self.make_label("conditional_else");
+                let end = self.make_label("conditional_end");
+                // Void results are discarded
+                let dst = if ty == Type::Void {None} else {Some(self.make_temporary(&ty))};
+                self.assemble_condition_jump(*condition, CondCode::E, else_label.clone(), instructions)?;
+                let value = self.assemble_expr(*then_expr, instructions)?;
+                if let Some(dst) = &dst {
+                    self.store(&Lvalue::Operand(dst.clone()), &ty, value, instructions);
+                }
+                instructions.push(Instr::Jmp { target: end.clone() });
+                instructions.push(Instr::Label { name: else_label });
+                let value = self.assemble_expr(*else_expr, instructions)?;
+                if let Some(dst) = &dst {
+                    self.store(&Lvalue::Operand(dst.clone()), &ty, value, instructions);
+                }
+                instructions.push(Instr::Label { name: end });
+                Ok(dst.unwrap_or(VOID))
+            }
+            ExprKind::Unary { op: UnaryOp::Not, expr } if expr.ty().is_floating() => {
+                let src_ty = expr.ty().clone();
+                let src = self.assemble_expr(*expr, instructions)?;
+                let zero = self.floating_constant(Const::Double(0.0).convert(&src_ty), src_ty.size());
+                Ok(self.assemble_binary(BinaryOp::Equal, &src_ty, src, zero, instructions))
+            }
+            ExprKind::Unary { op: UnaryOp::Negate, expr } if expr.ty().is_floating() => {
+                // Flip the sign bit, xor needs its memory operand to be 16 byte aligned
+                let src_ty = asm_type(expr.ty());
+                let src = self.assemble_expr(*expr, instructions)?;
+                let dst = self.make_temporary(&ty);
+                let sign_bit = self.floating_constant(Const::Double(-0.0).convert(&ty), 16);
+                instructions.push(Instr::Mov { ty: src_ty, src, dst: dst.clone() });
+                instructions.push(Instr::Binary { op: asm::BinaryOp::Xor, ty: src_ty, src: sign_bit, dst: dst.clone() });
+                Ok(dst)
+            }
+            ExprKind::Unary { op, expr } => {
+                let src_ty = asm_type(expr.ty());
+                let src = self.assemble_expr(*expr, instructions)?;
+                let dst = self.make_temporary(&ty);
+                match op {
+                    UnaryOp::Negate | UnaryOp::Complement => {
+                        let op = if op == UnaryOp::Negate {asm::UnaryOp::Neg} else {asm::UnaryOp::Not};
+                        instructions.push(Instr::Mov { ty: src_ty, src, dst: dst.clone() });
+                        instructions.push(Instr::Unary { op, ty: src_ty, operand: dst.clone() });
+                    }
+                    UnaryOp::Not => {
+                        instructions.push(Instr::Cmp { ty: src_ty, src: Operand::Imm { value: 0 }, dst: src });
+                        instructions.push(Instr::Mov { ty: AsmType::Longword, src: Operand::Imm { value: 0 }, dst: dst.clone() });
+                        instructions.push(Instr::SetCC { cond: CondCode::E, operand: dst.clone() });
+                    }
+                }
+                Ok(dst)
+            }
+            ExprKind::Binary { op: op @ (BinaryOp::And | BinaryOp::Or), left, right } => {
+                // Short circuit, the right operand is only evaluated when the left doesn't decide
+                // the result
+                let (short_circuit_cond, short_circuit_value) = match op {
+                    BinaryOp::And => (CondCode::E, 0),
+                    _ => (CondCode::NE, 1),
                 };
-                // Add return value to the func body
-                func_body.push(Box::new(Instr::Mov{src:ret_val, dst: Box::new(Instr::Register)}));
-                // Add the return instruction as the final instruction in the 
-                func_body.push(Box::new(Instr::Ret));
-                Ok(Box::new(Instr::FuncDef {name:name_identifier, instructions:func_body}))
-            }
-            Stmt::Return { value } => {
-                match *value{
-                    Expr::IntConstant { value } => {
-                        let mut series:Vec<Box<Instr>> = Vec::new();
-                        let ret_val = Box::new(Instr::Imm{value });
-                        // Move the return value to eax register
-                        series.push(Box::new(Instr::Mov{src: ret_val, dst: Box::new(Instr::Register)}));
-                        // Return from the function
-                        series.push(Box::new(Instr::Ret));
-                        Ok(Box::new(Instr::Series {instructions: series}))
-                        
+                let short_circuit = self.make_label("short_circuit");
+                let end = self.make_label("logical_end");
+                let dst = self.make_temporary(&ty);
+                for operand in [*left, *right] {
+                    self.assemble_condition_jump(operand, short_circuit_cond, short_circuit.clone(), instructions)?;
+                }
+                instructions.push(Instr::Mov { ty: AsmType::Longword, src: Operand::Imm { value: 1 - short_circuit_value }, dst: dst.clone() });
+                instructions.push(Instr::Jmp { target: end.clone() });
+                instructions.push(Instr::Label { name: short_circuit });
+                instructions.push(Instr::Mov { ty: AsmType::Longword, src: Operand::Imm { value: short_circuit_value }, dst: dst.clone() });
+                instructions.push(Instr::Label { name: end });
+                Ok(dst)
+            }
+            ExprKind::Binary { op: op @ (BinaryOp::Add | BinaryOp::Subtract), left, right } if ty.is_pointer() => {
+                // One operand is a pointer and the other a long index, which is only ever
+                // subtracted from the pointer
+                let left_is_pointer = left.ty().is_pointer();
+                let left = self.assemble_expr(*left, instructions)?;
+                let right = self.assemble_expr(*right, instructions)?;
+                let (pointer, index) = if left_is_pointer {(left, right)} else {(right, left)};
+                Ok(self.pointer_offset(pointer, index, &ty, op == BinaryOp::Subtract, instructions))
+            }
+            ExprKind::Binary { op: BinaryOp::Subtract, left, right } if left.ty().is_pointer() => {
+                // The difference of two pointers counts elements rather than bytes
+                let Type::Pointer(element) = left.ty() else { unreachable!() };
+                let element_size = Operand::Imm { value: element.size() as i64 };
+                let left = self.assemble_expr(*left, instructions)?;
+                let right = self.assemble_expr(*right, instructions)?;
+                let bytes = self.assemble_binary(BinaryOp::Subtract, &Type::Long, left, right, instructions);
+                Ok(self.assemble_binary(BinaryOp::Divide, &Type::Long, bytes, element_size, instructions))
+            }
+            ExprKind::Binary { op, left, right } => {
+                // Both operands have the same type, other than the count of a shift
+                let operand_ty = left.ty().clone();
+                let left = self.assemble_expr(*left, instructions)?;
+                let right = self.assemble_expr(*right, instructions)?;
+                Ok(self.assemble_binary(op, &operand_ty, left, right, instructions))
+            }
+            ExprKind::CompoundAssignment { op, left, right } => {
+                // The operation is done in the type the right operand was converted to, or the
+                // promoted left operand for shifts
+                let operation_ty = match op {
+                    BinaryOp::ShiftLeft | BinaryOp::ShiftRight => ty.promote(),
+                    _ => right.ty().clone(),
+                };
+                // The target is evaluated once, and used both as the left operand and destination
+                let dst = self.assemble_lvalue(*left, instructions)?;
+                let right = self.assemble_expr(*right, instructions)?;
+                let current = self.load(&dst, &ty, instructions);
+                let result = if ty.is_pointer() {
+                    self.pointer_offset(current, right, &ty, op == BinaryOp::Subtract, instructions)
+                } else {
+                    let left = self.assemble_conversion(current, &ty, &operation_ty, instructions);
+                    let result = self.assemble_binary(op, &operation_ty, left, right, instructions);
+                    self.assemble_conversion(result, &operation_ty, &ty, instructions)
+                };
+                self.store(&dst, &ty, result.clone(), instructions);
+                match dst {
+                    Lvalue::Operand(dst) => Ok(dst),
+                    Lvalue::Dereferenced(_) => Ok(result),
+                    Lvalue::BitField { .. } => Ok(self.load(&dst, &ty, instructions)),
+                }
+            }
+            ExprKind::Postfix { op, expr } => {
+                let dst = self.assemble_lvalue(*expr, instructions)?;
+                let current = self.load(&dst, &ty, instructions);
+                // Save the old value, which is the result of the expression
+                let old = self.make_temporary(&ty);
+                let expr_ty = ty.clone();
+                let ty = asm_type(&ty);
+                instructions.push(Instr::Mov { ty, src: current.clone(), dst: old.clone() });
+                let op = if op == BinaryOp::Add {asm::BinaryOp::Add} else {asm::BinaryOp::Sub};
+                // A pointer steps over a whole element
+                let one = match &expr_ty {
+                    Type::Pointer(element) => Operand::Imm { value: element.size() as i64 },
+                    _ if ty.is_floating() => self.floating_constant(Const::Double(1.0).convert(&expr_ty), ty.size()),
+                    _ => Operand::Imm { value: 1 },
+                };
+                instructions.push(Instr::Binary { op, ty, src: one, dst: current.clone() });
+                // A variable is updated in place, but an object reached through a pointer or a
+                // bit-field was loaded into a temporary
+                if !matches!(dst, Lvalue::Operand(_)) {
+                    self.store(&dst, &expr_ty, current, instructions);
+                }
+                Ok(old)
+            }
+        }
+    }
+
+    /// Convert an already evaluated value between arithmetic types, returning the operand holding
+    /// the converted value
+    fn assemble_conversion(&mut self, value: Operand, from: &Type, to: &Type, instructions: &mut Vec<Instr>) -> Operand{
+        if from == to {
+            return value;
+        }
+        match (from.is_floating(), to.is_floating()) {
+            (false, false) => self.assemble_integer_conversion(value, from, to, instructions),
+            (true, true) => {
+                let dst = self.make_temporary(to);
+                if *to == Type::Double {
+                    instructions.push(Instr::Cvtss2sd { src: value, dst: dst.clone() });
+                } else {
+                    instructions.push(Instr::Cvtsd2ss { src: value, dst: dst.clone() });
+                }
+                dst
+            }
+            (false, true) => self.assemble_integer_to_floating(value, from, to, instructions),
+            (true, false) => self.assemble_floating_to_integer(value, from, to, instructions),
+        }
+    }
+
+    fn assemble_integer_conversion(&mut self, value: Operand, from: &Type, to: &Type, instructions: &mut Vec<Instr>) -> Operand{
+        // Conversions between types of the same width keep the bits unchanged
+        if from.size() == to.size() {
+            return value;
+        }
+        let dst = self.make_temporary(to);
+        let (src_ty, dst_ty) = (asm_type(from), asm_type(to));
+        if from.size() > to.size() {
+            // Narrowing keeps the low bytes, which is what a narrower mov reads
+            instructions.push(Instr::Mov { ty: dst_ty, src: value, dst: dst.clone() });
+        } else if from.is_signed() {
+            instructions.push(Instr::Movsx { src_ty, dst_ty, src: value, dst: dst.clone() });
+        } else {
+            instructions.push(Instr::MovZeroExtend { src_ty, dst_ty, src: value, dst: dst.clone() });
+        }
+        dst
+    }
+
+    /// Convert an integer to a floating type. cvtsi2sd only converts signed 32 and 64 bit values,
+    /// so other types are first extended to one of those.
+    fn assemble_integer_to_floating(&mut self, value: Operand, from: &Type, to: &Type, instructions: &mut Vec<Instr>) -> Operand{
+        let wider = match from {
+            _ if from.size() < 4 => Type::Int,
+            Type::UInt => Type::Long,
+            Type::ULong => return self.assemble_unsigned_long_to_floating(value, to, instructions),
+            _ => {
+                let dst = self.make_temporary(to);
+                instructions.push(Instr::Cvtsi2sd { src_ty: asm_type(from), dst_ty: asm_type(to), src: value, dst: dst.clone() });
+                return dst;
+            }
+        };
+        let value = self.assemble_integer_conversion(value, from, &wider, instructions);
+        self.assemble_integer_to_floating(value, &wider, to, instructions)
+    }
+
+    /// Convert an unsigned long to a floating type. Values too large to be signed are halved,
+    /// keeping the lowest bit so they round correctly, converted and then doubled.
+    fn assemble_unsigned_long_to_floating(&mut self, value: Operand, to: &Type, instructions: &mut Vec<Instr>) -> Operand{
+        let dst = self.make_temporary(to);
+        let dst_ty = asm_type(to);
+        let large = self.make_label("ulong_large");
+        let end = self.make_label("ulong_end");
+        let (ax, dx) = (Operand::Register { reg: Reg::AX }, Operand::Register { reg: Reg::DX });
+        instructions.push(Instr::Cmp { ty: AsmType::Quadword, src: Operand::Imm { value: 0 }, dst: value.clone() });
+        instructions.push(Instr::JmpCC { cond: CondCode::L, target: large.clone() });
+        instructions.push(Instr::Cvtsi2sd { src_ty: AsmType::Quadword, dst_ty, src: value.clone(), dst: dst.clone() });
+        instructions.push(Instr::Jmp { target: end.clone() });
+        instructions.push(Instr::Label { name: large });
+        instructions.push(Instr::Mov { ty: AsmType::Quadword, src: value, dst: ax.clone() });
+        instructions.push(Instr::Mov { ty: AsmType::Quadword, src: ax.clone(), dst: dx.clone() });
+        instructions.push(Instr::Binary { op: asm::BinaryOp::Shr, ty: AsmType::Quadword, src: Operand::Imm { value: 1 }, dst: dx.clone() });
+        instructions.push(Instr::Binary { op: asm::BinaryOp::And, ty: AsmType::Quadword, src: Operand::Imm { value: 1 }, dst: ax.clone() });
+        instructions.push(Instr::Binary { op: asm::BinaryOp::Or, ty: AsmType::Quadword, src: ax, dst: dx.clone() });
+        instructions.push(Instr::Cvtsi2sd { src_ty: AsmType::Quadword, dst_ty, src: dx, dst: dst.clone() });
+        instructions.push(Instr::Binary { op: asm::BinaryOp::Add, ty: dst_ty, src: dst.clone(), dst: dst.clone() });
+        instructions.push(Instr::Label { name: end });
+        dst
+    }
+
+    /// Convert a floating value to an integer type, truncating towards zero. cvttsd2si only gives
+    /// signed 32 and 64 bit values, so other types are converted through one of those.
+    fn assemble_floating_to_integer(&mut self, value: Operand, from: &Type, to: &Type, instructions: &mut Vec<Instr>) -> Operand{
+        let wider = match to {
+            _ if to.size() < 4 => Type::Int,
+            Type::UInt => Type::Long,
+            Type::ULong => return self.assemble_floating_to_unsigned_long(value, from, instructions),
+            _ => {
+                let dst = self.make_temporary(to);
+                instructions.push(Instr::Cvttsd2si { src_ty: asm_type(from), dst_ty: asm_type(to), src: value, dst: dst.clone() });
+                return dst;
+            }
+        };
+        let value = self.assemble_floating_to_integer(value, from, &wider, instructions);
+        self.assemble_integer_conversion(value, &wider, to, instructions)
+    }
+
+    /// Convert a floating value to an unsigned long. Values too large to be signed have 2^63
+    /// subtracted before converting, which is added back afterwards.
+    fn assemble_floating_to_unsigned_long(&mut self, value: Operand, from: &Type, instructions: &mut Vec<Instr>) -> Operand{
+        let dst = self.make_temporary(&Type::ULong);
+        let src_ty = asm_type(from);
+        let large = self.make_label("ulong_large");
+        let end = self.make_label("ulong_end");
+        let limit = self.floating_constant(Const::Double(9223372036854775808.0).convert(from), src_ty.size());
+        instructions.push(Instr::Cmp { ty: src_ty, src: limit.clone(), dst: value.clone() });
+        instructions.push(Instr::JmpCC { cond: CondCode::AE, target: large.clone() });
+        instructions.push(Instr::Cvttsd2si { src_ty, dst_ty: AsmType::Quadword, src: value.clone(), dst: dst.clone() });
+        instructions.push(Instr::Jmp { target: end.clone() });
+        instructions.push(Instr::Label { name: large });
+        let reduced = self.make_temporary(from);
+        instructions.push(Instr::Mov { ty: src_ty, src: value, dst: reduced.clone() });
+        instructions.push(Instr::Binary { op: asm::BinaryOp::Sub, ty: src_ty, src: limit, dst: reduced.clone() });
+        instructions.push(Instr::Cvttsd2si { src_ty, dst_ty: AsmType::Quadword, src: reduced, dst: dst.clone() });
+        instructions.push(Instr::Binary { op: asm::BinaryOp::Add, ty: AsmType::Quadword, src: Operand::Imm { value: i64::MIN }, dst: dst.clone() });
+        instructions.push(Instr::Label { name: end });
+        dst
+    }
+
+    /// Load the address of a function. One defined in this file is addressed relative to the
+    /// instruction pointer, any other is read from the global offset table, since it may be in a
+    /// shared library too far away to reach that way.
+    fn function_address(&mut self, name: String, ty: &Type, instructions: &mut Vec<Instr>) -> Operand{
+        let dst = self.make_temporary(ty);
+        if let Some(Symbol { attrs: IdentAttrs::Function { defined: true, .. }, .. }) = self.symbols.get(&name) {
+            instructions.push(Instr::Lea { src: Operand::Data { name }, dst: dst.clone() });
+        } else {
+            instructions.push(Instr::Mov { ty: AsmType::Quadword, src: Operand::Got { name }, dst: dst.clone() });
+        }
+        dst
+    }
+
+    /// Generate a call following the System V calling convention, returning the temporary holding
+    /// the result. A function called by name is called directly, anything else through the
+    /// pointer the function expression evaluates to.
+    fn assemble_call(&mut self, function: Expr, args: Vec<Expr>, ret: &Type, instructions: &mut Vec<Instr>) -> Result<Operand, AssemblerError>{
+        let variadic = matches!(function.ty(), Type::Pointer(inner) if matches!(**inner, Type::Function { variadic: true, .. }));
+        let name = match &function.kind {
+            ExprKind::AddressOf { expr } if matches!(expr.ty(), Type::Function { .. }) => match &expr.kind {
+                ExprKind::Identifier { value } => Some(value.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        let call = match name {
+            Some(name) => Instr::Call { name },
+            None => Instr::CallIndirect { operand: self.assemble_expr(function, instructions)? },
+        };
+        // Evaluate every argument before loading any registers, since evaluating one argument
+        // may use the registers another is passed in
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            let ty = arg.ty().clone();
+            values.push((self.assemble_expr(arg, instructions)?, ty));
+        }
+        // A structure returned in memory is written to a temporary whose address is passed in di
+        let result = is_memory_class(ret).then(|| self.make_temporary(ret));
+        let integer_registers = if result.is_some() {&ARGUMENT_REGISTERS[1..]} else {&ARGUMENT_REGISTERS[..]};
+        let types: Vec<Type> = values.iter().map(|(_, ty)| ty.clone()).collect();
+        let mut register_values = Vec::new();
+        let mut stack_values = Vec::new();
+        for ((value, ty), regs) in values.into_iter().zip(argument_locations(&types, integer_registers)) {
+            match regs {
+                Some(regs) => register_values.push((value, ty, regs)),
+                None => stack_values.push((value, ty)),
+            }
+        }
+        // The stack must be 16 byte aligned at the call, each stack argument takes a multiple of
+        // 8 bytes
+        let stack_bytes = 8 * stack_values.iter().map(|(_, ty)| ty.size().div_ceil(8)).sum::<usize>() as i32;
+        let padding = if stack_bytes % 16 != 0 {8} else {0};
+        if padding != 0 {
+            instructions.push(Instr::AllocateStack { bytes: padding });
+        }
+        // Stack arguments are pushed last to first, so the first ends up nearest the callee. This
+        // is done before loading any registers, since pushing a structure goes through ax and cx.
+        let stack_bytes = stack_bytes + padding;
+        for (value, ty) in stack_values.into_iter().rev() {
+            if let Type::Struct(_) = ty {
+                self.push_struct(value, &ty, instructions);
+            // Pushing reads 8 bytes, which may run past the end of a narrower value in memory
+            } else if ty.size() < 8 && value.is_memory() {
+                let ax = Operand::Register { reg: Reg::AX };
+                // A float is moved as its bits, since ax isn't an xmm register
+                let mov = match ty {
+                    Type::Float => Instr::Mov { ty: AsmType::Longword, src: value, dst: ax.clone() },
+                    _ => extend_argument(value, &ty, ax.clone()),
+                };
+                instructions.push(mov);
+                instructions.push(Instr::Push { operand: ax });
+            } else {
+                instructions.push(Instr::Push { operand: value });
+            }
+        }
+        let sse_count = register_values.iter().flat_map(|(_, _, regs)| regs).filter(|reg| SSE_ARGUMENT_REGISTERS.contains(reg)).count();
+        for (value, ty, regs) in register_values {
+            if let Type::Struct(_) = ty {
+                self.load_eightbytes(&Lvalue::Operand(value), &ty, &regs, Reg::AX, instructions);
+            } else {
+                instructions.push(extend_argument(value, &ty, Operand::Register { reg: regs[0] }));
+            }
+        }
+        if let Some(result) = &result {
+            instructions.push(Instr::Lea { src: result.clone(), dst: Operand::Register { reg: Reg::DI } });
+        }
+        // A variadic callee expects %al to bound the number of vector registers holding
+        // arguments
+        if variadic {
+            instructions.push(Instr::Mov { ty: AsmType::Longword, src: Operand::Imm { value: sse_count as i64 }, dst: Operand::Register { reg: Reg::AX } });
+        }
+        instructions.push(call);
+        if stack_bytes != 0 {
+            instructions.push(Instr::DeallocateStack { bytes: stack_bytes });
+        }
+        if *ret == Type::Void {
+            return Ok(VOID);
+        }
+        if let Some(result) = result {
+            return Ok(result);
+        }
+        let dst = self.make_temporary(ret);
+        if let Type::Struct(_) = ret {
+            self.store_eightbytes(&return_registers(ret), ret, &Lvalue::Operand(dst.clone()), Reg::CX, instructions);
+        } else {
+            let reg = if ret.is_floating() {Reg::XMM0} else {Reg::AX};
+            instructions.push(Instr::Mov { ty: asm_type(ret), src: Operand::Register { reg }, dst: dst.clone() });
+        }
+        Ok(dst)
+    }
+
+    /// Start a walk through the variadic arguments, filling in the va_list ap points to, which is
+    /// addressed through ax
+    fn assemble_va_start(&mut self, ap: Operand, instructions: &mut Vec<Instr>){
+        let frame = self.variadic.clone().expect("the type checker only allows va_start in variadic functions");
+        let (ax, dx) = (Operand::Register { reg: Reg::AX }, Operand::Register { reg: Reg::DX });
+        instructions.push(Instr::Mov { ty: AsmType::Quadword, src: ap, dst: ax });
+        for (offset, value) in [(0, frame.gp_offset), (4, frame.fp_offset)] {
+            let dst = Operand::Memory { reg: Reg::AX, offset };
+            instructions.push(Instr::Mov { ty: AsmType::Longword, src: Operand::Imm { value: value as i64 }, dst });
+        }
+        for (offset, area) in [(8, Operand::Stack { offset: frame.overflow_offset }), (16, frame.save_area)] {
+            instructions.push(Instr::Lea { src: area, dst: dx.clone() });
+            instructions.push(Instr::Mov { ty: AsmType::Quadword, src: dx.clone(), dst: Operand::Memory { reg: Reg::AX, offset } });
+        }
+    }
+
+    /// Fetch the next variadic argument, of type ty, through the va_list ap points to. An
+    /// argument whose eightbytes all fit in the registers left was passed in them, and is read
+    /// from the register save area, anything else is read from the stack. The va_list is
+    /// addressed through ax and the argument through cx.
+    fn assemble_va_arg(&mut self, ap: Operand, ty: &Type, instructions: &mut Vec<Instr>) -> Operand{
+        let dst = self.make_temporary(ty);
+        let (ax, cx, dx) = (Operand::Register { reg: Reg::AX }, Operand::Register { reg: Reg::CX }, Operand::Register { reg: Reg::DX });
+        let classes = match ty {
+            Type::Struct(_) => classify(ty),
+            ty if ty.is_floating() => vec![Class::Sse],
+            _ => vec![Class::Integer],
+        };
+        let stack = self.make_label("va_arg_stack");
+        let end = self.make_label("va_arg_end");
+        instructions.push(Instr::Mov { ty: AsmType::Quadword, src: ap, dst: ax.clone() });
+        if !classes.contains(&Class::Memory) {
+            let integer_count = classes.iter().filter(|class| **class == Class::Integer).count();
+            let sse_count = classes.len() - integer_count;
+            for (field, count, limit) in [(0, 8 * integer_count, GP_SAVE_SIZE), (4, 16 * sse_count, REGISTER_SAVE_AREA_SIZE)] {
+                if count != 0 {
+                    let offset = Operand::Memory { reg: Reg::AX, offset: field };
+                    instructions.push(Instr::Cmp { ty: AsmType::Longword, src: Operand::Imm { value: (limit - count) as i64 }, dst: offset });
+                    instructions.push(Instr::JmpCC { cond: CondCode::A, target: stack.clone() });
+                }
+            }
+            // Each eightbyte is read from the save area at the offset for its class, moving the
+            // offset on past it
+            let (mut integer_regs, mut sse_regs) = ([Reg::SI, Reg::DI].into_iter(), [Reg::XMM0, Reg::XMM1].into_iter());
+            let mut regs = Vec::new();
+            for class in &classes {
+                let (field, step, reg) = match class {
+                    Class::Integer => (0, 8, integer_regs.next()),
+                    _ => (4, 16, sse_regs.next()),
+                };
+                let offset = Operand::Memory { reg: Reg::AX, offset: field };
+                instructions.push(Instr::Mov { ty: AsmType::Longword, src: offset.clone(), dst: dx.clone() });
+                instructions.push(Instr::Mov { ty: AsmType::Quadword, src: Operand::Memory { reg: Reg::AX, offset: 16 }, dst: cx.clone() });
+                instructions.push(Instr::Binary { op: asm::BinaryOp::Add, ty: AsmType::Quadword, src: dx.clone(), dst: cx.clone() });
+                let src = Operand::Memory { reg: Reg::CX, offset: 0 };
+                match (ty, reg) {
+                    (Type::Struct(_), Some(reg)) => {
+                        let reg_ty = if *class == Class::Sse {AsmType::Double} else {AsmType::Quadword};
+                        instructions.push(Instr::Mov { ty: reg_ty, src, dst: Operand::Register { reg } });
+                        regs.push(reg);
                     }
-                    _ => {Err(AssemblerError::InvalidReturn)}
+                    _ => instructions.push(Instr::Mov { ty: asm_type(ty), src, dst: dst.clone() }),
+                }
+                instructions.push(Instr::Binary { op: asm::BinaryOp::Add, ty: AsmType::Longword, src: Operand::Imm { value: step }, dst: offset });
+            }
+            if let Type::Struct(_) = ty {
+                self.store_eightbytes(&regs, ty, &Lvalue::Operand(dst.clone()), Reg::CX, instructions);
+            }
+            instructions.push(Instr::Jmp { target: end.clone() });
+        }
+        // Arguments on the stack each take a multiple of 8 bytes
+        instructions.push(Instr::Label { name: stack });
+        let overflow_area = Operand::Memory { reg: Reg::AX, offset: 8 };
+        instructions.push(Instr::Mov { ty: AsmType::Quadword, src: overflow_area.clone(), dst: cx.clone() });
+        let size = 8 * ty.size().div_ceil(8);
+        instructions.push(Instr::Binary { op: asm::BinaryOp::Add, ty: AsmType::Quadword, src: Operand::Imm { value: size as i64 }, dst: overflow_area });
+        if let Type::Struct(_) = ty {
+            self.copy_object(&Lvalue::Dereferenced(cx), &Lvalue::Operand(dst.clone()), ty.size(), instructions);
+        } else {
+            instructions.push(Instr::Mov { ty: asm_type(ty), src: Operand::Memory { reg: Reg::CX, offset: 0 }, dst: dst.clone() });
+        }
+        instructions.push(Instr::Label { name: end });
+        dst
+    }
+
+    /// Push a structure passed on the stack, last eightbyte first, addressing it through cx
+    fn push_struct(&mut self, value: Operand, ty: &Type, instructions: &mut Vec<Instr>){
+        self.load_address(&Lvalue::Operand(value), Reg::CX, instructions);
+        for offset in (0..ty.size()).step_by(8).rev() {
+            let src = Operand::Memory { reg: Reg::CX, offset: offset as i32 };
+            if ty.size() - offset >= 8 {
+                instructions.push(Instr::Push { operand: src });
+            } else {
+                // Pushing the tail directly would read past the end of the structure
+                load_eightbyte(Reg::CX, offset, ty.size() - offset, Class::Integer, Reg::AX, instructions);
+                instructions.push(Instr::Push { operand: Operand::Register { reg: Reg::AX } });
+            }
+        }
+    }
+
+    /// Load the eightbytes of a structure into the registers it is passed or returned in, through
+    /// its address in base
+    fn load_eightbytes(&mut self, src: &Lvalue, ty: &Type, regs: &[Reg], base: Reg, instructions: &mut Vec<Instr>){
+        self.load_address(src, base, instructions);
+        for (i, (class, reg)) in classify(ty).into_iter().zip(regs).enumerate() {
+            load_eightbyte(base, 8 * i, (ty.size() - 8 * i).min(8), class, *reg, instructions);
+        }
+    }
+
+    /// Store the eightbytes of a structure from the registers it was passed or returned in,
+    /// through the address of dst in base
+    fn store_eightbytes(&mut self, regs: &[Reg], ty: &Type, dst: &Lvalue, base: Reg, instructions: &mut Vec<Instr>){
+        self.load_address(dst, base, instructions);
+        for (i, (class, reg)) in classify(ty).into_iter().zip(regs).enumerate() {
+            let (offset, size) = (8 * i, (ty.size() - 8 * i).min(8));
+            if class == Class::Sse {
+                let ty = if size == 4 {AsmType::Float} else {AsmType::Double};
+                instructions.push(Instr::Mov { ty, src: Operand::Register { reg: *reg }, dst: Operand::Memory { reg: base, offset: offset as i32 } });
+                continue;
+            }
+            // Write the low bytes and shift them out, so the tail of a structure that isn't a
+            // multiple of 8 bytes isn't written past
+            let mut done = 0;
+            while done < size {
+                let ty = match size - done {
+                    8.. => AsmType::Quadword,
+                    4.. => AsmType::Longword,
+                    2.. => AsmType::Word,
+                    _ => AsmType::Byte,
+                };
+                let dst = Operand::Memory { reg: base, offset: (offset + done) as i32 };
+                instructions.push(Instr::Mov { ty, src: Operand::Register { reg: *reg }, dst });
+                done += ty.size();
+                if done < size {
+                    let count = Operand::Imm { value: 8 * ty.size() as i64 };
+                    instructions.push(Instr::Binary { op: asm::BinaryOp::Shr, ty: AsmType::Quadword, src: count, dst: Operand::Register { reg: *reg } });
+                }
+            }
+        }
+    }
+
+    /// Dispatch on a switch value by indexing a table of case labels with its offset from the
+    /// smallest case, cases must be sorted and non-empty
+    fn assemble_jump_table(&mut self, value: Operand, ty: &Type, cases: &[(i64, String)], fallback: String, label: &str, instructions: &mut Vec<Instr>){
+        let min = cases[0].0;
+        let max = cases[cases.len() - 1].0;
+        let mut targets = vec![fallback.clone(); max.wrapping_sub(min) as usize + 1];
+        for (case_value, case_label) in cases {
+            targets[case_value.wrapping_sub(min) as usize] = case_label.clone();
+        }
+        // Values below the smallest case wrap around to large unsigned offsets, so a single
+        // unsigned comparison catches values on either side of the table. A 32 bit subtraction
+        // clears the upper half of the register, so the whole register holds the offset.
+        let ty = asm_type(ty);
+        let index = Operand::Register { reg: Reg::AX };
+        instructions.push(Instr::Mov { ty, src: value, dst: index.clone() });
+        instructions.push(Instr::Binary { op: asm::BinaryOp::Sub, ty, src: Operand::Imm { value: min }, dst: index.clone() });
+        instructions.push(Instr::Cmp { ty, src: Operand::Imm { value: max.wrapping_sub(min) }, dst: index });
+        instructions.push(Instr::JmpCC { cond: CondCode::A, target: fallback });
+        instructions.push(Instr::JumpTable { index: Reg::AX, name: format!("table.{}", label), targets });
+    }
+
+    /// Dispatch on a switch value with a binary search over the sorted cases
+    fn assemble_case_tree(&mut self, value: &Operand, ty: &Type, cases: &[(i64, String)], fallback: &str, instructions: &mut Vec<Instr>){
+        let asm_ty = asm_type(ty);
+        if cases.len() <= CASE_TREE_LEAF_SIZE {
+            for (case_value, case_label) in cases {
+                instructions.push(Instr::Cmp { ty: asm_ty, src: Operand::Imm { value: *case_value }, dst: value.clone() });
+                instructions.push(Instr::JmpCC { cond: CondCode::E, target: case_label.clone() });
+            }
+            instructions.push(Instr::Jmp { target: fallback.to_string() });
+            return;
+        }
+        let middle = cases.len() / 2;
+        let (middle_value, middle_label) = &cases[middle];
+        let lower = self.make_label("case_lower");
+        let less = if ty.is_signed() {CondCode::L} else {CondCode::B};
+        instructions.push(Instr::Cmp { ty: asm_ty, src: Operand::Imm { value: *middle_value }, dst: value.clone() });
+        instructions.push(Instr::JmpCC { cond: CondCode::E, target: middle_label.clone() });
+        instructions.push(Instr::JmpCC { cond: less, target: lower.clone() });
+        self.assemble_case_tree(value, ty, &cases[middle + 1..], fallback, instructions);
+        instructions.push(Instr::Label { name: lower });
+        self.assemble_case_tree(value, ty, &cases[..middle], fallback, instructions);
+    }
+
+    /// Evaluate condition, jumping to target if comparing it to zero satisfies cond
+    fn assemble_condition_jump(&mut self, condition: Expr, cond: CondCode, target: String, instructions: &mut Vec<Instr>) -> Result<(), AssemblerError>{
+        let ty = condition.ty().clone();
+        let mut condition = self.assemble_expr(condition, instructions)?;
+        let mut asm_ty = asm_type(&ty);
+        // A floating condition is true unless it compares equal to zero, so NaNs are true
+        if ty.is_floating() {
+            let zero = self.floating_constant(Const::Double(0.0).convert(&ty), ty.size());
+            condition = self.assemble_binary(BinaryOp::NotEqual, &ty, condition, zero, instructions);
+            asm_ty = AsmType::Longword;
+        }
+        instructions.push(Instr::Cmp { ty: asm_ty, src: Operand::Imm { value: 0 }, dst: condition });
+        instructions.push(Instr::JmpCC { cond, target });
+        Ok(())
+    }
+
+    /// Generate the instructions computing an lvalue, returning where the object it designates is
+    fn assemble_lvalue(&mut self, expr: Expr, instructions: &mut Vec<Instr>) -> Result<Lvalue, AssemblerError>{
+        match expr.kind {
+            ExprKind::Identifier { value } => Ok(Lvalue::Operand(Operand::Pseudo { name: value })),
+            ExprKind::Dereference { expr } => Ok(Lvalue::Dereferenced(self.assemble_expr(*expr, instructions)?)),
+            ExprKind::String { value } => Ok(Lvalue::Operand(Operand::Data { name: self.string_constant(value) })),
+            ExprKind::Subscript { array, index } => Ok(Lvalue::Dereferenced(self.assemble_subscript(*array, *index, instructions)?)),
+            ExprKind::Member { expr, member } => self.assemble_member(*expr, &member, instructions),
+            _ => Err(AssemblerError::NotAssignable),
+        }
+    }
+
+    /// Initialize an object of type ty from an expression already converted to its type, or copy a
+    /// string literal into a char array
+    fn assemble_initializer(&mut self, init: Expr, ty: &Type, object: &Lvalue, instructions: &mut Vec<Instr>) -> Result<(), AssemblerError>{
+        if let (ExprKind::String { value }, Type::Array { .. }) = (&init.kind, ty) {
+            self.assemble_string_copy(value, ty.size(), object, instructions);
+            return Ok(());
+        }
+        let value_ty = init.ty().clone();
+        let value = self.assemble_expr(init, instructions)?;
+        self.store(object, &value_ty, value, instructions);
+        Ok(())
+    }
+
+    /// Initialize a local variable of type ty from the elements of a brace enclosed initializer,
+    /// after zeroing every byte they leave out. The storage units of bit-fields are zeroed too,
+    /// since the bit-fields are merged into them.
+    fn assemble_compound_initializer(&mut self, elements: Vec<InitElement>, ty: &Type, variable: &Lvalue, instructions: &mut Vec<Instr>) -> Result<(), AssemblerError>{
+        let mut initialized = vec![false; ty.size()];
+        for element in elements.iter().filter(|element| element.bits.is_none()) {
+            initialized[element.offset..element.offset + element.ty.size()].fill(true);
+        }
+        self.load_address(variable, Reg::AX, instructions);
+        let mut offset = 0;
+        while offset < initialized.len() {
+            let run = initialized[offset..].iter().take_while(|&&done| done == initialized[offset]).count();
+            if !initialized[offset] {
+                store_bytes(&vec![0; run], offset, instructions);
+            }
+            offset += run;
+        }
+        for element in elements {
+            self.load_address(variable, Reg::AX, instructions);
+            let pointer = self.make_temporary(&Type::Pointer(Box::new(element.ty.clone())));
+            instructions.push(Instr::Lea { src: Operand::Memory { reg: Reg::AX, offset: element.offset as i32 }, dst: pointer.clone() });
+            let object = match element.bits {
+                Some(bits) => Lvalue::BitField { pointer, unit: element.ty.clone(), bits },
+                None => Lvalue::Dereferenced(pointer),
+            };
+            self.assemble_initializer(element.expr, &element.ty, &object, instructions)?;
+        }
+        Ok(())
+    }
+
+    /// Copy a string into the char array dst of the given size, truncating it or padding it with
+    /// zeros to fill the array
+    fn assemble_string_copy(&mut self, value: &[u8], size: usize, dst: &Lvalue, instructions: &mut Vec<Instr>){
+        let mut bytes = value.to_vec();
+        bytes.resize(size, 0);
+        self.load_address(dst, Reg::AX, instructions);
+        store_bytes(&bytes, 0, instructions);
+    }
+
+    /// Compute the address of the element a subscript designates. The typechecker leaves the
+    /// pointer operand as the array and converts the index to a long.
+    fn assemble_subscript(&mut self, array: Expr, index: Expr, instructions: &mut Vec<Instr>) -> Result<Operand, AssemblerError>{
+        let pointer_ty = array.ty().clone();
+        let pointer = self.assemble_expr(array, instructions)?;
+        let index = self.assemble_expr(index, instructions)?;
+        Ok(self.pointer_offset(pointer, index, &pointer_ty, false, instructions))
+    }
+
+    /// Compute the address of a member of the structure expr evaluates to, or of the storage unit
+    /// holding a bit-field. A structure which isn't an lvalue, such as the result of an
+    /// assignment, is held in a temporary.
+    fn assemble_member(&mut self, expr: Expr, member: &str, instructions: &mut Vec<Instr>) -> Result<Lvalue, AssemblerError>{
+        let Type::Struct(struct_type) = expr.ty() else {
+            unreachable!("member access on the non structure type {}", expr.ty());
+        };
+        let member = struct_type.member(member).expect("members have been type checked");
+        let object = if expr.is_lvalue() {
+            self.assemble_lvalue(expr, instructions)?
+        } else {
+            Lvalue::Operand(self.assemble_expr(expr, instructions)?)
+        };
+        self.load_address(&object, Reg::AX, instructions);
+        let dst = self.make_temporary(&Type::Pointer(Box::new(member.ty.clone())));
+        instructions.push(Instr::Lea { src: Operand::Memory { reg: Reg::AX, offset: member.offset as i32 }, dst: dst.clone() });
+        Ok(match member.bits {
+            Some(bits) => Lvalue::BitField { pointer: dst, unit: member.ty, bits },
+            None => Lvalue::Dereferenced(dst),
+        })
+    }
+
+    /// Load the address of the object an lvalue designates into a register
+    fn load_address(&mut self, lvalue: &Lvalue, reg: Reg, instructions: &mut Vec<Instr>){
+        let dst = Operand::Register { reg };
+        match lvalue {
+            Lvalue::Operand(object) => instructions.push(Instr::Lea { src: object.clone(), dst }),
+            Lvalue::Dereferenced(pointer) => instructions.push(Instr::Mov { ty: AsmType::Quadword, src: pointer.clone(), dst }),
+            Lvalue::BitField { .. } => unreachable!("bit-fields have no address"),
+        }
+    }
+
+    /// Copy a structure, which is too big to be an operand, from one object to another. It is
+    /// moved eight, four, two or one bytes at a time, through its addresses in si and di.
+    fn copy_object(&mut self, src: &Lvalue, dst: &Lvalue, size: usize, instructions: &mut Vec<Instr>){
+        self.load_address(src, Reg::SI, instructions);
+        self.load_address(dst, Reg::DI, instructions);
+        let mut offset = 0;
+        while offset < size {
+            let ty = match size - offset {
+                8.. => AsmType::Quadword,
+                4.. => AsmType::Longword,
+                2.. => AsmType::Word,
+                _ => AsmType::Byte,
+            };
+            let (src, dst) = (Operand::Memory { reg: Reg::SI, offset: offset as i32 }, Operand::Memory { reg: Reg::DI, offset: offset as i32 });
+            instructions.push(Instr::Mov { ty, src, dst });
+            offset += ty.size();
+        }
+    }
+
+    /// Read the value of an lvalue of type ty. Objects reached through a pointer are addressed
+    /// through ax, so the pointer is only loaded right before it is used.
+    fn load(&mut self, lvalue: &Lvalue, ty: &Type, instructions: &mut Vec<Instr>) -> Operand{
+        match lvalue {
+            Lvalue::Operand(operand) => operand.clone(),
+            Lvalue::Dereferenced(_) if matches!(ty, Type::Struct(_)) => {
+                let dst = self.make_temporary(ty);
+                self.copy_object(lvalue, &Lvalue::Operand(dst.clone()), ty.size(), instructions);
+                dst
+            }
+            Lvalue::Dereferenced(pointer) => {
+                let dst = self.make_temporary(ty);
+                instructions.push(Instr::Mov { ty: AsmType::Quadword, src: pointer.clone(), dst: Operand::Register { reg: Reg::AX } });
+                instructions.push(Instr::Mov { ty: asm_type(ty), src: Operand::Memory { reg: Reg::AX, offset: 0 }, dst: dst.clone() });
+                dst
+            }
+            Lvalue::BitField { pointer, unit, bits } => {
+                // Shift the bit-field to the top of dx and back down, which extends its sign if
+                // its type is signed
+                let dst = self.make_temporary(ty);
+                let dx = Operand::Register { reg: Reg::DX };
+                instructions.push(Instr::Mov { ty: AsmType::Quadword, src: pointer.clone(), dst: Operand::Register { reg: Reg::AX } });
+                load_storage_unit(unit, Reg::DX, instructions);
+                let above = 64 - bits.offset - bits.width;
+                if above != 0 {
+                    instructions.push(Instr::Binary { op: asm::BinaryOp::Sal, ty: AsmType::Quadword, src: Operand::Imm { value: above as i64 }, dst: dx.clone() });
                 }
+                let op = if unit.is_signed() {asm::BinaryOp::Sar} else {asm::BinaryOp::Shr};
+                instructions.push(Instr::Binary { op, ty: AsmType::Quadword, src: Operand::Imm { value: 64 - bits.width as i64 }, dst: dx.clone() });
+                instructions.push(Instr::Mov { ty: asm_type(ty), src: dx, dst: dst.clone() });
+                dst
+            }
+        }
+    }
+
+    /// Write a value of type ty to an lvalue
+    fn store(&mut self, lvalue: &Lvalue, ty: &Type, value: Operand, instructions: &mut Vec<Instr>){
+        if let Type::Struct(_) = ty {
+            self.copy_object(&Lvalue::Operand(value), lvalue, ty.size(), instructions);
+            return;
+        }
+        match lvalue {
+            Lvalue::Operand(operand) => {
+                instructions.push(Instr::Mov { ty: asm_type(ty), src: value, dst: operand.clone() });
+            }
+            Lvalue::Dereferenced(pointer) => {
+                instructions.push(Instr::Mov { ty: AsmType::Quadword, src: pointer.clone(), dst: Operand::Register { reg: Reg::AX } });
+                instructions.push(Instr::Mov { ty: asm_type(ty), src: value, dst: Operand::Memory { reg: Reg::AX, offset: 0 } });
+            }
+            Lvalue::BitField { pointer, unit, bits } => {
+                // Mask the value to the width of the bit-field in dx, and merge it with the other
+                // bits of the storage unit in cx
+                let (cx, dx) = (Operand::Register { reg: Reg::CX }, Operand::Register { reg: Reg::DX });
+                let mask = (u64::MAX >> (64 - bits.width)) as i64;
+                instructions.push(Instr::Mov { ty: AsmType::Quadword, src: pointer.clone(), dst: Operand::Register { reg: Reg::AX } });
+                instructions.push(Instr::Mov { ty: asm_type(ty), src: value, dst: dx.clone() });
+                instructions.push(Instr::Binary { op: asm::BinaryOp::And, ty: AsmType::Quadword, src: Operand::Imm { value: mask }, dst: dx.clone() });
+                if bits.offset != 0 {
+                    instructions.push(Instr::Binary { op: asm::BinaryOp::Sal, ty: AsmType::Quadword, src: Operand::Imm { value: bits.offset as i64 }, dst: dx.clone() });
+                }
+                load_storage_unit(unit, Reg::CX, instructions);
+                instructions.push(Instr::Binary { op: asm::BinaryOp::And, ty: AsmType::Quadword, src: Operand::Imm { value: !(mask << bits.offset) }, dst: cx.clone() });
+                instructions.push(Instr::Binary { op: asm::BinaryOp::Or, ty: AsmType::Quadword, src: dx, dst: cx.clone() });
+                instructions.push(Instr::Mov { ty: asm_type(unit), src: cx, dst: Operand::Memory { reg: Reg::AX, offset: 0 } });
+            }
+        }
+    }
+
+    /// Compute the address `index` elements past (or before, if negate is set) an already
+    /// evaluated pointer of type pointer_ty, returning the temporary holding it. The index is a
+    /// long, scaled by the element size with the addressing mode where possible.
+    fn pointer_offset(&mut self, pointer: Operand, index: Operand, pointer_ty: &Type, negate: bool, instructions: &mut Vec<Instr>) -> Operand{
+        let Type::Pointer(element) = pointer_ty else {
+            unreachable!("pointer arithmetic on the non pointer type {}", pointer_ty);
+        };
+        let scale = element.size() as i64;
+        let dst = self.make_temporary(pointer_ty);
+        instructions.push(Instr::Mov { ty: AsmType::Quadword, src: pointer, dst: Operand::Register { reg: Reg::AX } });
+        // A constant index folds into the displacement, if it fits
+        if let Operand::Imm { value } = index {
+            let offset = value.wrapping_mul(if negate {-scale} else {scale});
+            if let Ok(offset) = i32::try_from(offset) {
+                instructions.push(Instr::Lea { src: Operand::Memory { reg: Reg::AX, offset }, dst: dst.clone() });
+                return dst;
+            }
+        }
+        let index_reg = Operand::Register { reg: Reg::DX };
+        instructions.push(Instr::Mov { ty: AsmType::Quadword, src: index, dst: index_reg.clone() });
+        if negate {
+            instructions.push(Instr::Unary { op: asm::UnaryOp::Neg, ty: AsmType::Quadword, operand: index_reg.clone() });
+        }
+        let scale = if matches!(scale, 1 | 2 | 4 | 8) {
+            scale as u8
+        } else {
+            instructions.push(Instr::Binary { op: asm::BinaryOp::Mult, ty: AsmType::Quadword, src: Operand::Imm { value: scale }, dst: index_reg });
+            1
+        };
+        instructions.push(Instr::Lea { src: Operand::Indexed { base: Reg::AX, index: Reg::DX, scale }, dst: dst.clone() });
+        dst
+    }
+
+    /// Generate the instructions for a non short circuiting binary operation on already evaluated
+    /// operands of type ty, returning the temporary holding the result
+    fn assemble_binary(&mut self, op: BinaryOp, ty: &Type, left: Operand, right: Operand, instructions: &mut Vec<Instr>) -> Operand{
+        let signed = ty.is_signed();
+        let asm_ty = asm_type(ty);
+        match op {
+            BinaryOp::Divide | BinaryOp::Remainder if !ty.is_floating() => {
+                // Division divides dx:ax, leaving the quotient in ax and remainder in dx. The
+                // upper half is the sign extension of the dividend, or zero if it is unsigned.
+                let dst = self.make_temporary(ty);
+                let result = if op == BinaryOp::Divide {Reg::AX} else {Reg::DX};
+                instructions.push(Instr::Mov { ty: asm_ty, src: left, dst: Operand::Register { reg: Reg::AX } });
+                if signed {
+                    instructions.push(Instr::Cdq { ty: asm_ty });
+                    instructions.push(Instr::Idiv { ty: asm_ty, operand: right });
+                } else {
+                    instructions.push(Instr::Mov { ty: asm_ty, src: Operand::Imm { value: 0 }, dst: Operand::Register { reg: Reg::DX } });
+                    instructions.push(Instr::Div { ty: asm_ty, operand: right });
+                }
+                instructions.push(Instr::Mov { ty: asm_ty, src: Operand::Register { reg: result }, dst: dst.clone() });
+                dst
+            }
+            BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::LessThan | BinaryOp::LessOrEqual
+            | BinaryOp::GreaterThan | BinaryOp::GreaterOrEqual if ty.is_floating() => {
+                self.assemble_floating_comparison(op, asm_ty, left, right, instructions)
+            }
+            BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::LessThan | BinaryOp::LessOrEqual
+            | BinaryOp::GreaterThan | BinaryOp::GreaterOrEqual => {
+                // Comparisons give an int, whatever the type of their operands
+                let dst = self.make_temporary(&Type::Int);
+                let cond = match (op, signed) {
+                    (BinaryOp::Equal, _) => CondCode::E,
+                    (BinaryOp::NotEqual, _) => CondCode::NE,
+                    (BinaryOp::LessThan, true) => CondCode::L,
+                    (BinaryOp::LessOrEqual, true) => CondCode::LE,
+                    (BinaryOp::GreaterThan, true) => CondCode::G,
+                    (_, true) => CondCode::GE,
+                    (BinaryOp::LessThan, false) => CondCode::B,
+                    (BinaryOp::LessOrEqual, false) => CondCode::BE,
+                    (BinaryOp::GreaterThan, false) => CondCode::A,
+                    (_, false) => CondCode::AE,
+                };
+                instructions.push(Instr::Cmp { ty: asm_ty, src: right, dst: left });
+                instructions.push(Instr::Mov { ty: AsmType::Longword, src: Operand::Imm { value: 0 }, dst: dst.clone() });
+                instructions.push(Instr::SetCC { cond, operand: dst.clone() });
+                dst
+            }
+            _ => {
+                let dst = self.make_temporary(ty);
+                let op = match op {
+                    BinaryOp::Add => asm::BinaryOp::Add,
+                    BinaryOp::Subtract => asm::BinaryOp::Sub,
+                    BinaryOp::Multiply => asm::BinaryOp::Mult,
+                    BinaryOp::Divide => asm::BinaryOp::DivDouble,
+                    BinaryOp::BitwiseAnd => asm::BinaryOp::And,
+                    BinaryOp::BitwiseOr => asm::BinaryOp::Or,
+                    BinaryOp::BitwiseXor => asm::BinaryOp::Xor,
+                    BinaryOp::ShiftLeft => asm::BinaryOp::Sal,
+                    // The sign bit of signed values must be preserved
+                    _ if signed => asm::BinaryOp::Sar,
+                    _ => asm::BinaryOp::Shr,
+                };
+                instructions.push(Instr::Mov { ty: asm_ty, src: left, dst: dst.clone() });
+                instructions.push(Instr::Binary { op, ty: asm_ty, src: right, dst: dst.clone() });
+                dst
             }
         }
     }
-    
-    fn assemble_expr(&self, expr: Expr) -> Result<Box<Instr>, AssemblerError>{
-        match expr{
-            Expr::IntConstant { value } => {
-                Ok(Box::new(Instr::Imm { value: value.clone() }))
+
+    /// Compare floating operands, giving an int. An unordered comparison, when either operand is a
+    /// NaN, sets the zero, parity and carry flags, so every comparison other than != must be
+    /// false when it does.
+    fn assemble_floating_comparison(&mut self, op: BinaryOp, ty: AsmType, left: Operand, right: Operand, instructions: &mut Vec<Instr>) -> Operand{
+        let dst = self.make_temporary(&Type::Int);
+        // Less than is tested as greater than with the operands swapped, since only the
+        // unsigned above conditions are false when the carry flag is set
+        let (cond, left, right) = match op {
+            BinaryOp::Equal => (CondCode::E, left, right),
+            BinaryOp::NotEqual => (CondCode::NE, left, right),
+            BinaryOp::LessThan => (CondCode::A, right, left),
+            BinaryOp::LessOrEqual => (CondCode::AE, right, left),
+            BinaryOp::GreaterThan => (CondCode::A, left, right),
+            _ => (CondCode::AE, left, right),
+        };
+        let unordered_value = if op == BinaryOp::NotEqual {1} else {0};
+        instructions.push(Instr::Mov { ty: AsmType::Longword, src: Operand::Imm { value: unordered_value }, dst: dst.clone() });
+        instructions.push(Instr::Cmp { ty, src: right, dst: left });
+        // Equality is decided by the zero flag alone, so an unordered result must skip it
+        if matches!(op, BinaryOp::Equal | BinaryOp::NotEqual) {
+            let end = self.make_label("unordered");
+            instructions.push(Instr::JmpCC { cond: CondCode::P, target: end.clone() });
+            instructions.push(Instr::SetCC { cond, operand: dst.clone() });
+            instructions.push(Instr::Label { name: end });
+        } else {
+            instructions.push(Instr::SetCC { cond, operand: dst.clone() });
+        }
+        dst
+    }
+
+    /// A read only constant holding a floating value, reusing an earlier one with the same value
+    /// and alignment
+    fn floating_constant(&mut self, value: Const, alignment: usize) -> Operand {
+        let ty = asm_type(&value.ty());
+        let key = (value.bits(), ty, alignment);
+        if let Some(name) = self.constant_names.get(&key) {
+            return Operand::Data { name: name.clone() };
+        }
+        let name = format!(".L{}", self.make_label("constant"));
+        self.constant_names.insert(key, name.clone());
+        self.constants.push(Instr::StaticConstant { name: name.clone(), alignment, ty, init: asm::StaticInit::Scalar(value.bits()) });
+        Operand::Data { name }
+    }
+
+    /// Name of the read only constant holding a string literal, which is NUL terminated. Each
+    /// distinct string is only emitted once.
+    fn string_constant(&mut self, value: Vec<u8>) -> String {
+        if let Some(name) = self.string_names.get(&value) {
+            return name.clone();
+        }
+        let name = format!(".L{}", self.make_label("string"));
+        let size = value.len() + 1;
+        let ty = AsmType::ByteArray { size, alignment: 1 };
+        self.string_names.insert(value.clone(), name.clone());
+        self.constants.push(Instr::StaticConstant { name: name.clone(), alignment: 1, ty, init: asm::StaticInit::String { value, size } });
+        name
+    }
+
+    /// Make a new temporary of the given type, which is added to the symbol table so it can be
+    /// given a stack slot of the right size
+    fn make_temporary(&mut self, ty: &Type) -> Operand {
+        let name = format!("tmp.{}", self.temp_counter);
+        self.temp_counter += 1;
+        self.symbols.insert(name.clone(), Symbol { ty: ty.clone(), attrs: IdentAttrs::Local });
+        Operand::Pseudo { name }
+    }
+
+    fn make_label(&mut self, prefix: &str) -> String {
+        let name = format!("{}.{}", prefix, self.label_counter);
+        self.label_counter += 1;
+        name
+    }
+}
+
+/// Width of the operands holding a value of an integer type
+fn asm_type(ty: &Type) -> AsmType {
+    match ty {
+        Type::Float => return AsmType::Float,
+        Type::Double => return AsmType::Double,
+        // The ABI aligns array variables of at least 16 bytes to 16, so they can be accessed with
+        // aligned SSE instructions
+        Type::Array { .. } => {
+            let alignment = if ty.size() >= 16 {16} else {ty.alignment()};
+            return AsmType::ByteArray { size: ty.size(), alignment };
+        }
+        Type::Struct(_) => return AsmType::ByteArray { size: ty.size(), alignment: ty.alignment() },
+        _ => {}
+    }
+    match ty.size() {
+        1 => AsmType::Byte,
+        2 => AsmType::Word,
+        4 => AsmType::Longword,
+        _ => AsmType::Quadword,
+    }
+}
+
+/// System V class of an eightbyte of a value, which decides the kind of register it is passed in
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Class {
+    Integer,
+    Sse,
+    /// Passed on the stack and returned through a pointer
+    Memory,
+}
+
+/// Class of each eightbyte of a value of the given type. Structures larger than 16 bytes are passed
+/// in memory, any other eightbyte is SSE if it only holds floating members.
+fn classify(ty: &Type) -> Vec<Class> {
+    match ty {
+        Type::Struct(_) if ty.size() > 16 => vec![Class::Memory],
+        Type::Struct(_) => {
+            let mut classes = vec![Class::Sse; ty.size().div_ceil(8)];
+            mark_integer_eightbytes(ty, 0, &mut classes);
+            classes
+        }
+        _ if ty.is_floating() => vec![Class::Sse],
+        _ => vec![Class::Integer],
+    }
+}
+
+/// Mark the eightbytes holding a non-floating scalar of an object at the given offset as integer
+fn mark_integer_eightbytes(ty: &Type, offset: usize, classes: &mut [Class]) {
+    match ty {
+        Type::Struct(struct_type) => {
+            let definition = struct_type.definition.borrow();
+            for member in &definition.as_ref().expect("passed structures are complete").members {
+                mark_integer_eightbytes(&member.ty, offset + member.offset, classes);
             }
-            Expr::Identifier { value } => {
-                Ok(Box::new(Instr::Identifier {value: value.clone()}))
+        }
+        Type::Array { element, size } => {
+            for i in 0..size.expect("passed arrays are complete") {
+                mark_integer_eightbytes(element, offset + i * element.size(), classes);
+            }
+        }
+        _ if ty.is_floating() => {}
+        _ => classes[offset / 8] = Class::Integer,
+    }
+}
+
+/// Whether a value of the given type is passed on the stack and returned through a pointer
+fn is_memory_class(ty: &Type) -> bool {
+    classify(ty)[0] == Class::Memory
+}
+
+/// Registers holding each eightbyte of the arguments of the given types, or None for those passed
+/// on the stack. Eightbytes are given the next free register of their class, but an argument is
+/// only passed in registers if there are enough left for all of it.
+fn argument_locations(types: &[Type], integer_registers: &[Reg]) -> Vec<Option<Vec<Reg>>> {
+    let (mut integer_registers, mut sse_registers) = (integer_registers.iter().copied(), SSE_ARGUMENT_REGISTERS.iter().copied());
+    types.iter().map(|ty| {
+        let classes = classify(ty);
+        let count = |class| classes.iter().filter(|c| **c == class).count();
+        if classes[0] == Class::Memory || count(Class::Integer) > integer_registers.len() || count(Class::Sse) > sse_registers.len() {
+            return None;
+        }
+        let regs = classes.iter().map(|class| if *class == Class::Sse {sse_registers.next()} else {integer_registers.next()});
+        Some(regs.map(|reg| reg.expect("enough registers are left")).collect())
+    }).collect()
+}
+
+/// Registers holding each eightbyte of a structure returned in registers
+fn return_registers(ty: &Type) -> Vec<Reg> {
+    let (mut integer_registers, mut sse_registers) = (RETURN_REGISTERS.into_iter(), SSE_RETURN_REGISTERS.into_iter());
+    classify(ty).into_iter().map(|class| match class {
+        Class::Sse => sse_registers.next(),
+        _ => integer_registers.next(),
+    }.expect("small structures have at most two eightbytes")).collect()
+}
+
+/// Load the size bytes of an eightbyte at an offset from the address in base into reg, without
+/// reading past them
+fn load_eightbyte(base: Reg, offset: usize, size: usize, class: Class, reg: Reg, instructions: &mut Vec<Instr>) {
+    let dst = Operand::Register { reg };
+    let ty = match (class, size) {
+        (Class::Sse, 4) => AsmType::Float,
+        (Class::Sse, _) => AsmType::Double,
+        (_, 8) => AsmType::Quadword,
+        (_, 4) => AsmType::Longword,
+        (_, 2) => AsmType::Word,
+        (_, 1) => AsmType::Byte,
+        _ => {
+            // Other sizes are built up a byte at a time from the last, since moving a byte into a
+            // register leaves the rest of it alone
+            for i in (0..size).rev() {
+                if i != size - 1 {
+                    instructions.push(Instr::Binary { op: asm::BinaryOp::Sal, ty: AsmType::Quadword, src: Operand::Imm { value: 8 }, dst: dst.clone() });
+                }
+                let src = Operand::Memory { reg: base, offset: (offset + i) as i32 };
+                instructions.push(Instr::Mov { ty: AsmType::Byte, src, dst: dst.clone() });
             }
+            return;
         }
+    };
+    instructions.push(Instr::Mov { ty, src: Operand::Memory { reg: base, offset: offset as i32 }, dst });
+}
+
+/// Store bytes at offset from the address in ax, eight, four or one bytes at a time
+fn store_bytes(bytes: &[u8], offset: usize, instructions: &mut Vec<Instr>) {
+    let mut stored = 0;
+    while stored < bytes.len() {
+        let chunk = &bytes[stored..];
+        let (ty, value) = if chunk.len() >= 8 {
+            (AsmType::Quadword, i64::from_le_bytes(chunk[..8].try_into().unwrap()))
+        } else if chunk.len() >= 4 {
+            (AsmType::Longword, i32::from_le_bytes(chunk[..4].try_into().unwrap()) as i64)
+        } else {
+            (AsmType::Byte, chunk[0] as i8 as i64)
+        };
+        let dst = Operand::Memory { reg: Reg::AX, offset: (offset + stored) as i32 };
+        instructions.push(Instr::Mov { ty, src: Operand::Imm { value }, dst });
+        stored += ty.size();
     }
 }
 
+/// Load the storage unit of a bit-field, an object of the given type whose address is in ax, into
+/// all of a register
+fn load_storage_unit(unit: &Type, reg: Reg, instructions: &mut Vec<Instr>) {
+    let (src, dst) = (Operand::Memory { reg: Reg::AX, offset: 0 }, Operand::Register { reg });
+    let instr = match asm_type(unit) {
+        src_ty @ (AsmType::Byte | AsmType::Word) => Instr::MovZeroExtend { src_ty, dst_ty: AsmType::Quadword, src, dst },
+        // Moving a longword clears the upper half of the register
+        ty => Instr::Mov { ty, src, dst },
+    };
+    instructions.push(instr);
+}
+
+/// Move an argument into dst, extending arguments narrower than int to 32 bits as callers are
+/// expected to
+fn extend_argument(value: Operand, ty: &Type, dst: Operand) -> Instr {
+    let src_ty = asm_type(ty);
+    match ty.size() {
+        1 | 2 if ty.is_signed() => Instr::Movsx { src_ty, dst_ty: AsmType::Longword, src: value, dst },
+        1 | 2 => Instr::MovZeroExtend { src_ty, dst_ty: AsmType::Longword, src: value, dst },
+        _ => Instr::Mov { ty: src_ty, src: value, dst },
+    }
+}
+
+/// Whether the sorted cases of a switch are dense enough to use a jump table
+fn use_jump_table(cases: &[(i64, String)]) -> bool {
+    if cases.len() < JUMP_TABLE_MIN_CASES {
+        return false;
+    }
+    let entries = (cases[cases.len() - 1].0.wrapping_sub(cases[0].0) as u64).saturating_add(1);
+    entries <= JUMP_TABLE_MAX_ENTRIES_PER_CASE * cases.len() as u64
+}
+
+/// Replace every pseudo-register with a stack slot, or the data of a variable with static storage,
+/// returning the number of bytes of stack needed, rounded up to keep the stack 16 byte aligned.
+/// Each slot is aligned to the alignment of its type. Every variable keeps its slot for the whole function, so
+/// a pointer to it stays valid.
+fn replace_pseudos(instructions: &mut [Instr], symbols: &HashMap<String, Symbol>) -> i32 {
+    let mut offsets: HashMap<String, i32> = HashMap::new();
+    let mut bytes = 0;
+    let mut replace = |operand: &mut Operand| {
+        if let Operand::Pseudo { name } = operand {
+            let symbol = &symbols[name.as_str()];
+            if let IdentAttrs::Static { .. } = symbol.attrs {
+                *operand = Operand::Data { name: name.clone() };
+                return;
+            }
+            let offset = *offsets.entry(name.clone()).or_insert_with(|| {
+                let ty = asm_type(&symbol.ty);
+                let (size, alignment) = (ty.size() as i32, ty.alignment() as i32);
+                bytes = (bytes + size + alignment - 1) / alignment * alignment;
+                -bytes
+            });
+            *operand = Operand::Stack { offset };
+        }
+    };
+    for instr in instructions.iter_mut() {
+        match instr {
+            Instr::Mov { src, dst, .. } | Instr::Movsx { src, dst, .. } | Instr::MovZeroExtend { src, dst, .. }
+            | Instr::Binary { src, dst, .. } | Instr::Cmp { src, dst, .. } | Instr::Cvtsi2sd { src, dst, .. }
+            | Instr::Cvttsd2si { src, dst, .. } | Instr::Cvtss2sd { src, dst } | Instr::Cvtsd2ss { src, dst }
+            | Instr::Lea { src, dst } => {
+                replace(src);
+                replace(dst);
+            }
+            Instr::Unary { operand, .. } | Instr::Idiv { operand, .. } | Instr::Div { operand, .. }
+            | Instr::SetCC { operand, .. } | Instr::Push { operand } | Instr::CallIndirect { operand } => {
+                replace(operand);
+            }
+            _ => {}
+        }
+    }
+    (bytes + 15) / 16 * 16
+}
+
+/// Whether the operand is an immediate which doesn't fit in the sign extended 32 bit immediate of
+/// a 64 bit instruction
+fn is_large_immediate(operand: &Operand) -> bool {
+    matches!(operand, Operand::Imm { value } if i32::try_from(*value).is_err())
+}
+
+/// Rewrite instructions whose operands x86 can't encode, using r10 and r11 as scratch registers
+fn fix_instructions(instructions: Vec<Instr>) -> Vec<Instr> {
+    let mut fixed = Vec::with_capacity(instructions.len());
+    for instr in instructions {
+        fix_instruction(instr, &mut fixed);
+    }
+    fixed
+}
+
+/// Rewrite a single instruction, any instruction it is rewritten to is fixed in turn
+fn fix_instruction(instr: Instr, fixed: &mut Vec<Instr>) {
+    let r10 = Operand::Register { reg: Reg::R10 };
+    let r11 = Operand::Register { reg: Reg::R11 };
+    let xmm14 = Operand::Register { reg: Reg::XMM14 };
+    let xmm15 = Operand::Register { reg: Reg::XMM15 };
+    match instr {
+        // Floating instructions go through xmm registers rather than r10 and r11, and other than
+        // mov they can only write to a register
+        Instr::Mov { ty, src, dst } if ty.is_floating() && src.is_memory() && dst.is_memory() => {
+            fixed.push(Instr::Mov { ty, src, dst: xmm14.clone() });
+            fixed.push(Instr::Mov { ty, src: xmm14, dst });
+        }
+        Instr::Binary { op, ty, src, dst } if ty.is_floating() && !matches!(dst, Operand::Register { .. }) => {
+            fixed.push(Instr::Mov { ty, src: dst.clone(), dst: xmm15.clone() });
+            fixed.push(Instr::Binary { op, ty, src, dst: xmm15.clone() });
+            fixed.push(Instr::Mov { ty, src: xmm15, dst });
+        }
+        Instr::Cmp { ty, src, dst } if ty.is_floating() && !matches!(dst, Operand::Register { .. }) => {
+            fixed.push(Instr::Mov { ty, src: dst, dst: xmm15.clone() });
+            fixed.push(Instr::Cmp { ty, src, dst: xmm15 });
+        }
+        Instr::Cvtsi2sd { src_ty, dst_ty, src: src @ Operand::Imm { .. }, dst } => {
+            fixed.push(Instr::Mov { ty: src_ty, src, dst: r10.clone() });
+            fix_instruction(Instr::Cvtsi2sd { src_ty, dst_ty, src: r10, dst }, fixed);
+        }
+        Instr::Cvtsi2sd { src_ty, dst_ty, src, dst } if dst.is_memory() => {
+            fixed.push(Instr::Cvtsi2sd { src_ty, dst_ty, src, dst: xmm15.clone() });
+            fixed.push(Instr::Mov { ty: dst_ty, src: xmm15, dst });
+        }
+        Instr::Cvttsd2si { src_ty, dst_ty, src, dst } if dst.is_memory() => {
+            fixed.push(Instr::Cvttsd2si { src_ty, dst_ty, src, dst: r11.clone() });
+            fixed.push(Instr::Mov { ty: dst_ty, src: r11, dst });
+        }
+        Instr::Cvtss2sd { src, dst } if dst.is_memory() => {
+            fixed.push(Instr::Cvtss2sd { src, dst: xmm15.clone() });
+            fixed.push(Instr::Mov { ty: AsmType::Double, src: xmm15, dst });
+        }
+        Instr::Cvtsd2ss { src, dst } if dst.is_memory() => {
+            fixed.push(Instr::Cvtsd2ss { src, dst: xmm15.clone() });
+            fixed.push(Instr::Mov { ty: AsmType::Float, src: xmm15, dst });
+        }
+        // lea must write to a register
+        Instr::Lea { src, dst } if dst.is_memory() => {
+            fixed.push(Instr::Lea { src, dst: r11.clone() });
+            fixed.push(Instr::Mov { ty: AsmType::Quadword, src: r11, dst });
+        }
+        // Only a mov into a register can take a 64 bit immediate
+        Instr::Mov { ty: AsmType::Quadword, src, dst } if is_large_immediate(&src) && dst.is_memory() => {
+            fixed.push(Instr::Mov { ty: AsmType::Quadword, src, dst: r10.clone() });
+            fixed.push(Instr::Mov { ty: AsmType::Quadword, src: r10, dst });
+        }
+        Instr::Binary { op, ty: AsmType::Quadword, src, dst } if is_large_immediate(&src) => {
+            fixed.push(Instr::Mov { ty: AsmType::Quadword, src, dst: r10.clone() });
+            fix_instruction(Instr::Binary { op, ty: AsmType::Quadword, src: r10, dst }, fixed);
+        }
+        Instr::Cmp { ty: AsmType::Quadword, src, dst } if is_large_immediate(&src) => {
+            fixed.push(Instr::Mov { ty: AsmType::Quadword, src, dst: r10.clone() });
+            fix_instruction(Instr::Cmp { ty: AsmType::Quadword, src: r10, dst }, fixed);
+        }
+        Instr::Push { operand } if is_large_immediate(&operand) => {
+            fixed.push(Instr::Mov { ty: AsmType::Quadword, src: operand, dst: r10.clone() });
+            fixed.push(Instr::Push { operand: r10 });
+        }
+        // An indirect call can't go to an immediate address
+        Instr::CallIndirect { operand: operand @ Operand::Imm { .. } } => {
+            fixed.push(Instr::Mov { ty: AsmType::Quadword, src: operand, dst: r10.clone() });
+            fixed.push(Instr::CallIndirect { operand: r10 });
+        }
+        // At most one operand may be in memory
+        Instr::Mov { ty, src, dst } if src.is_memory() && dst.is_memory() => {
+            fixed.push(Instr::Mov { ty, src, dst: r10.clone() });
+            fixed.push(Instr::Mov { ty, src: r10, dst });
+        }
+        // Extending moves can't take an immediate, and must write to a register
+        Instr::Movsx { src_ty, dst_ty, src: src @ Operand::Imm { .. }, dst } => {
+            fixed.push(Instr::Mov { ty: src_ty, src, dst: r10.clone() });
+            fix_instruction(Instr::Movsx { src_ty, dst_ty, src: r10, dst }, fixed);
+        }
+        Instr::Movsx { src_ty, dst_ty, src, dst } if dst.is_memory() => {
+            fixed.push(Instr::Movsx { src_ty, dst_ty, src, dst: r11.clone() });
+            fixed.push(Instr::Mov { ty: dst_ty, src: r11, dst });
+        }
+        Instr::MovZeroExtend { src_ty, dst_ty, src: src @ Operand::Imm { .. }, dst } => {
+            fixed.push(Instr::Mov { ty: src_ty, src, dst: r10.clone() });
+            fix_instruction(Instr::MovZeroExtend { src_ty, dst_ty, src: r10, dst }, fixed);
+        }
+        // A 32 bit mov into a register clears the upper half, so needs no zero extending form
+        Instr::MovZeroExtend { src_ty: AsmType::Longword, dst_ty, src, dst } => {
+            if dst.is_memory() {
+                fixed.push(Instr::Mov { ty: AsmType::Longword, src, dst: r11.clone() });
+                fixed.push(Instr::Mov { ty: dst_ty, src: r11, dst });
+            } else {
+                fixed.push(Instr::Mov { ty: AsmType::Longword, src, dst });
+            }
+        }
+        Instr::MovZeroExtend { src_ty, dst_ty, src, dst } if dst.is_memory() => {
+            fixed.push(Instr::MovZeroExtend { src_ty, dst_ty, src, dst: r11.clone() });
+            fixed.push(Instr::Mov { ty: dst_ty, src: r11, dst });
+        }
+        // Division can't take an immediate
+        Instr::Idiv { ty, operand: operand @ Operand::Imm { .. } } => {
+            fixed.push(Instr::Mov { ty, src: operand, dst: r10.clone() });
+            fixed.push(Instr::Idiv { ty, operand: r10 });
+        }
+        Instr::Div { ty, operand: operand @ Operand::Imm { .. } } => {
+            fixed.push(Instr::Mov { ty, src: operand, dst: r10.clone() });
+            fixed.push(Instr::Div { ty, operand: r10 });
+        }
+        // Variable shift counts must be in %cl, counts are at least int so their low 32 bits can
+        // be moved
+        Instr::Binary { op, ty, src, dst } if op.is_shift()
+            && !matches!(src, Operand::Imm { .. } | Operand::Register { reg: Reg::CX }) => {
+            let cx = Operand::Register { reg: Reg::CX };
+            fixed.push(Instr::Mov { ty: AsmType::Longword, src, dst: cx.clone() });
+            fixed.push(Instr::Binary { op, ty, src: cx, dst });
+        }
+        // imul can't write to memory
+        Instr::Binary { op: asm::BinaryOp::Mult, ty, src, dst } if dst.is_memory() => {
+            fixed.push(Instr::Mov { ty, src: dst.clone(), dst: r11.clone() });
+            fixed.push(Instr::Binary { op: asm::BinaryOp::Mult, ty, src, dst: r11.clone() });
+            fixed.push(Instr::Mov { ty, src: r11, dst });
+        }
+        Instr::Binary { op, ty, src, dst } if src.is_memory() && dst.is_memory() => {
+            fixed.push(Instr::Mov { ty, src, dst: r10.clone() });
+            fixed.push(Instr::Binary { op, ty, src: r10, dst });
+        }
+        Instr::Cmp { ty, src, dst } if src.is_memory() && dst.is_memory() => {
+            fixed.push(Instr::Mov { ty, src, dst: r10.clone() });
+            fixed.push(Instr::Cmp { ty, src: r10, dst });
+        }
+        // The second operand of cmp can't be an immediate
+        Instr::Cmp { ty, src, dst: dst @ Operand::Imm { .. } } => {
+            fixed.push(Instr::Mov { ty, src: dst, dst: r11.clone() });
+            fixed.push(Instr::Cmp { ty, src, dst: r11 });
+        }
+        instr => fixed.push(instr),
+    }
+}
+
+/// Where the object an lvalue designates is
+enum Lvalue {
+    /// An operand which is the object itself, such as a variable
+    Operand(Operand),
+    /// An operand holding a pointer to the object
+    Dereferenced(Operand),
+    /// A bit-field, given by a pointer to the storage unit of type unit holding it
+    BitField{pointer: Operand, unit: Type, bits: BitField},
+}
+
+#[derive(Debug)]
 pub enum AssemblerError{
     InvalidFuncName,
     InvalidFuncBody,
-    InvalidReturn
-}
\ No newline at end of file
+    NotAssignable,
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblerError::InvalidFuncName => write!(f, "invalid function name"),
+            AssemblerError::InvalidFuncBody => write!(f, "invalid function body"),
+            AssemblerError::NotAssignable => write!(f, "expression is not assignable"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use crate::lex;
+    use crate::parse::c_ast::{StructType, StructDef};
+    use crate::parse::parsing::Parser;
+    use crate::validate::resolving::Resolver;
+    use crate::validate::labeling::Labeler;
+    use crate::validate::typechecking::TypeChecker;
+
+    /// A structure with unnamed members of the given types
+    fn structure(members: Vec<Type>) -> Type {
+        let struct_type = StructType::new("s".to_string(), false);
+        let members = members.into_iter().enumerate().map(|(i, ty)| (format!("m{}", i), ty, None)).collect();
+        *struct_type.definition.borrow_mut() = Some(StructDef::layout(members, false));
+        Type::Struct(Rc::new(struct_type))
+    }
+
+    /// The instructions of the function with the given name in a program
+    fn assemble(source: &str, function: &str) -> Vec<Instr> {
+        let tokens = lex::Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut ast = Parser::new(tokens).parse().unwrap();
+        Resolver::new().resolve(&mut ast).unwrap();
+        Labeler::new().label(&mut ast).unwrap();
+        let mut checker = TypeChecker::new();
+        checker.check(&mut ast).unwrap();
+        let Instr::Program { body } = Assembler::new(checker.into_symbols()).assemble(ast).unwrap() else {
+            panic!("Expected program");
+        };
+        body.into_iter().find_map(|item| match item {
+            Instr::FuncDef { name, instructions, .. } if name == function => Some(instructions),
+            _ => None,
+        }).expect("function is defined")
+    }
+
+    /// Position of the first instruction matching a predicate
+    fn position(instructions: &[Instr], predicate: impl Fn(&Instr) -> bool) -> usize {
+        instructions.iter().position(predicate).expect("instruction is present")
+    }
+
+    fn moves_from(reg: Reg) -> impl Fn(&Instr) -> bool {
+        move |instr| matches!(instr, Instr::Mov { src: Operand::Register { reg: src }, .. } if *src == reg)
+    }
+
+    fn moves_to(reg: Reg) -> impl Fn(&Instr) -> bool {
+        move |instr| matches!(instr, Instr::Mov { dst: Operand::Register { reg: dst }, .. } if *dst == reg)
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(&structure(vec![Type::Double, Type::Long])), vec![Class::Sse, Class::Integer]);
+        assert_eq!(classify(&structure(vec![Type::Float, Type::Float, Type::Float])), vec![Class::Sse, Class::Sse]);
+        assert_eq!(classify(&structure(vec![Type::Array { element: Box::new(Type::Char), size: Some(7) }])), vec![Class::Integer]);
+        assert_eq!(classify(&structure(vec![Type::Long, Type::Double, Type::Int])), vec![Class::Memory]);
+        // The inner structure's float shares the first eightbyte, its int is in the second
+        let inner = structure(vec![Type::Float, Type::Int]);
+        assert_eq!(classify(&structure(vec![Type::Float, inner])), vec![Class::Sse, Class::Integer]);
+        assert_eq!(classify(&Type::Double), vec![Class::Sse]);
+        assert_eq!(classify(&Type::Pointer(Box::new(Type::Double))), vec![Class::Integer]);
+    }
+
+    #[test]
+    fn test_argument_locations() {
+        let mixed = structure(vec![Type::Double, Type::Long]);
+        assert_eq!(argument_locations(&[Type::Int, mixed.clone()], &ARGUMENT_REGISTERS), vec![
+            Some(vec![Reg::DI]), Some(vec![Reg::XMM0, Reg::SI]),
+        ]);
+        // A structure which doesn't fit in the registers left goes on the stack whole, and later
+        // arguments still take the registers it didn't
+        let pair = structure(vec![Type::Long, Type::Long]);
+        let mut types = vec![Type::Long; 5];
+        types.extend([pair, Type::Long]);
+        let locations = argument_locations(&types, &ARGUMENT_REGISTERS);
+        assert_eq!(locations[5], None);
+        assert_eq!(locations[6], Some(vec![Reg::R9]));
+        let doubles = structure(vec![Type::Double, Type::Double]);
+        let mut types = vec![Type::Double; 7];
+        types.extend([doubles, Type::Double]);
+        let locations = argument_locations(&types, &ARGUMENT_REGISTERS);
+        assert_eq!(locations[7], None);
+        assert_eq!(locations[8], Some(vec![Reg::XMM7]));
+        // One integer register short for the mixed structure
+        let mut types = vec![Type::Long; 6];
+        types.push(mixed);
+        assert_eq!(argument_locations(&types, &ARGUMENT_REGISTERS)[6], None);
+        assert_eq!(argument_locations(&[structure(vec![Type::Long; 3])], &ARGUMENT_REGISTERS), vec![None]);
+    }
+
+    #[test]
+    fn test_eightbytes() {
+        // Seven bytes are loaded a byte at a time, last first, so nothing past them is read
+        let mut instructions = Vec::new();
+        load_eightbyte(Reg::CX, 8, 7, Class::Integer, Reg::AX, &mut instructions);
+        assert_eq!(instructions.len(), 13);
+        assert!(matches!(instructions[0], Instr::Mov { ty: AsmType::Byte, src: Operand::Memory { reg: Reg::CX, offset: 14 }, .. }));
+        assert!(matches!(instructions[1], Instr::Binary { op: asm::BinaryOp::Sal, src: Operand::Imm { value: 8 }, .. }));
+        assert!(matches!(instructions[12], Instr::Mov { ty: AsmType::Byte, src: Operand::Memory { reg: Reg::CX, offset: 8 }, .. }));
+        // A 12 byte structure is pushed tail first, the tail loaded as a longword
+        let mut assembler = Assembler::new(HashMap::new());
+        let mut instructions = Vec::new();
+        let floats = structure(vec![Type::Float, Type::Float, Type::Float]);
+        assembler.push_struct(Operand::Pseudo { name: "s".to_string() }, &floats, &mut instructions);
+        assert!(matches!(&instructions[..], [
+            Instr::Lea { dst: Operand::Register { reg: Reg::CX }, .. },
+            Instr::Mov { ty: AsmType::Longword, src: Operand::Memory { reg: Reg::CX, offset: 8 }, dst: Operand::Register { reg: Reg::AX } },
+            Instr::Push { operand: Operand::Register { reg: Reg::AX } },
+            Instr::Push { operand: Operand::Memory { reg: Reg::CX, offset: 0 } },
+        ]));
+        // The tail of a structure stored from an integer register is written in pieces
+        let mut instructions = Vec::new();
+        let bytes = structure(vec![Type::Array { element: Box::new(Type::Char), size: Some(7) }]);
+        assembler.store_eightbytes(&[Reg::DI], &bytes, &Lvalue::Operand(Operand::Pseudo { name: "s".to_string() }), Reg::AX, &mut instructions);
+        let stored: Vec<AsmType> = instructions.iter().filter_map(|instr| match instr {
+            Instr::Mov { ty, dst: Operand::Memory { .. }, .. } => Some(*ty),
+            _ => None,
+        }).collect();
+        assert_eq!(stored, vec![AsmType::Longword, AsmType::Word, AsmType::Byte]);
+        let mut instructions = Vec::new();
+        assembler.load_eightbytes(&Lvalue::Operand(Operand::Pseudo { name: "s".to_string() }), &floats, &[Reg::XMM0, Reg::XMM1], Reg::AX, &mut instructions);
+        assert!(matches!(&instructions[1..], [
+            Instr::Mov { ty: AsmType::Double, dst: Operand::Register { reg: Reg::XMM0 }, .. },
+            Instr::Mov { ty: AsmType::Float, src: Operand::Memory { reg: Reg::AX, offset: 8 }, dst: Operand::Register { reg: Reg::XMM1 } },
+        ]));
+    }
+
+    #[test]
+    fn test_struct_returns() {
+        // A structure returned in memory is written through a pointer passed in di, so the first
+        // parameter is passed in si
+        let source = "struct s {long a; long b; long c;};\n\
+                      struct s f(long x){struct s r = {x, 2, 3}; return r;}\n\
+                      long g(void){return f(7).a;}";
+        let instructions = assemble(source, "f");
+        assert!(moves_from(Reg::DI)(&instructions[1]));
+        assert!(moves_from(Reg::SI)(&instructions[2]));
+        assert!(instructions.iter().any(|instr| matches!(instr, Instr::Mov { dst: Operand::Register { reg: Reg::AX }, .. })));
+        let instructions = assemble(source, "g");
+        let call = position(&instructions, |instr| matches!(instr, Instr::Call { name } if name == "f"));
+        assert!(position(&instructions, |instr| matches!(instr, Instr::Lea { dst: Operand::Register { reg: Reg::DI }, .. })) < call);
+        assert!(position(&instructions, moves_to(Reg::SI)) < call);
+        // A mixed structure comes back in xmm0 and ax
+        let source = "struct m {double d; long l;};\n\
+                      struct m f(void){struct m r = {1.5, 2}; return r;}\n\
+                      long g(void){return f().l;}";
+        let instructions = assemble(source, "f");
+        let ret = position(&instructions, |instr| matches!(instr, Instr::Ret));
+        assert!(position(&instructions, moves_to(Reg::XMM0)) < ret);
+        assert!(position(&instructions, moves_to(Reg::AX)) < ret);
+        let instructions = assemble(source, "g");
+        let call = position(&instructions, |instr| matches!(instr, Instr::Call { .. }));
+        assert!(position(&instructions, moves_from(Reg::XMM0)) > call);
+        assert!(position(&instructions, moves_from(Reg::AX)) > call);
+    }
+
+    #[test]
+    fn test_switch_lowering() {
+        let cases = |values: &[i64]| -> Vec<(i64, String)> { values.iter().map(|value| (*value, format!("case.{}", value))).collect() };
+        assert!(use_jump_table(&cases(&[1, 2, 3, 4])));
+        assert!(!use_jump_table(&cases(&[1, 2, 3])));
+        // Up to four table entries per case
+        assert!(use_jump_table(&cases(&[0, 1, 2, 15])));
+        assert!(!use_jump_table(&cases(&[0, 1, 2, 16])));
+        assert!(use_jump_table(&cases(&[i64::MIN, i64::MIN + 1, i64::MIN + 2, i64::MIN + 3])));
+        assert!(!use_jump_table(&cases(&[i64::MIN, 0, 1, i64::MAX])));
+        // The table covers every value from the smallest case to the largest, with the gaps
+        // going to the default
+        let instructions = assemble("int f(int x){switch (x) {case 2: return 1; case 3: return 2; case 5: return 3;
+                                     case 6: return 4; default: return 5;}}", "f");
+        let table = instructions.iter().find_map(|instr| match instr {
+            Instr::JumpTable { targets, .. } => Some(targets),
+            _ => None,
+        }).expect("a jump table is used");
+        assert_eq!(table.len(), 5);
+        assert!(table[2].starts_with("default"), "{}", table[2]);
+        let sub = position(&instructions, |instr| matches!(instr, Instr::Binary { op: asm::BinaryOp::Sub, src: Operand::Imm { value: 2 }, .. }));
+        assert!(matches!(instructions[sub + 1], Instr::Cmp { src: Operand::Imm { value: 4 }, .. }));
+        assert!(matches!(instructions[sub + 2], Instr::JmpCC { cond: CondCode::A, .. }));
+        // Sparse cases are found with a binary search, comparing the last few one at a time
+        let instructions = assemble("int f(int x){switch (x) {case 0: return 1; case 10: return 2; case 20: return 3;
+                                     case 30: return 4; case 1000: return 5;} return 0;}", "f");
+        assert!(!instructions.iter().any(|instr| matches!(instr, Instr::JumpTable { .. })));
+        let compared: Vec<i64> = instructions.iter().filter_map(|instr| match instr {
+            Instr::Cmp { src: Operand::Imm { value }, .. } => Some(*value),
+            _ => None,
+        }).collect();
+        assert_eq!(compared, vec![20, 30, 1000, 0, 10]);
+        let jumps = position(&instructions, |instr| matches!(instr, Instr::Cmp { src: Operand::Imm { value: 20 }, .. }));
+        assert!(matches!(instructions[jumps + 1], Instr::JmpCC { cond: CondCode::E, .. }));
+        assert!(matches!(instructions[jumps + 2], Instr::JmpCC { cond: CondCode::L, .. }));
+        let instructions = assemble("int f(unsigned x){switch (x) {case 1: return 1; case 2: return 2; case 3: return 3;
+                                     case 40: return 4; case 50: return 5;} return 0;}", "f");
+        assert!(instructions.iter().any(|instr| matches!(instr, Instr::JmpCC { cond: CondCode::B, .. })));
+    }
+
+    #[test]
+    fn test_variadic_calls() {
+        // al holds the number of vector registers used, set last so no argument overwrites it
+        let source = "int printf(char *fmt, ...); int g(double x, double y);
+                      int f(double x){printf(\"%f %d %f\", x, 2, 1.5); printf(\"%d\", 1); return g(x, x);}";
+        let instructions = assemble(source, "f");
+        let calls: Vec<usize> = instructions.iter().enumerate()
+            .filter(|(_, instr)| matches!(instr, Instr::Call { .. }))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(calls.len(), 3);
+        assert!(matches!(instructions[calls[0] - 1],
+            Instr::Mov { ty: AsmType::Longword, src: Operand::Imm { value: 2 }, dst: Operand::Register { reg: Reg::AX } }));
+        assert!(matches!(instructions[calls[1] - 1],
+            Instr::Mov { ty: AsmType::Longword, src: Operand::Imm { value: 0 }, dst: Operand::Register { reg: Reg::AX } }));
+        assert!(!moves_to(Reg::AX)(&instructions[calls[2] - 1]));
+    }
+
+    #[test]
+    fn test_floating_comparisons() {
+        // The result starts as the value for unordered operands, which == and != keep by jumping
+        // over the setcc when the parity flag is set
+        let comparison = |op: &str| {
+            let instructions = assemble(&format!("int f(double a, double b){{return a {} b;}}", op), "f");
+            let cmp = position(&instructions, |instr| matches!(instr, Instr::Cmp { ty: AsmType::Double, .. }));
+            let initial = instructions[..cmp].iter().rev()
+                .find(|instr| matches!(instr, Instr::Mov { ty: AsmType::Longword, src: Operand::Imm { .. }, .. }))
+                .expect("the result is initialized");
+            (initial.clone(), instructions[cmp + 1..cmp + 3].to_vec())
+        };
+        let (initial, after) = comparison("==");
+        assert!(matches!(initial, Instr::Mov { src: Operand::Imm { value: 0 }, .. }));
+        assert!(matches!(&after[..], [Instr::JmpCC { cond: CondCode::P, .. }, Instr::SetCC { cond: CondCode::E, .. }]));
+        let (initial, after) = comparison("!=");
+        assert!(matches!(initial, Instr::Mov { src: Operand::Imm { value: 1 }, .. }));
+        assert!(matches!(&after[..], [Instr::JmpCC { cond: CondCode::P, .. }, Instr::SetCC { cond: CondCode::NE, .. }]));
+        // The ordered comparisons use the above conditions, false when the carry flag is set
+        for (op, cond) in [("<", CondCode::A), ("<=", CondCode::AE), (">", CondCode::A), (">=", CondCode::AE)] {
+            let (initial, after) = comparison(op);
+            assert!(matches!(initial, Instr::Mov { src: Operand::Imm { value: 0 }, .. }));
+            assert!(matches!(&after[0], Instr::SetCC { cond: found, .. } if *found == cond), "{}", op);
+        }
+        // A NaN condition is true
+        let instructions = assemble("int f(float x){if (x) return 1; return 0;}", "f");
+        let cmp = position(&instructions, |instr| matches!(instr, Instr::Cmp { ty: AsmType::Float, .. }));
+        assert!(matches!(&instructions[cmp + 1..cmp + 3], [Instr::JmpCC { cond: CondCode::P, .. }, Instr::SetCC { cond: CondCode::NE, .. }]));
+    }
+
+    #[test]
+    fn test_bit_fields() {
+        let source = "struct s {unsigned a : 3; int b : 5; long c : 40;};
+                      void set(struct s *p, int v){p->b = v;}
+                      int get(struct s *p){return p->a + p->b;}
+                      long wide(struct s *p){return p->c;}";
+        let binaries = |instructions: &[Instr]| -> Vec<(asm::BinaryOp, i64, Reg)> {
+            instructions.iter().filter_map(|instr| match instr {
+                Instr::Binary { op, src: Operand::Imm { value }, dst: Operand::Register { reg }, .. } => Some((*op, *value, *reg)),
+                Instr::Binary { op, src: Operand::Register { reg: Reg::DX }, dst: Operand::Register { reg }, .. } => Some((*op, 0, *reg)),
+                _ => None,
+            }).collect()
+        };
+        // The value is masked to five bits and moved up past a, the storage unit is cleared where
+        // b goes, and the two are merged. The value of the assignment is then read back.
+        let instructions = assemble(source, "set");
+        assert_eq!(binaries(&instructions), vec![
+            (asm::BinaryOp::And, 0x1f, Reg::DX), (asm::BinaryOp::Sal, 3, Reg::DX),
+            (asm::BinaryOp::And, !(0x1f << 3), Reg::CX), (asm::BinaryOp::Or, 0, Reg::CX),
+            (asm::BinaryOp::Sal, 56, Reg::DX), (asm::BinaryOp::Sar, 59, Reg::DX),
+        ]);
+        let store = instructions.iter().rev().find(|instr| matches!(instr, Instr::Mov { dst: Operand::Memory { .. }, .. }));
+        assert!(matches!(store, Some(Instr::Mov { ty: AsmType::Longword, src: Operand::Register { reg: Reg::CX }, .. })));
+        // A bit-field is shifted to the top of the register and back down, arithmetically if it
+        // is signed
+        let instructions = assemble(source, "get");
+        let shifts: Vec<(asm::BinaryOp, i64, Reg)> = binaries(&instructions).into_iter()
+            .filter(|(op, _, _)| matches!(op, asm::BinaryOp::Sal | asm::BinaryOp::Sar | asm::BinaryOp::Shr))
+            .collect();
+        assert_eq!(shifts, vec![
+            (asm::BinaryOp::Sal, 61, Reg::DX), (asm::BinaryOp::Shr, 61, Reg::DX),
+            (asm::BinaryOp::Sal, 56, Reg::DX), (asm::BinaryOp::Sar, 59, Reg::DX),
+        ]);
+        // c shares an eight byte storage unit with a and b, starting right after them
+        let instructions = assemble(source, "wide");
+        assert!(instructions.iter().any(|instr| matches!(instr,
+            Instr::Mov { ty: AsmType::Quadword, src: Operand::Memory { reg: Reg::AX, offset: 0 }, dst: Operand::Register { reg: Reg::DX } })));
+        assert_eq!(binaries(&instructions), vec![(asm::BinaryOp::Sal, 16, Reg::DX), (asm::BinaryOp::Sar, 24, Reg::DX)]);
+    }
+
+    #[test]
+    fn test_function_addresses() {
+        // A function defined in the file is addressed relative to rip, one defined elsewhere
+        // through the global offset table, and either is called through the pointer
+        let source = "int ext(int x); static int local(int x){return x;}
+                      int f(int n){int (*p)(int) = n ? ext : local; return p(n) + ext(n) + (*local)(n);}";
+        let instructions = assemble(source, "f");
+        assert!(instructions.iter().any(|instr| matches!(instr,
+            Instr::Mov { ty: AsmType::Quadword, src: Operand::Got { name }, .. } if name == "ext")));
+        assert!(instructions.iter().any(|instr| matches!(instr, Instr::Lea { src: Operand::Data { name }, .. } if name == "local")));
+        let calls: Vec<&Instr> = instructions.iter().filter(|instr| matches!(instr, Instr::Call { .. } | Instr::CallIndirect { .. })).collect();
+        // Only a call naming the function directly is a direct call
+        assert!(matches!(calls[..], [Instr::CallIndirect { .. }, Instr::Call { name }, Instr::CallIndirect { .. }] if name == "ext"));
+        let got = instructions.iter().filter(|instr| matches!(instr, Instr::Mov { src: Operand::Got { .. }, .. })).count();
+        assert_eq!(got, 1);
+    }
+
+    #[test]
+    fn test_variadic_functions() {
+        let source = "int f(int n, double d, ...){__builtin_va_list ap; __builtin_va_start(ap, n);
+                      double x = __builtin_va_arg(ap, double); long y = __builtin_va_arg(ap, long); return x + y;}";
+        let instructions = assemble(source, "f");
+        // Every argument register is saved on entry, the integer registers in the first 48 bytes
+        // of a 176 byte area and the xmm registers 16 bytes apart after them
+        let lea = position(&instructions, |instr| matches!(instr, Instr::Lea { dst: Operand::Register { reg: Reg::AX }, .. }));
+        let Instr::Lea { src: save_area @ Operand::Stack { offset }, .. } = &instructions[lea] else { panic!("Expected save area") };
+        assert!(*offset <= -(REGISTER_SAVE_AREA_SIZE as i32));
+        let saved: Vec<(Reg, i32)> = instructions[lea + 1..lea + 15].iter().filter_map(|instr| match instr {
+            Instr::Mov { src: Operand::Register { reg }, dst: Operand::Memory { reg: Reg::AX, offset }, .. } => Some((*reg, *offset)),
+            _ => None,
+        }).collect();
+        let expected: Vec<(Reg, i32)> = ARGUMENT_REGISTERS.into_iter().zip((0..).step_by(8))
+            .chain(SSE_ARGUMENT_REGISTERS.into_iter().zip((48..).step_by(16)))
+            .collect();
+        assert_eq!(saved, expected);
+        assert_eq!(expected.last(), Some(&(Reg::XMM7, REGISTER_SAVE_AREA_SIZE as i32 - 16)));
+        // va_start skips the register used by each named parameter, and points at the stack
+        // arguments above the return address and the save area
+        let start = position(&instructions, |instr| matches!(instr, Instr::Mov { ty: AsmType::Longword, dst: Operand::Memory { reg: Reg::AX, offset: 0 }, .. }));
+        assert!(matches!(&instructions[start..start + 6], [
+            Instr::Mov { src: Operand::Imm { value: 8 }, .. },
+            Instr::Mov { src: Operand::Imm { value: 64 }, dst: Operand::Memory { reg: Reg::AX, offset: 4 }, .. },
+            Instr::Lea { src: Operand::Stack { offset: 16 }, dst: Operand::Register { reg: Reg::DX } },
+            Instr::Mov { dst: Operand::Memory { reg: Reg::AX, offset: 8 }, .. },
+            Instr::Lea { src: area, dst: Operand::Register { reg: Reg::DX } },
+            Instr::Mov { dst: Operand::Memory { reg: Reg::AX, offset: 16 }, .. },
+        ] if area == save_area));
+        // va_arg reads from the save area while there is room for the argument there, moving the
+        // offset for its class past it, and otherwise from the stack, moving the overflow pointer
+        let checks: Vec<(i64, i32)> = instructions.iter().filter_map(|instr| match instr {
+            Instr::Cmp { ty: AsmType::Longword, src: Operand::Imm { value }, dst: Operand::Memory { reg: Reg::AX, offset } } => Some((*value, *offset)),
+            _ => None,
+        }).collect();
+        assert_eq!(checks, vec![(REGISTER_SAVE_AREA_SIZE as i64 - 16, 4), (GP_SAVE_SIZE as i64 - 8, 0)]);
+        let steps: Vec<(i64, i32)> = instructions.iter().filter_map(|instr| match instr {
+            Instr::Binary { op: asm::BinaryOp::Add, src: Operand::Imm { value }, dst: Operand::Memory { reg: Reg::AX, offset }, .. } => Some((*value, *offset)),
+            _ => None,
+        }).collect();
+        assert_eq!(steps, vec![(16, 4), (8, 8), (8, 0), (8, 8)]);
+    }
+}
@@ -1,21 +1,238 @@
-
 /// Assembly Instructions AST
 #[derive(Debug, Clone)]
 pub enum Instr {
-    /// Represents an assembly program
-    Program{body: Box<Instr>},
-    /// Represents a function definition
-    FuncDef{name: Box<Instr>, instructions: Vec<Box<Instr>>},
+    /// Represents an assembly program, made up of functions and static variables
+    Program{body: Vec<Instr>},
+    /// Represents a function definition, global functions are visible to other object files
+    FuncDef{name: String, global: bool, instructions: Vec<Instr>},
+    /// Represents a variable with static storage, in .bss if it is zero and .data otherwise
+    StaticVariable{name: String, global: bool, ty: AsmType, init: StaticInit},
+    /// Represents a read only constant, such as a floating value which can't be an immediate, or a
+    /// string literal
+    StaticConstant{name: String, alignment: usize, ty: AsmType, init: StaticInit},
     /// Represents a Move Operation
-    Mov{src: Box<Instr>, dst: Box<Instr>},
+    Mov{ty: AsmType, src: Operand, dst: Operand},
+    /// Represents a move which sign extends a narrower source into a wider destination register
+    Movsx{src_ty: AsmType, dst_ty: AsmType, src: Operand, dst: Operand},
+    /// Represents a move which zero extends a narrower source into a wider destination register
+    MovZeroExtend{src_ty: AsmType, dst_ty: AsmType, src: Operand, dst: Operand},
+    /// Represents a unary operation performed in place on its operand
+    Unary{op: UnaryOp, ty: AsmType, operand: Operand},
+    /// Represents a binary operation, storing `dst op src` into dst
+    Binary{op: BinaryOp, ty: AsmType, src: Operand, dst: Operand},
+    /// Represents a comparison, setting the flags according to `dst - src`
+    Cmp{ty: AsmType, src: Operand, dst: Operand},
+    /// Represents a signed division of edx:eax (or rdx:rax) by the operand
+    Idiv{ty: AsmType, operand: Operand},
+    /// Represents an unsigned division of edx:eax (or rdx:rax) by the operand
+    Div{ty: AsmType, operand: Operand},
+    /// Represents sign extending eax into edx (cdq), or rax into rdx (cqo)
+    Cdq{ty: AsmType},
+    /// Represents loading the address of a memory operand into a register
+    Lea{src: Operand, dst: Operand},
+    /// Represents converting a signed integer to a float or double, written to an xmm register
+    Cvtsi2sd{src_ty: AsmType, dst_ty: AsmType, src: Operand, dst: Operand},
+    /// Represents converting a float or double to a signed integer, truncating towards zero and
+    /// written to a general purpose register
+    Cvttsd2si{src_ty: AsmType, dst_ty: AsmType, src: Operand, dst: Operand},
+    /// Represents widening a float to a double, written to an xmm register
+    Cvtss2sd{src: Operand, dst: Operand},
+    /// Represents narrowing a double to a float, written to an xmm register
+    Cvtsd2ss{src: Operand, dst: Operand},
+    /// Represents an unconditional jump
+    Jmp{target: String},
+    /// Represents a jump taken when the condition holds
+    JmpCC{cond: CondCode, target: String},
+    /// Represents setting a byte to 1 if the condition holds and 0 otherwise
+    SetCC{cond: CondCode, operand: Operand},
+    /// Represents a jump target
+    Label{name: String},
+    /// Represents an indirect jump through a table of labels in read only data, to the target
+    /// at the (zero extended) index held in the register
+    JumpTable{index: Reg, name: String, targets: Vec<String>},
+    /// Represents reserving stack space, for the function's locals or to align the stack before a
+    /// call
+    AllocateStack{bytes: i32},
+    /// Represents releasing stack space, such as arguments passed on the stack after a call
+    DeallocateStack{bytes: i32},
+    /// Represents pushing an 8 byte value onto the stack
+    Push{operand: Operand},
+    /// Represents calling a function
+    Call{name: String},
+    /// Represents calling the function whose address the operand holds
+    CallIndirect{operand: Operand},
     /// Represents a return instruction
     Ret,
-    /// Represents an immediate value
-    Imm{value: i32},
-    /// Represents a single register
-    Register, // Currently just eax
-    /// Represents an identifier
-    Identifier{value: String},
-    /// Represents a series of instructions
-    Series{instructions: Vec<Box<Instr>>}
-}
\ No newline at end of file
+}
+
+/// Initial value of static data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StaticInit {
+    /// The bits of a value of the data's type, zero fills the whole of the data
+    Scalar(i64),
+    /// The bytes of a string, truncated or padded with zeros to size
+    String{value: Vec<u8>, size: usize},
+    /// The address of a label, plus an offset in bytes
+    Address{name: String, offset: i64},
+    /// Values of parts of the data at their offsets in bytes, in order, with zeros between them
+    /// and after the last one
+    Aggregate(Vec<(usize, AsmType, StaticInit)>),
+}
+
+impl StaticInit {
+    /// Whether the data is all zero, so it can go in the .bss section
+    pub fn is_zero(&self) -> bool {
+        match self {
+            StaticInit::Scalar(value) => *value == 0,
+            StaticInit::Aggregate(values) => values.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+/// Operands of assembly instructions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    /// Represents an immediate value, only a mov into a register can take one which doesn't fit in
+    /// 32 bits
+    Imm{value: i64},
+    /// Represents a hardware register
+    Register{reg: Reg},
+    /// Represents a temporary value which has not yet been given a location
+    Pseudo{name: String},
+    /// Represents a value stored at an offset from the base pointer
+    Stack{offset: i32},
+    /// Represents a variable with static storage, addressed relative to the instruction pointer
+    Data{name: String},
+    /// Represents the global offset table entry holding the address of a symbol, which the
+    /// dynamic linker fills in for a function that may be defined in a shared library
+    Got{name: String},
+    /// Represents a value stored at an offset from the address held in a register
+    Memory{reg: Reg, offset: i32},
+    /// Represents a value stored at the address `base + index * scale`, where scale is 1, 2, 4
+    /// or 8
+    Indexed{base: Reg, index: Reg, scale: u8},
+}
+
+impl Operand {
+    /// Whether the operand lives in memory
+    pub fn is_memory(&self) -> bool {
+        matches!(self, Operand::Stack { .. } | Operand::Data { .. } | Operand::Got { .. } | Operand::Memory { .. }
+            | Operand::Indexed { .. })
+    }
+}
+
+/// Hardware registers, all of which are caller saved so functions never need to preserve them.
+/// The only callee saved register used is %rbp, which the prologue and epilogue save and restore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reg {
+    AX,
+    CX,
+    DX,
+    DI,
+    SI,
+    R8,
+    R9,
+    R10,
+    R11,
+    XMM0,
+    XMM1,
+    XMM2,
+    XMM3,
+    XMM4,
+    XMM5,
+    XMM6,
+    XMM7,
+    /// Scratch registers for rewriting floating instructions, like r10 and r11
+    XMM14,
+    XMM15,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mult,
+    And,
+    Or,
+    Xor,
+    /// Left shift
+    Sal,
+    /// Arithmetic (sign preserving) right shift, used for signed operands
+    Sar,
+    /// Logical (zero filling) right shift, used for unsigned operands
+    Shr,
+    /// Floating division, integers are divided with idiv and div instead
+    DivDouble,
+}
+
+impl BinaryOp {
+    /// Whether the operation is a shift, whose count must be an immediate or %cl
+    pub fn is_shift(&self) -> bool {
+        matches!(self, BinaryOp::Sal | BinaryOp::Sar | BinaryOp::Shr)
+    }
+}
+
+/// Condition codes for conditional jumps and sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CondCode {
+    E,
+    NE,
+    G,
+    GE,
+    L,
+    LE,
+    /// Unsigned greater than
+    A,
+    /// Unsigned greater than or equal
+    AE,
+    /// Unsigned less than
+    B,
+    /// Unsigned less than or equal
+    BE,
+    /// Parity, set by a floating comparison when either operand is a NaN
+    P,
+}
+
+/// Width of the operands of an instruction, floating values are held in xmm registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AsmType {
+    Byte,
+    Word,
+    Longword,
+    Quadword,
+    Float,
+    Double,
+    /// An array, which is only ever stored and addressed, never an operand of an instruction
+    ByteArray{size: usize, alignment: usize},
+}
+
+impl AsmType {
+    /// Size in bytes of an operand of the type
+    pub fn size(&self) -> usize {
+        match self {
+            AsmType::Byte => 1,
+            AsmType::Word => 2,
+            AsmType::Longword | AsmType::Float => 4,
+            AsmType::Quadword | AsmType::Double => 8,
+            AsmType::ByteArray { size, .. } => *size,
+        }
+    }
+
+    pub fn alignment(&self) -> usize {
+        match self {
+            AsmType::ByteArray { alignment, .. } => *alignment,
+            ty => ty.size(),
+        }
+    }
+
+    pub fn is_floating(&self) -> bool {
+        matches!(self, AsmType::Float | AsmType::Double)
+    }
+}
@@ -1,5 +1,6 @@
+use std::fmt;
 use std::path::PathBuf;
-use super::assembly_ast::Instr;
+use super::assembly_ast::{Instr, Operand, Reg, UnaryOp, BinaryOp, CondCode, AsmType, StaticInit};
 
 pub struct Emitter{
     output: String
@@ -10,7 +11,7 @@ impl Emitter{
         Emitter{output: String::new()}
     }
 
-    pub fn emit(&mut self, file: PathBuf, instr: Box<Instr>)->Result<(), EmitError>{
+    pub fn emit(&mut self, file: PathBuf, instr: Instr)->Result<(), EmitError>{
         let assembly = self.emit_str(instr)?;
         match std::fs::write(file, assembly){
             Ok(_) => {}
@@ -19,69 +20,401 @@ impl Emitter{
         Ok(())
     }
 
-    fn emit_str(&mut self, instr: Box<Instr>)->Result<String, EmitError>{
+    fn emit_str(&mut self, instr: Instr)->Result<String, EmitError>{
         self.emit_instr(instr)?;
         Ok(self.output.clone())
     }
 
-    fn emit_instr(&mut self, instr:Box<Instr>)->Result<(), EmitError>{
-        match *instr{
+    fn emit_instr(&mut self, instr:Instr)->Result<(), EmitError>{
+        match instr{
             Instr::Program { body } => {
-                self.output.push_str("    .globl main\n");
-                self.emit_instr(body)?;
-                self.output.push_str("    .section .note.GNU-stack,\"\",@progbits");
-                Ok(())
-            }
-            Instr::FuncDef { name, instructions } => {
-                let func_name = match *name {
-                    Instr::Identifier { value } => {
-                        value.clone()
-                    }
-                    _=>{return Err(EmitError::InvalidFunctionName)}
-                };
-                self.output.push_str(format!("{}:\n", func_name).as_str());
+                for function in body {
+                    self.emit_instr(function)?;
+                }
+                self.output.push_str("    .section .note.GNU-stack,\"\",@progbits\n");
+            }
+            Instr::FuncDef { name, global, instructions } => {
+                if global {
+                    self.line(&format!(".globl {}", name));
+                }
+                self.line(".text");
+                self.output.push_str(format!("{}:\n", name).as_str());
+                // Set up the stack frame
+                self.output.push_str("    pushq    %rbp\n");
+                self.output.push_str("    movq    %rsp, %rbp\n");
                 for instr in instructions{
                     self.emit_instr(instr)?;
                 }
-                Ok(())
             }
-            Instr::Mov { src, dst } => {
-                self.output.push_str("    movl");
-                self.output.push_str("    ");
-                self.emit_instr(src)?;
-                self.output.push_str(", ");
-                self.emit_instr(dst)?;
-                self.output.push_str("\n");
-                Ok(())
+            Instr::StaticVariable { name, global, ty, init } => {
+                if global {
+                    self.line(&format!(".globl {}", name));
+                }
+                let zero = init.is_zero();
+                self.line(if zero {".bss"} else {".data"});
+                self.line(&format!(".align {}", ty.alignment()));
+                self.output.push_str(format!("{}:\n", name).as_str());
+                if zero {
+                    self.line(&format!(".zero {}", ty.size()));
+                } else {
+                    self.static_init(ty, init);
+                }
             }
-            Instr::Ret => {
-                self.output.push_str("    ret\n");
-                Ok(())
+            Instr::StaticConstant { name, alignment, ty, init } => {
+                self.line(".section .rodata");
+                self.line(&format!(".align {}", alignment));
+                self.output.push_str(format!("{}:\n", name).as_str());
+                self.static_init(ty, init);
+                // Constants narrower than their alignment are padded, so a 16 byte operand
+                // read from them stays within the section
+                if ty.size() < alignment {
+                    self.line(&format!(".zero {}", alignment - ty.size()));
+                }
             }
-            Instr::Imm { value } => {
-                self.output.push_str(format!("${}", value).as_str());
-                Ok(())
+            Instr::Mov { ty, src, dst } => {
+                let src = operand(&src, ty.size())?;
+                let dst = operand(&dst, ty.size())?;
+                self.line(&format!("mov{}    {}, {}", suffix(ty), src, dst));
             }
-            Instr::Register => {
-                self.output.push_str("%eax");
-                Ok(())
+            Instr::Movsx { src_ty, dst_ty, src, dst } => {
+                let src = operand(&src, src_ty.size())?;
+                let dst = operand(&dst, dst_ty.size())?;
+                self.line(&format!("movs{}{}    {}, {}", suffix(src_ty), suffix(dst_ty), src, dst));
             }
-            Instr::Identifier { value } => {
-                self.output.push_str(value.as_str());
-                Ok(())
+            Instr::MovZeroExtend { src_ty, dst_ty, src, dst } => {
+                let src = operand(&src, src_ty.size())?;
+                let dst = operand(&dst, dst_ty.size())?;
+                self.line(&format!("movz{}{}    {}, {}", suffix(src_ty), suffix(dst_ty), src, dst));
             }
-            Instr::Series { instructions } => {
-                for instr in instructions{
-                    self.emit_instr(instr)?;
+            Instr::Unary { op, ty, operand: target } => {
+                let mnemonic = match op {
+                    UnaryOp::Neg => "neg",
+                    UnaryOp::Not => "not",
                 };
-                Ok(())
+                let target = operand(&target, ty.size())?;
+                self.line(&format!("{}{}    {}", mnemonic, suffix(ty), target));
+            }
+            Instr::Binary { op, ty, src, dst } => {
+                if ty.is_floating() {
+                    let mnemonic = match op {
+                        BinaryOp::Add => "add",
+                        BinaryOp::Sub => "sub",
+                        BinaryOp::Mult => "mul",
+                        BinaryOp::DivDouble => "div",
+                        // Bitwise operations on packed values, only used to flip the sign bit
+                        BinaryOp::Xor if ty == AsmType::Double => "xorpd",
+                        BinaryOp::Xor => "xorps",
+                        _ => return Err(EmitError::InvalidFloatingOperation),
+                    };
+                    let suffix = if mnemonic.starts_with("xor") {""} else {suffix(ty)};
+                    let src = operand(&src, ty.size())?;
+                    let dst = operand(&dst, ty.size())?;
+                    self.line(&format!("{}{}    {}, {}", mnemonic, suffix, src, dst));
+                    return Ok(());
+                }
+                let mnemonic = match op {
+                    BinaryOp::Add => "add",
+                    BinaryOp::Sub => "sub",
+                    BinaryOp::Mult => "imul",
+                    BinaryOp::And => "and",
+                    BinaryOp::Or => "or",
+                    BinaryOp::Xor => "xor",
+                    BinaryOp::Sal => "sal",
+                    BinaryOp::Sar => "sar",
+                    BinaryOp::Shr => "shr",
+                    BinaryOp::DivDouble => return Err(EmitError::InvalidFloatingOperation),
+                };
+                // Shift counts are always a single byte
+                let src_size = if op.is_shift() {1} else {ty.size()};
+                let src = operand(&src, src_size)?;
+                let dst = operand(&dst, ty.size())?;
+                self.line(&format!("{}{}    {}, {}", mnemonic, suffix(ty), src, dst));
+            }
+            Instr::Cmp { ty, src, dst } => {
+                let src = operand(&src, ty.size())?;
+                let dst = operand(&dst, ty.size())?;
+                // Floating comparisons set the flags the way unsigned ones do
+                let mnemonic = if ty.is_floating() {"comi"} else {"cmp"};
+                self.line(&format!("{}{}    {}, {}", mnemonic, suffix(ty), src, dst));
+            }
+            Instr::Lea { src, dst } => {
+                self.line(&format!("leaq    {}, {}", operand(&src, 8)?, operand(&dst, 8)?));
+            }
+            Instr::Cvtsi2sd { src_ty, dst_ty, src, dst } => {
+                let src = operand(&src, src_ty.size())?;
+                let dst = operand(&dst, dst_ty.size())?;
+                self.line(&format!("cvtsi2{}{}    {}, {}", suffix(dst_ty), suffix(src_ty), src, dst));
+            }
+            Instr::Cvttsd2si { src_ty, dst_ty, src, dst } => {
+                let src = operand(&src, src_ty.size())?;
+                let dst = operand(&dst, dst_ty.size())?;
+                self.line(&format!("cvtt{}2si{}    {}, {}", suffix(src_ty), suffix(dst_ty), src, dst));
+            }
+            Instr::Cvtss2sd { src, dst } => {
+                self.line(&format!("cvtss2sd    {}, {}", operand(&src, 4)?, operand(&dst, 8)?));
+            }
+            Instr::Cvtsd2ss { src, dst } => {
+                self.line(&format!("cvtsd2ss    {}, {}", operand(&src, 8)?, operand(&dst, 4)?));
+            }
+            Instr::Idiv { ty, operand: divisor } => {
+                let divisor = operand(&divisor, ty.size())?;
+                self.line(&format!("idiv{}    {}", suffix(ty), divisor));
+            }
+            Instr::Div { ty, operand: divisor } => {
+                let divisor = operand(&divisor, ty.size())?;
+                self.line(&format!("div{}    {}", suffix(ty), divisor));
+            }
+            Instr::Cdq { ty } => {
+                self.line(if ty == AsmType::Quadword {"cqo"} else {"cdq"});
+            }
+            Instr::Jmp { target } => {
+                self.line(&format!("jmp    .L{}", target));
+            }
+            Instr::JmpCC { cond, target } => {
+                self.line(&format!("j{}    .L{}", cond_suffix(cond), target));
+            }
+            Instr::SetCC { cond, operand: target } => {
+                let target = operand(&target, 1)?;
+                self.line(&format!("set{}    {}", cond_suffix(cond), target));
+            }
+            Instr::Label { name } => {
+                self.output.push_str(format!(".L{}:\n", name).as_str());
+            }
+            Instr::JumpTable { index, name, targets } => {
+                // The table holds offsets from its own start, so no relocations are needed
+                self.line(&format!("leaq    .L{}(%rip), %rcx", name));
+                self.line(&format!("movslq    (%rcx,{},4), %rdx", register_quad(index)));
+                self.line("addq    %rcx, %rdx");
+                self.line("jmp    *%rdx");
+                self.line(".section .rodata");
+                self.line(".align 4");
+                self.output.push_str(format!(".L{}:\n", name).as_str());
+                for target in targets {
+                    self.line(&format!(".long    .L{}-.L{}", target, name));
+                }
+                self.line(".text");
+            }
+            Instr::AllocateStack { bytes } => {
+                self.line(&format!("subq    ${}, %rsp", bytes));
+            }
+            Instr::DeallocateStack { bytes } => {
+                self.line(&format!("addq    ${}, %rsp", bytes));
+            }
+            Instr::Push { operand: value } => {
+                let value = operand(&value, 8)?;
+                self.line(&format!("pushq    {}", value));
+            }
+            Instr::Call { name } => {
+                // Going through the PLT works whether or not the function is defined in this file
+                self.line(&format!("call    {}@PLT", name));
+            }
+            Instr::CallIndirect { operand: target } => {
+                let target = operand(&target, 8)?;
+                self.line(&format!("call    *{}", target));
+            }
+            Instr::Ret => {
+                // Tear down the stack frame before returning
+                self.line("movq    %rbp, %rsp");
+                self.line("popq    %rbp");
+                self.line("ret");
             }
         }
+        Ok(())
+    }
+
+    /// Emit a single indented line
+    fn line(&mut self, line: &str){
+        self.output.push_str("    ");
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    /// Emit the directives initializing static data of type ty
+    fn static_init(&mut self, ty: AsmType, init: StaticInit){
+        match init {
+            StaticInit::Scalar(value) => self.line(&format!("{} {}", data_directive(ty), value)),
+            StaticInit::String { value, size } if value.len() < size => {
+                // The NUL terminator and any padding after it
+                self.line(&format!(".asciz \"{}\"", escape_string(&value)));
+                if size > value.len() + 1 {
+                    self.line(&format!(".zero {}", size - value.len() - 1));
+                }
+            }
+            StaticInit::String { value, size } => self.line(&format!(".ascii \"{}\"", escape_string(&value[..size]))),
+            StaticInit::Address { name, offset: 0 } => self.line(&format!(".quad {}", name)),
+            StaticInit::Address { name, offset } => self.line(&format!(".quad {}{:+}", name, offset)),
+            StaticInit::Aggregate(values) => {
+                let mut end = 0;
+                for (offset, value_ty, value) in values {
+                    if offset > end {
+                        self.line(&format!(".zero {}", offset - end));
+                    }
+                    self.static_init(value_ty, value);
+                    end = offset + value_ty.size();
+                }
+                if ty.size() > end {
+                    self.line(&format!(".zero {}", ty.size() - end));
+                }
+            }
+        }
+    }
+}
+
+/// Escape the bytes of a string for the assembler, writing anything other than printable ASCII as
+/// an octal escape
+fn escape_string(value: &[u8]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for &byte in value {
+        match byte {
+            b'"' | b'\\' => {
+                escaped.push('\\');
+                escaped.push(byte as char);
+            }
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Format an operand, using the register names for the given size in bytes. Immediates are
+/// truncated to the size, so unsigned values are written as the signed value with the same bits.
+fn operand(operand: &Operand, size: usize) -> Result<String, EmitError> {
+    match operand {
+        Operand::Imm { value } => {
+            let value = match size {
+                1 => *value as i8 as i64,
+                2 => *value as i16 as i64,
+                4 => *value as i32 as i64,
+                _ => *value,
+            };
+            Ok(format!("${}", value))
+        }
+        Operand::Register { reg } if size == 8 => Ok(register_quad(*reg).to_string()),
+        Operand::Register { reg } => Ok(register(*reg, size).to_string()),
+        Operand::Stack { offset } => Ok(format!("{}(%rbp)", offset)),
+        Operand::Data { name } => Ok(format!("{}(%rip)", name)),
+        Operand::Got { name } => Ok(format!("{}@GOTPCREL(%rip)", name)),
+        Operand::Memory { reg, offset } => Ok(format!("{}({})", offset, register_quad(*reg))),
+        Operand::Indexed { base, index, scale } => {
+            Ok(format!("({},{},{})", register_quad(*base), register_quad(*index), scale))
+        }
+        Operand::Pseudo { .. } => Err(EmitError::UnresolvedPseudo),
     }
 }
 
+fn register(reg: Reg, size: usize) -> &'static str {
+    match (reg, size) {
+        (Reg::AX, 1) => "%al",
+        (Reg::AX, 2) => "%ax",
+        (Reg::AX, _) => "%eax",
+        (Reg::CX, 1) => "%cl",
+        (Reg::CX, 2) => "%cx",
+        (Reg::CX, _) => "%ecx",
+        (Reg::DX, 1) => "%dl",
+        (Reg::DX, 2) => "%dx",
+        (Reg::DX, _) => "%edx",
+        (Reg::DI, 1) => "%dil",
+        (Reg::DI, 2) => "%di",
+        (Reg::DI, _) => "%edi",
+        (Reg::SI, 1) => "%sil",
+        (Reg::SI, 2) => "%si",
+        (Reg::SI, _) => "%esi",
+        (Reg::R8, 1) => "%r8b",
+        (Reg::R8, 2) => "%r8w",
+        (Reg::R8, _) => "%r8d",
+        (Reg::R9, 1) => "%r9b",
+        (Reg::R9, 2) => "%r9w",
+        (Reg::R9, _) => "%r9d",
+        (Reg::R10, 1) => "%r10b",
+        (Reg::R10, 2) => "%r10w",
+        (Reg::R10, _) => "%r10d",
+        (Reg::R11, 1) => "%r11b",
+        (Reg::R11, 2) => "%r11w",
+        (Reg::R11, _) => "%r11d",
+        // Vector registers have the same name whatever the size of the value
+        (reg, _) => register_quad(reg),
+    }
+}
+
+/// Full 64 bit name of a register, used for addressing and pushing
+fn register_quad(reg: Reg) -> &'static str {
+    match reg {
+        Reg::AX => "%rax",
+        Reg::CX => "%rcx",
+        Reg::DX => "%rdx",
+        Reg::DI => "%rdi",
+        Reg::SI => "%rsi",
+        Reg::R8 => "%r8",
+        Reg::R9 => "%r9",
+        Reg::R10 => "%r10",
+        Reg::R11 => "%r11",
+        Reg::XMM0 => "%xmm0",
+        Reg::XMM1 => "%xmm1",
+        Reg::XMM2 => "%xmm2",
+        Reg::XMM3 => "%xmm3",
+        Reg::XMM4 => "%xmm4",
+        Reg::XMM5 => "%xmm5",
+        Reg::XMM6 => "%xmm6",
+        Reg::XMM7 => "%xmm7",
+        Reg::XMM14 => "%xmm14",
+        Reg::XMM15 => "%xmm15",
+    }
+}
 
+fn cond_suffix(cond: CondCode) -> &'static str {
+    match cond {
+        CondCode::E => "e",
+        CondCode::NE => "ne",
+        CondCode::G => "g",
+        CondCode::GE => "ge",
+        CondCode::L => "l",
+        CondCode::LE => "le",
+        CondCode::A => "a",
+        CondCode::AE => "ae",
+        CondCode::B => "b",
+        CondCode::BE => "be",
+        CondCode::P => "p",
+    }
+}
+
+/// Instruction suffix giving the width of the operands
+fn suffix(ty: AsmType) -> &'static str {
+    match ty {
+        AsmType::Byte => "b",
+        AsmType::Word => "w",
+        AsmType::Longword => "l",
+        AsmType::Quadword => "q",
+        AsmType::Float => "ss",
+        AsmType::Double => "sd",
+        AsmType::ByteArray { .. } => unreachable!("arrays are never operands of an instruction"),
+    }
+}
+
+/// Directive giving the bits of a static value of the type
+fn data_directive(ty: AsmType) -> &'static str {
+    match ty {
+        AsmType::Byte => ".byte",
+        AsmType::Word => ".value",
+        AsmType::Longword | AsmType::Float => ".long",
+        AsmType::Quadword | AsmType::Double => ".quad",
+        AsmType::ByteArray { .. } => unreachable!("arrays are only initialized from strings"),
+    }
+}
+
+#[derive(Debug)]
 pub enum EmitError{
     FileError,
-    InvalidFunctionName,
-}
\ No newline at end of file
+    UnresolvedPseudo,
+    InvalidFloatingOperation,
+}
+
+impl fmt::Display for EmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmitError::FileError => write!(f, "unable to write assembly file"),
+            EmitError::UnresolvedPseudo => write!(f, "operand was never assigned a location"),
+            EmitError::InvalidFloatingOperation => write!(f, "operation has no floating point form"),
+        }
+    }
+}
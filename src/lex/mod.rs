@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use regex;
+use std::fmt;
 
 /// Lexer which processes source and returns a vec of tokens
 #[derive(Debug)]
@@ -14,6 +14,10 @@ pub(crate) struct Lexer {
     match_lengths: HashMap<TokenType, usize>,
     /// Current position in the source code
     position: usize,
+    /// Line of the preprocessed source at the current position
+    line: usize,
+    /// Where the lines of the preprocessed source came from
+    line_map: LineMap,
 }
 
 impl Lexer {
@@ -26,45 +30,147 @@ impl Lexer {
         // Through the source code removing each token as it is found
         token_regex.insert(TokenType::Identifier, regex::Regex::new(r"^[a-zA-Z_]\w*\b").unwrap());
         match_lengths.insert(TokenType::Identifier, 0);
-        token_regex.insert(TokenType::Constant, regex::Regex::new(r"^[0-9]+\b").unwrap());
+        // Integer constants may have an unsigned and a long or long long suffix, in either order
+        token_regex.insert(TokenType::Constant, regex::Regex::new(r"^[0-9]+(?:[uU](?:ll|LL|[lL])?|(?:ll|LL|[lL])[uU]?)?\b").unwrap());
         match_lengths.insert(TokenType::Constant, 0);
+        // Floating constants have a fraction, an exponent or both, and an optional float suffix
+        token_regex.insert(TokenType::FloatConstant, regex::Regex::new(r"^(?:(?:[0-9]*\.[0-9]+|[0-9]+\.)(?:[eE][+-]?[0-9]+)?|[0-9]+[eE][+-]?[0-9]+)[fF]?").unwrap());
+        match_lengths.insert(TokenType::FloatConstant, 0);
+        // Character constants and string literals are kept with their escape sequences, which the
+        // parser decodes
+        token_regex.insert(TokenType::CharConstant, regex::Regex::new(r#"^'(?:[^'\\\n]|\\[^\n])+'"#).unwrap());
+        match_lengths.insert(TokenType::CharConstant, 0);
+        token_regex.insert(TokenType::StringLiteral, regex::Regex::new(r#"^"(?:[^"\\\n]|\\[^\n])*""#).unwrap());
+        match_lengths.insert(TokenType::StringLiteral, 0);
         // Keywords will just be matched as identifiers, and then further identified later
-        token_regex.insert(TokenType::LeftParen, regex::Regex::new(r"^\(").unwrap());
-        match_lengths.insert(TokenType::LeftParen, 0);
-        token_regex.insert(TokenType::RightParen, regex::Regex::new(r"^\)").unwrap());
-        match_lengths.insert(TokenType::RightParen, 0);
-        token_regex.insert(TokenType::LeftBrace, regex::Regex::new(r"^\{").unwrap());
-        match_lengths.insert(TokenType::LeftBrace, 0);
-        token_regex.insert(TokenType::RightBrace, regex::Regex::new(r"^}").unwrap());
-        match_lengths.insert(TokenType::RightBrace, 0);
-        token_regex.insert(TokenType::Semicolon, regex::Regex::new(r"^;").unwrap());
-        match_lengths.insert(TokenType::Semicolon, 0);
+        // Punctuators are matched literally, the longest match wins so that e.g. `<<` is
+        // not split into two `<` tokens
+        let punctuators = [
+            (TokenType::LeftParen, "("),
+            (TokenType::RightParen, ")"),
+            (TokenType::LeftBrace, "{"),
+            (TokenType::RightBrace, "}"),
+            (TokenType::LeftBracket, "["),
+            (TokenType::RightBracket, "]"),
+            (TokenType::Semicolon, ";"),
+            (TokenType::Tilde, "~"),
+            (TokenType::Bang, "!"),
+            (TokenType::Plus, "+"),
+            (TokenType::Minus, "-"),
+            (TokenType::Star, "*"),
+            (TokenType::Slash, "/"),
+            (TokenType::Percent, "%"),
+            (TokenType::Ampersand, "&"),
+            (TokenType::Pipe, "|"),
+            (TokenType::Caret, "^"),
+            (TokenType::LessLess, "<<"),
+            (TokenType::GreaterGreater, ">>"),
+            (TokenType::AmpersandAmpersand, "&&"),
+            (TokenType::PipePipe, "||"),
+            (TokenType::EqualEqual, "=="),
+            (TokenType::BangEqual, "!="),
+            (TokenType::Less, "<"),
+            (TokenType::LessEqual, "<="),
+            (TokenType::Greater, ">"),
+            (TokenType::GreaterEqual, ">="),
+            (TokenType::Equal, "="),
+            (TokenType::PlusEqual, "+="),
+            (TokenType::MinusEqual, "-="),
+            (TokenType::StarEqual, "*="),
+            (TokenType::SlashEqual, "/="),
+            (TokenType::PercentEqual, "%="),
+            (TokenType::AmpersandEqual, "&="),
+            (TokenType::PipeEqual, "|="),
+            (TokenType::CaretEqual, "^="),
+            (TokenType::LessLessEqual, "<<="),
+            (TokenType::GreaterGreaterEqual, ">>="),
+            (TokenType::PlusPlus, "++"),
+            (TokenType::MinusMinus, "--"),
+            (TokenType::Question, "?"),
+            (TokenType::Colon, ":"),
+            (TokenType::Comma, ","),
+            (TokenType::Ellipsis, "..."),
+            (TokenType::Dot, "."),
+            (TokenType::Arrow, "->"),
+        ];
+        for (token_type, symbol) in punctuators {
+            let pattern = format!("^{}", regex::escape(symbol));
+            token_regex.insert(token_type, regex::Regex::new(&pattern).unwrap());
+            match_lengths.insert(token_type, 0);
+        }
         // Return lex object
         Lexer{
-            source, tokens, token_regex, match_lengths, position:0
+            source, tokens, token_regex, match_lengths, position:0, line:1, line_map: LineMap::default()
         }
     }
 
+    /// Line of the source currently being tokenized, used to locate errors
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Where the lines of the source came from, according to its line markers
+    pub fn line_map(&self) -> &LineMap {
+        &self.line_map
+    }
+
     fn zero_match_lengths(&mut self){
         for v in self.match_lengths.values_mut(){
             *v = 0;
         }
     }
 
+    /// Skip whitespace and preprocessor line markers, keeping track of the current line
     fn skip_whitespace(&mut self){
-        let bytes = self.source.as_bytes();
-        while self.position < self.source.len() && (bytes[self.position] as char).is_whitespace(){
-            self.position += 1;
+        while self.position < self.source.len() {
+            match self.source.as_bytes()[self.position] as char {
+                '\n' => {
+                    self.line += 1;
+                    self.position += 1;
+                }
+                '#' if self.at_line_start() => self.skip_directive(),
+                c if c.is_whitespace() => self.position += 1,
+                _ => break,
+            }
         }
     }
 
+    /// Whether only whitespace precedes the current position on its line
+    fn at_line_start(&self) -> bool {
+        self.source[..self.position].chars().rev()
+            .take_while(|&c| c != '\n')
+            .all(|c| c == ' ' || c == '\t')
+    }
+
+    /// Skip a line left by the preprocessor, such as `# 12 "file.c"` which gives the file and line
+    /// number in the original source of the line following it
+    fn skip_directive(&mut self){
+        let end = match self.source[self.position..].find('\n') {
+            Some(offset) => self.position + offset,
+            None => self.source.len(),
+        };
+        let directive = &self.source[self.position+1..end];
+        let line_number = directive.split_whitespace().next().and_then(|n| n.parse::<usize>().ok());
+        if let Some(line_number) = line_number {
+            let file = directive.split_once('"').and_then(|(_, rest)| rest.rsplit_once('"')).map(|(file, _)| file.to_string());
+            self.line_map.markers.push(LineMarker { start: self.line + 1, file, line: line_number });
+        }
+        // Consume the newline as well
+        self.line += 1;
+        self.position = (end + 1).min(self.source.len());
+    }
+
     /// Find the max match length for TokenType in match_lengths
-    fn find_max_token(&self) -> Result<TokenType, LexerError> {
+    fn find_max_token(&self) -> Result<(TokenType, usize), LexerError> {
         let max_key = match self.match_lengths.iter().max_by_key(|&(_, v)| v){
             None => {return Err(LexerError::UnknownToken)}
             Some(v) => {v}
         };
-        Ok(max_key.0.clone())
+        // Nothing matched at this position
+        if *max_key.1 == 0 {
+            return Err(LexerError::UnknownToken);
+        }
+        Ok((*max_key.0, *max_key.1))
     }
 
     /// Processes source code into series of tokens
@@ -75,6 +181,10 @@ impl Lexer {
             self.zero_match_lengths();
             // Remove any leading whitespace from the string
             self.skip_whitespace();
+            // Trailing whitespace leaves nothing left to tokenize
+            if self.position >= self.source.len() {
+                break;
+            }
             // Iterate through all possible token types, finding the match lengths
             for (&token_type, regex) in &self.token_regex {
                 // If there is a regex match
@@ -83,7 +193,9 @@ impl Lexer {
                 }
             }
             // Find which token type is being scanned
-            match self.find_max_token()? {
+            let (token_type, length) = self.find_max_token()?;
+            let token_count = self.tokens.len();
+            match token_type {
                 TokenType::Identifier => {
                     // The match can either be a identifier, or a keyword
                     // So find the match, check if it matches keywords, then
@@ -106,9 +218,12 @@ impl Lexer {
                             self.tokens.push(Token::new_return());
                             self.position += re_match.len();
                         }
-                        identifier=>{
-                            // Didn't match any keywords, it is an identifier
-                            self.tokens.push(Token::new_identifier(identifier.to_string()));
+                        word=>{
+                            // Check the remaining keywords, if none match it is an identifier
+                            match keyword(word) {
+                                Some(keyword) => self.tokens.push(Token::new_keyword(keyword)),
+                                None => self.tokens.push(Token::new_identifier(word.to_string())),
+                            }
                             self.position += re_match.len();
                         }
                     }
@@ -121,6 +236,23 @@ impl Lexer {
                     self.tokens.push(Token::new_constant(re_match.as_str().to_string()));
                     self.position += re_match.len();
                 }
+                TokenType::FloatConstant => {
+                    let lexeme = self.source[self.position..self.position + length].to_string();
+                    // Like integer constants, floating constants can't run into an identifier or
+                    // another constant
+                    let next = self.source[self.position + length..].chars().next();
+                    if next.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+                        return Err(LexerError::UnknownToken);
+                    }
+                    self.tokens.push(Token::new_float_constant(lexeme));
+                    self.position += length;
+                }
+                TokenType::CharConstant | TokenType::StringLiteral => {
+                    // The quotes are dropped, leaving the escaped characters
+                    let lexeme = self.source[self.position + 1..self.position + length - 1].to_string();
+                    self.tokens.push(Token::new_literal(token_type, lexeme));
+                    self.position += length;
+                }
                 TokenType::Int | TokenType::Void | TokenType::Return => {} //Can't actually happen
                 TokenType::LeftParen => {
                     self.tokens.push(Token::new_left_paren());
@@ -142,6 +274,14 @@ impl Lexer {
                     self.tokens.push(Token::new_semicolon());
                     self.position += 1;
                 }
+                operator => {
+                    self.tokens.push(Token::new_operator(operator));
+                    self.position += length;
+                }
+            }
+            // Record where the new token came from
+            if self.tokens.len() > token_count {
+                self.tokens[token_count].line = self.line;
             }
         }
 
@@ -150,10 +290,45 @@ impl Lexer {
 }
 
 
+/// Where the lines of preprocessed source came from, as given by the line markers the preprocessor
+/// leaves. Lines before the first marker are lines of the file being compiled.
+#[derive(Debug, Default)]
+pub(crate) struct LineMap {
+    markers: Vec<LineMarker>,
+}
+
+/// A line marker, saying that from a line of preprocessed source on, lines come from the given
+/// line of a file
+#[derive(Debug)]
+struct LineMarker {
+    /// Line of preprocessed source following the marker
+    start: usize,
+    /// The file named by the marker, or None to stay in the same file
+    file: Option<String>,
+    /// Line in the file of the line following the marker
+    line: usize,
+}
+
+impl LineMap {
+    /// The file a line of preprocessed source came from, if a marker named one, and its line in
+    /// that file
+    pub fn locate(&self, line: usize) -> (Option<&str>, usize) {
+        let markers = &self.markers[..self.markers.partition_point(|marker| marker.start <= line)];
+        let file = markers.iter().rev().find_map(|marker| marker.file.as_deref());
+        match markers.last() {
+            Some(marker) => (file, marker.line + line - marker.start),
+            None => (None, line),
+        }
+    }
+}
+
 #[derive(Hash, Eq, PartialEq, Copy, Clone, Debug)]
 pub(crate) enum TokenType {
     Identifier,
     Constant,
+    FloatConstant,
+    CharConstant,
+    StringLiteral,
     Int,
     Void,
     Return,
@@ -161,54 +336,189 @@ pub(crate) enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Semicolon,
+    Tilde,
+    Bang,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    LessLess,
+    GreaterGreater,
+    AmpersandAmpersand,
+    PipePipe,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+    AmpersandEqual,
+    PipeEqual,
+    CaretEqual,
+    LessLessEqual,
+    GreaterGreaterEqual,
+    PlusPlus,
+    MinusMinus,
+    Question,
+    Colon,
+    Comma,
+    Ellipsis,
+    Dot,
+    Arrow,
+    If,
+    Else,
+    While,
+    Do,
+    For,
+    Break,
+    Continue,
+    Switch,
+    Case,
+    Default,
+    Goto,
+    Static,
+    Extern,
+    Long,
+    Short,
+    Char,
+    Signed,
+    Unsigned,
+    Float,
+    Double,
+    Sizeof,
+    Alignof,
+    Struct,
+    Union,
+    Enum,
+    Typedef,
+    /// The builtins `<stdarg.h>` defines the va_ macros as, which are keywords since va_arg takes
+    /// a type name
+    VaStart,
+    VaArg,
+    VaEnd,
+    VaCopy,
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+/// Token type of the keyword spelled by word, if it is one
+fn keyword(word: &str) -> Option<TokenType> {
+    match word {
+        "if" => Some(TokenType::If),
+        "else" => Some(TokenType::Else),
+        "while" => Some(TokenType::While),
+        "do" => Some(TokenType::Do),
+        "for" => Some(TokenType::For),
+        "break" => Some(TokenType::Break),
+        "continue" => Some(TokenType::Continue),
+        "switch" => Some(TokenType::Switch),
+        "case" => Some(TokenType::Case),
+        "default" => Some(TokenType::Default),
+        "goto" => Some(TokenType::Goto),
+        "static" => Some(TokenType::Static),
+        "extern" => Some(TokenType::Extern),
+        "long" => Some(TokenType::Long),
+        "short" => Some(TokenType::Short),
+        "char" => Some(TokenType::Char),
+        "signed" => Some(TokenType::Signed),
+        "unsigned" => Some(TokenType::Unsigned),
+        "float" => Some(TokenType::Float),
+        "double" => Some(TokenType::Double),
+        "sizeof" => Some(TokenType::Sizeof),
+        "_Alignof" => Some(TokenType::Alignof),
+        "struct" => Some(TokenType::Struct),
+        "union" => Some(TokenType::Union),
+        "enum" => Some(TokenType::Enum),
+        "typedef" => Some(TokenType::Typedef),
+        "__builtin_va_start" => Some(TokenType::VaStart),
+        "__builtin_va_arg" => Some(TokenType::VaArg),
+        "__builtin_va_end" => Some(TokenType::VaEnd),
+        "__builtin_va_copy" => Some(TokenType::VaCopy),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Eq, Debug)]
 pub(crate) struct Token {
     pub(crate) token_type: TokenType,
     pub(crate) lexeme: Option<String>,
+    /// Line in the original source the token was found on
+    pub(crate) line: usize,
+}
+
+/// Tokens are equal when they have the same type and lexeme, regardless of where they appear
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.token_type == other.token_type && self.lexeme == other.lexeme
+    }
 }
 
 impl Token {
     fn new_identifier(lexeme: String) -> Token {
-        Token{token_type: TokenType::Identifier , lexeme: Some(lexeme)}
+        Token{token_type: TokenType::Identifier , lexeme: Some(lexeme), line: 0}
     }
 
     fn new_constant(lexeme: String) -> Token {
-        Token{token_type: TokenType::Constant, lexeme: Some(lexeme)}
+        Token{token_type: TokenType::Constant, lexeme: Some(lexeme), line: 0}
+    }
+
+    fn new_float_constant(lexeme: String) -> Token {
+        Token{token_type: TokenType::FloatConstant, lexeme: Some(lexeme), line: 0}
+    }
+
+    fn new_literal(token_type: TokenType, lexeme: String) -> Token {
+        Token{token_type, lexeme: Some(lexeme), line: 0}
     }
 
     fn new_int() -> Token {
-        Token {token_type: TokenType::Int, lexeme: None, }
+        Token {token_type: TokenType::Int, lexeme: None, line: 0, }
     }
 
     fn new_void() -> Token {
-        Token {token_type: TokenType::Void, lexeme: None, }
+        Token {token_type: TokenType::Void, lexeme: None, line: 0, }
     }
 
     fn new_return() -> Token {
-        Token {token_type: TokenType::Return, lexeme: None, }
+        Token {token_type: TokenType::Return, lexeme: None, line: 0, }
     }
 
     fn new_left_paren() -> Token {
-        Token {token_type: TokenType::LeftParen, lexeme: None, }
+        Token {token_type: TokenType::LeftParen, lexeme: None, line: 0, }
     }
 
     fn new_right_paren() -> Token {
-        Token {token_type: TokenType::RightParen, lexeme: None, }
+        Token {token_type: TokenType::RightParen, lexeme: None, line: 0, }
     }
 
     fn new_left_brace() -> Token {
-        Token {token_type: TokenType::LeftBrace, lexeme: None, }
+        Token {token_type: TokenType::LeftBrace, lexeme: None, line: 0, }
     }
 
     fn new_right_brace() -> Token {
-        Token {token_type: TokenType::RightBrace, lexeme: None, }
+        Token {token_type: TokenType::RightBrace, lexeme: None, line: 0, }
     }
 
     fn new_semicolon() -> Token {
-        Token {token_type: TokenType::Semicolon, lexeme: None, }
+        Token {token_type: TokenType::Semicolon, lexeme: None, line: 0, }
+    }
+
+    fn new_keyword(token_type: TokenType) -> Token {
+        Token {token_type, lexeme: None, line: 0, }
+    }
+
+    fn new_operator(token_type: TokenType) -> Token {
+        Token {token_type, lexeme: None, line: 0, }
     }
 
 }
@@ -219,6 +529,15 @@ pub enum LexerError {
     UnknownToken,
 }
 
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexerError::RegexFailure => write!(f, "internal lexer failure"),
+            LexerError::UnknownToken => write!(f, "unrecognized token"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test_lexer {
     use super::*;
@@ -239,4 +558,93 @@ mod test_lexer {
         assert_eq!(tokens.len(),expected_tokens.len());
         assert!(tokens.iter().zip(expected_tokens).all(|(a, b)| *a == b));
     }
+    #[test]
+    fn test_tokenize_operators() {
+        let mut lexer = Lexer::new("~a << 2 >= b&&c & -d\n".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let token_types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(token_types, vec![TokenType::Tilde, TokenType::Identifier, TokenType::LessLess,
+                                     TokenType::Constant, TokenType::GreaterEqual, TokenType::Identifier,
+                                     TokenType::AmpersandAmpersand, TokenType::Identifier,
+                                     TokenType::Ampersand, TokenType::Minus, TokenType::Identifier]);
+    }
+    #[test]
+    fn test_line_markers() {
+        let source = "# 1 \"file.c\"\nint\n# 7 \"file.c\"\n\n  main\n(";
+        let mut lexer = Lexer::new(source.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let lines: Vec<(Option<&str>, usize)> = tokens.iter().map(|t| lexer.line_map().locate(t.line)).collect();
+        assert_eq!(lines, vec![(Some("file.c"), 1), (Some("file.c"), 8), (Some("file.c"), 9)]);
+        // Each line is located in the file its marker names, and a marker without one stays in
+        // the same file
+        let source = "int a;\n# 1 \"inc.h\" 1\nint b;\n# 4\nint c;\n# 3 \"main.c\" 2\nint d;";
+        let mut lexer = Lexer::new(source.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let lines: Vec<(Option<&str>, usize)> = tokens.iter().step_by(3).map(|t| lexer.line_map().locate(t.line)).collect();
+        assert_eq!(lines, vec![(None, 1), (Some("inc.h"), 1), (Some("inc.h"), 4), (Some("main.c"), 3)]);
+    }
+    #[test]
+    fn test_constant_suffixes() {
+        let mut lexer = Lexer::new("1 2u 3L 4ul 5LU 6ll 7ULL".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let lexemes: Vec<&str> = tokens.iter().map(|t| t.lexeme.as_deref().unwrap()).collect();
+        assert_eq!(lexemes, vec!["1", "2u", "3L", "4ul", "5LU", "6ll", "7ULL"]);
+        // A suffix can't be followed by anything else
+        let mut lexer = Lexer::new("1lul".to_string());
+        assert!(lexer.tokenize().is_err());
+    }
+    #[test]
+    fn test_float_constants() {
+        let mut lexer = Lexer::new("1.5 .5 2. 1e10 3.0E-2f 4e+1F 7".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let token_types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(token_types[..6], [TokenType::FloatConstant; 6]);
+        assert_eq!(token_types[6], TokenType::Constant);
+        let lexemes: Vec<&str> = tokens.iter().map(|t| t.lexeme.as_deref().unwrap()).collect();
+        assert_eq!(lexemes, vec!["1.5", ".5", "2.", "1e10", "3.0E-2f", "4e+1F", "7"]);
+        for source in ["1.5x", "1.2.3", "1e"] {
+            let mut lexer = Lexer::new(source.to_string());
+            assert!(lexer.tokenize().is_err());
+        }
+    }
+    #[test]
+    fn test_literals() {
+        let mut lexer = Lexer::new(r#"'a' '\'' "hi\n" "say \"x\"" """#.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let token_types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(token_types, [TokenType::CharConstant, TokenType::CharConstant, TokenType::StringLiteral,
+                                 TokenType::StringLiteral, TokenType::StringLiteral]);
+        let lexemes: Vec<&str> = tokens.iter().map(|t| t.lexeme.as_deref().unwrap()).collect();
+        assert_eq!(lexemes, vec!["a", r"\'", r"hi\n", r#"say \"x\""#, ""]);
+        for source in ["''", "'a", "\"abc", "\"a\nb\""] {
+            let mut lexer = Lexer::new(source.to_string());
+            assert!(lexer.tokenize().is_err());
+        }
+    }
+
+    #[test]
+    fn test_member_access() {
+        let mut lexer = Lexer::new("union struct s a; a.b->c .5 x->y-->z".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let token_types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(token_types, [TokenType::Union, TokenType::Struct, TokenType::Identifier, TokenType::Identifier, TokenType::Semicolon,
+                                 TokenType::Identifier, TokenType::Dot, TokenType::Identifier, TokenType::Arrow,
+                                 TokenType::Identifier, TokenType::FloatConstant, TokenType::Identifier, TokenType::Arrow,
+                                 TokenType::Identifier, TokenType::MinusMinus, TokenType::Greater, TokenType::Identifier]);
+    }
+
+    #[test]
+    fn test_builtins() {
+        let mut lexer = Lexer::new("__builtin_va_start __builtin_va_arg __builtin_va_end __builtin_va_copy __builtin_va_list".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let token_types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        // va_list is a typedef name the parser declares, rather than a keyword
+        assert_eq!(token_types, [TokenType::VaStart, TokenType::VaArg, TokenType::VaEnd, TokenType::VaCopy, TokenType::Identifier]);
+    }
+
+    #[test]
+    fn test_unknown_token() {
+        let mut lexer = Lexer::new("return @;".to_string());
+        assert!(lexer.tokenize().is_err());
+    }
 }
@@ -1,12 +1,15 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, ExitCode};
 
 use clap::{Parser};
 use crate::assemble::emmiting::Emitter;
+use crate::lex::LineMap;
+use crate::validate::evaluating::Warning;
 
 mod lex;
 mod parse;
+mod validate;
 mod assemble;
 
 #[derive(Parser)]
@@ -19,10 +22,14 @@ struct Cli {
     #[arg(short, long, action = clap::ArgAction::SetTrue)]
     lex: bool,
 
-    /// Run the lex and parse, but stop before assembly generation
+    /// Run the lex and parse, but stop before semantic analysis
     #[arg(short, long, action = clap::ArgAction::SetTrue)]
     parse: bool,
 
+    /// Run the lex, parse and semantic analysis, but stop before assembly generation
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    validate: bool,
+
     /// Perform lexing, parsing, and assembly generation, but stop before code emission
     #[arg(short, long, action = clap::ArgAction::SetTrue)]
     codegen: bool,
@@ -30,12 +37,17 @@ struct Cli {
     /// Don't delete the assembly file
     #[arg(short, long, action = clap::ArgAction::SetTrue)]
     keepassembly: bool,
+
+    /// Assemble to an object file instead of linking an executable, so it can be linked with
+    /// code from other compilers
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    object: bool,
 }
 
 fn main() -> ExitCode {
     let cli = Cli::parse();
     // Preprocess source file
-    let _response = Command::new("gcc").arg("-E").arg("-P").arg(cli.file.clone()).arg("-o").arg(cli.file.with_extension("i")).output().expect("Failed to preprocess file");
+    let _response = Command::new("gcc").arg("-E").arg(cli.file.clone()).arg("-o").arg(cli.file.with_extension("i")).output().expect("Failed to preprocess file");
 
     // Read in the preprocessed file and compile it to assembly
     let source_file = fs::read_to_string(cli.file.with_extension("i")).expect("Unable to read preprocessed file");
@@ -46,38 +58,105 @@ fn main() -> ExitCode {
     let mut lexer = lex::Lexer::new(source_file);
     let tokens = match lexer.tokenize() {
         Ok(tokens) => tokens,
-        Err(_) => return ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{}: error: {}", location(&cli.file, lexer.line_map(), lexer.line()), e);
+            return ExitCode::FAILURE;
+        }
     };
     if cli.lex {
         return ExitCode::SUCCESS;
     }
     // Compile the source file to an AST
     let mut parser = parse::parsing::Parser::new(tokens);
-    let program_ast = match parser.parse() {
+    let result = parser.parse();
+    print_warnings(&cli.file, lexer.line_map(), parser.warnings());
+    let mut program_ast = match result {
         Ok(program_ast) => program_ast,
-        Err(_) => return ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{}: error: {}", location(&cli.file, lexer.line_map(), parser.line()), e);
+            return ExitCode::FAILURE;
+        }
     };
     if cli.parse {
+        parse::printing::Printer::new().print_stmt(&program_ast);
+        return ExitCode::SUCCESS;
+    }
+    // Resolve variables to their declarations
+    let mut resolver = validate::resolving::Resolver::new();
+    if let Err(e) = resolver.resolve(&mut program_ast) {
+        eprintln!("{}: error: {}", location(&cli.file, lexer.line_map(), e.line()), e);
+        return ExitCode::FAILURE;
+    }
+    // Bind break, continue and goto statements to their targets
+    let mut labeler = validate::labeling::Labeler::new();
+    if let Err(e) = labeler.label(&mut program_ast) {
+        eprintln!("{}: error: {}", location(&cli.file, lexer.line_map(), e.line()), e);
+        return ExitCode::FAILURE;
+    }
+    // Check that functions and variables are declared and used consistently
+    let mut type_checker = validate::typechecking::TypeChecker::new();
+    let result = type_checker.check(&mut program_ast);
+    print_warnings(&cli.file, lexer.line_map(), type_checker.warnings());
+    if let Err(e) = result {
+        eprintln!("{}: error: {}", location(&cli.file, lexer.line_map(), e.line()), e);
+        return ExitCode::FAILURE;
+    }
+    if cli.validate {
         return ExitCode::SUCCESS;
     }
     // Assemble the c_ast into an assembly ast
-    let assembler = assemble::assembling::Assembler::new();
+    let mut assembler = assemble::assembling::Assembler::new(type_checker.into_symbols());
     let assembly_ast = match assembler.assemble(program_ast) {
         Ok(assembly_ast) => assembly_ast,
-        Err(_) => return ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
     };
+    if cli.codegen {
+        return ExitCode::SUCCESS;
+    }
     // Emit the assembly to a file
     let mut emitter = Emitter::new();
     match emitter.emit(cli.file.with_extension("s"), assembly_ast) {
         Ok(_) => (),
-        Err(_) => return ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
     };
-    // Link the assembly file
-    _ = Command::new("gcc").arg(cli.file.with_extension("s")).arg("-o").arg(cli.file.with_extension("")).output().expect("Unable to link assembly file");
+    // Assemble and link the assembly file, or only assemble it into an object file
+    let mut gcc = Command::new("gcc");
+    if cli.object {
+        gcc.arg("-c").arg(cli.file.with_extension("s")).arg("-o").arg(cli.file.with_extension("o"));
+    } else {
+        gcc.arg(cli.file.with_extension("s")).arg("-o").arg(cli.file.with_extension(""));
+    }
+    let response = gcc.output().expect("Unable to link assembly file");
     // Delete the assembly file
     if !cli.keepassembly {
         _ = fs::remove_file(cli.file.with_extension("s"));
     }
+    if !response.status.success() {
+        eprint!("{}", String::from_utf8_lossy(&response.stderr));
+        return ExitCode::FAILURE;
+    }
     // If succesful, return 0
     ExitCode::SUCCESS
 }
+
+/// Print warnings found compiling a file, which don't stop it compiling
+fn print_warnings(file: &Path, line_map: &LineMap, warnings: &[Warning]) {
+    for warning in warnings {
+        eprintln!("{}: warning: {}", location(file, line_map, warning.line()), warning);
+    }
+}
+
+/// The file and line a line of the preprocessed source came from, for a diagnostic. Lines the
+/// preprocessor didn't mark are lines of the file being compiled.
+fn location(file: &Path, line_map: &LineMap, line: usize) -> String {
+    match line_map.locate(line) {
+        (Some(name), line) => format!("{}:{}", name, line),
+        (None, line) => format!("{}:{}", file.display(), line),
+    }
+}
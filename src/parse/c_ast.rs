@@ -1,20 +1,646 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// An expression, along with the line of the source it was found on and its type, which is
+/// filled in by the type checker
 #[derive(Debug, Clone)]
-pub enum Expr {
-    IntConstant{value: i32},
+pub struct Expr {
+    pub kind: ExprKind,
+    pub line: usize,
+    pub ty: Option<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Constant{value: Const},
+    /// A string literal, an array of chars holding the decoded bytes followed by a NUL
+    String{value: Vec<u8>},
     Identifier{value: String},
+    Unary{op: UnaryOp, expr: Box<Expr>},
+    Binary{op: BinaryOp, left: Box<Expr>, right: Box<Expr>},
+    Assignment{left: Box<Expr>, right: Box<Expr>},
+    /// Compound assignment such as `a += b`, prefix increments and decrements are represented as
+    /// `a += 1` and `a -= 1`
+    CompoundAssignment{op: BinaryOp, left: Box<Expr>, right: Box<Expr>},
+    /// Postfix increment (op is Add) or decrement (op is Subtract), evaluating to the old value
+    Postfix{op: BinaryOp, expr: Box<Expr>},
+    Conditional{condition: Box<Expr>, then_expr: Box<Expr>, else_expr: Box<Expr>},
+    /// A call of a function, or of a pointer to one. Once type checked the function is always a
+    /// pointer, a call of a function by its name being a call through its address.
+    FunctionCall{function: Box<Expr>, args: Vec<Expr>},
+    /// Explicit casts, and the implicit conversions inserted by the type checker
+    Cast{target: Type, expr: Box<Expr>},
+    /// The unary `&` operator, giving a pointer to an lvalue or function. The type checker also
+    /// uses it for the conversion of an array to a pointer to its first element, and of a
+    /// function to a pointer to it.
+    AddressOf{expr: Box<Expr>},
+    /// The unary `*` operator, giving the object a pointer points to
+    Dereference{expr: Box<Expr>},
+    /// `array[index]`, once type checked the array is the operand with pointer type
+    Subscript{array: Box<Expr>, index: Box<Expr>},
+    /// `sizeof expr`, whose operand is never evaluated. The type checker replaces it, and the
+    /// other size and alignment operators, with an unsigned long constant.
+    SizeOf{expr: Box<Expr>},
+    /// `sizeof (type)`
+    SizeOfType{ty: Type},
+    /// `_Alignof (type)`
+    AlignOf{ty: Type},
+    /// `object.member`, `pointer->member` is parsed as `(*pointer).member`
+    Member{expr: Box<Expr>, member: String},
+    /// `__builtin_va_start(ap, last)`, starting a walk through the variadic arguments of the
+    /// function. Only ap is kept, since the arguments are found without the last parameter.
+    VaStart{ap: Box<Expr>},
+    /// `__builtin_va_arg(ap, type)`, the next variadic argument
+    VaArg{ap: Box<Expr>, ty: Type},
+    /// `__builtin_va_end(ap)`, which has nothing to clean up
+    VaEnd{ap: Box<Expr>},
+    /// `__builtin_va_copy(dst, src)`
+    VaCopy{dst: Box<Expr>, src: Box<Expr>},
 }
 
 impl Expr {
-    pub fn new_int(value: &str) -> Expr {
-        Expr::IntConstant {
-            value: value.parse::<i32>().unwrap(),
+    pub fn new(kind: ExprKind, line: usize) -> Expr {
+        Expr { kind, line, ty: None }
+    }
+
+    /// Type of the expression, which is known once it has been type checked
+    pub fn ty(&self) -> &Type {
+        self.ty.as_ref().expect("expression has not been type checked")
+    }
+
+    /// Whether the expression designates an object which can be assigned to
+    pub fn is_lvalue(&self) -> bool {
+        match &self.kind {
+            ExprKind::Identifier { .. } | ExprKind::String { .. } | ExprKind::Dereference { .. }
+            | ExprKind::Subscript { .. } => true,
+            // A member of a structure which isn't an lvalue, such as one returned by a function,
+            // isn't either
+            ExprKind::Member { expr, .. } => expr.is_lvalue(),
+            _ => false,
+        }
+    }
+}
+
+/// A constant of one of the types a literal can have
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Const {
+    Int(i32),
+    Long(i64),
+    UInt(u32),
+    ULong(u64),
+    Float(f32),
+    Double(f64),
+}
+
+impl Const {
+    pub fn ty(&self) -> Type {
+        match self {
+            Const::Int(_) => Type::Int,
+            Const::Long(_) => Type::Long,
+            Const::UInt(_) => Type::UInt,
+            Const::ULong(_) => Type::ULong,
+            Const::Float(_) => Type::Float,
+            Const::Double(_) => Type::Double,
+        }
+    }
+
+    /// The value as a 64 bit pattern, integers are sign or zero extended according to their type
+    /// and floating values give their IEEE 754 representation
+    pub fn bits(&self) -> i64 {
+        match *self {
+            Const::Int(value) => value as i64,
+            Const::Long(value) => value,
+            Const::UInt(value) => value as i64,
+            Const::ULong(value) => value as i64,
+            Const::Float(value) => value.to_bits() as i64,
+            Const::Double(value) => value.to_bits() as i64,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match *self {
+            Const::Float(value) => value == 0.0,
+            Const::Double(value) => value == 0.0,
+            _ => self.bits() == 0,
+        }
+    }
+
+    /// The constant converted to type ty as though by a cast. Floating values converted to an
+    /// integer type are truncated towards zero, saturating if they are out of range.
+    pub fn convert(&self, ty: &Type) -> Const {
+        match (*self, ty) {
+            (Const::Float(value), Type::Double) => Const::Double(value as f64),
+            (Const::Double(value), Type::Float) => Const::Float(value as f32),
+            (Const::Float(_) | Const::Double(_), Type::Float | Type::Double) => *self,
+            (Const::Float(value), _) => Const::Double(value as f64).convert(ty),
+            (Const::Double(value), _) if ty.is_signed() => Const::from_bits(ty, value as i64),
+            (Const::Double(value), _) => Const::from_bits(ty, value as u64 as i64),
+            // Integers are converted from their value, which for unsigned types is their bits
+            // read as unsigned
+            (_, Type::Float) if self.ty().is_signed() => Const::Float(self.bits() as f32),
+            (_, Type::Float) => Const::Float(self.bits() as u64 as f32),
+            (_, Type::Double) if self.ty().is_signed() => Const::Double(self.bits() as f64),
+            (_, Type::Double) => Const::Double(self.bits() as u64 as f64),
+            _ => Const::from_bits(ty, self.bits()),
+        }
+    }
+
+    /// The constant of integer type ty with the given bits, converted as though by a cast. Types
+    /// narrower than int give an int, since they are promoted whenever they are used.
+    pub fn from_bits(ty: &Type, bits: i64) -> Const {
+        let bits = ty.convert_bits(bits);
+        match ty.promote() {
+            Type::Long => Const::Long(bits),
+            Type::UInt => Const::UInt(bits as u32),
+            // Pointers converted from constants are addresses, which are unsigned
+            Type::ULong | Type::Pointer(_) => Const::ULong(bits as u64),
+            _ => Const::Int(bits as i32),
+        }
+    }
+}
+
+impl fmt::Display for Const {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Const::Int(value) => write!(f, "{}", value),
+            Const::Long(value) => write!(f, "{}L", value),
+            Const::UInt(value) => write!(f, "{}U", value),
+            Const::ULong(value) => write!(f, "{}UL", value),
+            Const::Float(value) => write!(f, "{:?}F", value),
+            Const::Double(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// Types of objects and functions, long long has the same representation as long so is treated as
+/// the same type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    /// Plain char, which is signed
+    Char,
+    SChar,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Long,
+    ULong,
+    Float,
+    Double,
+    /// The type of an expression with no value, and of what a generic pointer points to
+    Void,
+    Pointer(Box<Type>),
+    /// The size is None if it was left out, making the type incomplete
+    Array{element: Box<Type>, size: Option<usize>},
+    Function{params: Vec<Type>, ret: Box<Type>, variadic: bool},
+    Struct(Rc<StructType>),
+}
+
+/// A structure or union type, shared by every use of its tag within the scope it is declared in,
+/// so that defining its members completes the type everywhere it is used. Two structure types are
+/// the same only if they are the same declaration.
+pub struct StructType {
+    /// The tag, which is empty for an anonymous structure
+    pub tag: String,
+    /// Whether the members all share the same storage
+    pub union: bool,
+    /// The layout of the members, None until the structure is defined
+    pub definition: RefCell<Option<StructDef>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    pub members: Vec<Member>,
+    pub size: usize,
+    pub alignment: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    /// Offset in bytes from the start of the structure. For a bit-field this is the offset of the
+    /// storage unit holding it, an object of its type.
+    pub offset: usize,
+    pub bits: Option<BitField>,
+}
+
+/// Where a bit-field is within its storage unit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitField {
+    /// Position of the lowest bit
+    pub offset: usize,
+    pub width: usize,
+}
+
+impl StructType {
+    pub fn new(tag: String, union: bool) -> StructType {
+        StructType { tag, union, definition: RefCell::new(None) }
+    }
+
+    /// The keyword introducing the type
+    pub fn keyword(&self) -> &'static str {
+        if self.union {"union"} else {"struct"}
+    }
+
+    /// The member with the given name, if the structure is defined and has one
+    pub fn member(&self, name: &str) -> Option<Member> {
+        self.definition.borrow().as_ref()?.members.iter().find(|member| member.name == name).cloned()
+    }
+}
+
+impl Member {
+    /// Type of the member's value. As in gcc, a bit-field narrower than int is an int whatever its
+    /// declared type, and so are those of exactly the width of int unless they are unsigned.
+    pub fn value_type(&self) -> Type {
+        match self.bits {
+            Some(bits) if bits.width < 32 || (bits.width == 32 && self.ty.is_signed()) => Type::Int,
+            Some(bits) if bits.width == 32 => Type::UInt,
+            _ => self.ty.clone(),
+        }
+    }
+}
+
+impl StructDef {
+    /// Lay out members of complete types, and bit-fields of the given widths, as the System V ABI
+    /// does. Each member is placed at the next offset which is a multiple of its alignment, or at
+    /// offset 0 in a union, and the structure is aligned like its most aligned member, with
+    /// padding at the end to make its size a multiple of that.
+    ///
+    /// A bit-field is placed at the next free bit unless it would then straddle a boundary of its
+    /// storage unit, in which case it starts the next unit. Unnamed bit-fields, which have an
+    /// empty name and aren't members, only take up space and don't affect the alignment, and one
+    /// of width 0 just moves on to the next unit.
+    pub fn layout(members: Vec<(String, Type, Option<usize>)>, union: bool) -> StructDef {
+        // Position of the next free bit, which for a union is the end of its largest member
+        let mut end: usize = 0;
+        let mut alignment = 1;
+        let mut laid_out = Vec::new();
+        for (name, ty, width) in members {
+            let start = if union {0} else {end};
+            let member_end = match width {
+                None => {
+                    let offset = start.div_ceil(8).next_multiple_of(ty.alignment());
+                    alignment = alignment.max(ty.alignment());
+                    let member_end = 8 * (offset + ty.size());
+                    laid_out.push(Member { name, ty, offset, bits: None });
+                    member_end
+                }
+                Some(width) => {
+                    let unit = 8 * ty.size();
+                    let offset = if width == 0 || start / unit != (start + width - 1) / unit {
+                        start.next_multiple_of(unit)
+                    } else {
+                        start
+                    };
+                    if !name.is_empty() {
+                        alignment = alignment.max(ty.alignment());
+                        let bits = Some(BitField { offset: offset % unit, width });
+                        laid_out.push(Member { name, offset: offset / unit * ty.size(), ty, bits });
+                    }
+                    offset + width
+                }
+            };
+            end = end.max(member_end);
+        }
+        StructDef { members: laid_out, size: end.div_ceil(8).next_multiple_of(alignment), alignment }
+    }
+}
+
+impl PartialEq for StructType {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for StructType {}
+
+/// Only the tag is shown, since a structure can contain a pointer to itself
+impl fmt::Debug for StructType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.keyword(), self.tag)
+    }
+}
+
+/// Tag of the structure holding the state of a walk through variadic arguments
+pub const VA_LIST_TAG: &str = "__va_list_tag";
+
+impl Type {
+    /// A new `__builtin_va_list` type, which the System V ABI makes an array of one structure
+    /// holding the offsets of the next arguments in the register save area, and where the next
+    /// argument passed on the stack is. Being an array, it is passed to functions by reference.
+    /// As a structure type is only the same as itself, a program must use a single instance.
+    pub fn va_list() -> Type {
+        let members = [("gp_offset", Type::UInt), ("fp_offset", Type::UInt),
+                       ("overflow_arg_area", Type::Pointer(Box::new(Type::Void))),
+                       ("reg_save_area", Type::Pointer(Box::new(Type::Void)))];
+        let tag = StructType::new(VA_LIST_TAG.to_string(), false);
+        let members = members.into_iter().map(|(name, ty)| (name.to_string(), ty, None)).collect();
+        *tag.definition.borrow_mut() = Some(StructDef::layout(members, false));
+        Type::Array { element: Box::new(Type::Struct(Rc::new(tag))), size: Some(1) }
+    }
+
+    /// Whether the type is what a va_list converts to, a pointer to its structure
+    pub fn is_va_list_pointer(&self) -> bool {
+        matches!(self, Type::Pointer(inner) if matches!(&**inner, Type::Struct(struct_type) if struct_type.tag == VA_LIST_TAG))
+    }
+
+    /// Size in bytes of an object of the type
+    pub fn size(&self) -> usize {
+        match self {
+            Type::Char | Type::SChar | Type::UChar => 1,
+            Type::Short | Type::UShort => 2,
+            Type::Int | Type::UInt | Type::Float => 4,
+            Type::Long | Type::ULong | Type::Double | Type::Pointer(_) => 8,
+            Type::Array { element, size } => element.size() * size.unwrap_or(0),
+            Type::Struct(struct_type) => struct_type.definition.borrow().as_ref().map_or(0, |definition| definition.size),
+            Type::Void | Type::Function { .. } => 0,
         }
     }
+
+    /// Alignment in bytes of an object of the type, arrays are aligned like their elements
+    pub fn alignment(&self) -> usize {
+        match self {
+            Type::Array { element, .. } => element.alignment(),
+            Type::Struct(struct_type) => struct_type.definition.borrow().as_ref().map_or(0, |definition| definition.alignment),
+            ty => ty.size(),
+        }
+    }
+
+    pub fn is_floating(&self) -> bool {
+        matches!(self, Type::Float | Type::Double)
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, Type::Pointer(_))
+    }
+
+    /// Whether the type is an integer or floating type, which arithmetic operators apply to
+    pub fn is_arithmetic(&self) -> bool {
+        !matches!(self, Type::Void | Type::Pointer(_) | Type::Array { .. } | Type::Function { .. } | Type::Struct(_))
+    }
+
+    /// Whether the type is arithmetic or a pointer, which can be compared to zero
+    pub fn is_scalar(&self) -> bool {
+        self.is_arithmetic() || self.is_pointer()
+    }
+
+    /// Whether the size of the type is known, void and functions aren't objects so have none, a
+    /// structure has none until its members are defined, and an array none if it isn't given
+    pub fn is_complete(&self) -> bool {
+        match self {
+            Type::Void | Type::Function { .. } => false,
+            Type::Struct(struct_type) => struct_type.definition.borrow().is_some(),
+            Type::Array { size, .. } => size.is_some(),
+            _ => true,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.is_arithmetic() && !self.is_floating()
+    }
+
+    /// Whether the type is one of the char types, arrays of which can hold strings
+    pub fn is_character(&self) -> bool {
+        matches!(self, Type::Char | Type::SChar | Type::UChar)
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Type::Char | Type::SChar | Type::Short | Type::Int | Type::Long)
+    }
+
+    /// The type an operand is converted to by the integer promotions, types narrower than int
+    /// become int
+    pub fn promote(&self) -> Type {
+        match self {
+            Type::Char | Type::SChar | Type::UChar | Type::Short | Type::UShort => Type::Int,
+            ty => ty.clone(),
+        }
+    }
+
+    /// The type both operands of a binary operator are converted to by the usual arithmetic
+    /// conversions
+    pub fn common(left: &Type, right: &Type) -> Type {
+        let (left, right) = (left.promote(), right.promote());
+        if left == Type::Double || right == Type::Double {
+            Type::Double
+        } else if left == Type::Float || right == Type::Float {
+            Type::Float
+        } else if left == right {
+            left
+        } else if left.size() == right.size() {
+            // Of two types with the same width, the unsigned one can hold the other's bits
+            if left.is_signed() {right} else {left}
+        } else if left.size() > right.size() {
+            // A wider type can represent every value of a narrower one, signed or not
+            left
+        } else {
+            right
+        }
+    }
+
+    /// Convert a 64 bit pattern to a value of this integer type, truncating it to the type's width
+    /// and then sign or zero extending it back to 64 bits
+    pub fn convert_bits(&self, bits: i64) -> i64 {
+        match (self.size(), self.is_signed()) {
+            (1, true) => bits as i8 as i64,
+            (1, false) => bits as u8 as i64,
+            (2, true) => bits as i16 as i64,
+            (2, false) => bits as u16 as i64,
+            (4, true) => bits as i32 as i64,
+            (4, false) => bits as u32 as i64,
+            _ => bits,
+        }
+    }
+}
+
+impl Type {
+    /// The type written as C would, around a declarator string which is empty for an abstract
+    /// declarator. Pointers to arrays and functions need parentheses, as in "int (*)[3]".
+    fn declaration(&self, declarator: String) -> String {
+        let name = match self {
+            Type::Char => "char",
+            Type::SChar => "signed char",
+            Type::UChar => "unsigned char",
+            Type::Short => "short",
+            Type::UShort => "unsigned short",
+            Type::Int => "int",
+            Type::UInt => "unsigned int",
+            Type::Long => "long",
+            Type::ULong => "unsigned long",
+            Type::Float => "float",
+            Type::Double => "double",
+            Type::Void => "void",
+            Type::Struct(struct_type) => {
+                let tag = if struct_type.tag.is_empty() {"<anonymous>"} else {&struct_type.tag};
+                return format!("{} {}{}", struct_type.keyword(), tag, pad_declarator(declarator));
+            }
+            Type::Pointer(inner) => {
+                return match **inner {
+                    Type::Array { .. } | Type::Function { .. } => inner.declaration(format!("(*{})", declarator)),
+                    _ => inner.declaration(format!("*{}", declarator)),
+                };
+            }
+            Type::Array { element, size: Some(size) } => return element.declaration(format!("{}[{}]", declarator, size)),
+            Type::Array { element, size: None } => return element.declaration(format!("{}[]", declarator)),
+            Type::Function { params, ret, variadic } => {
+                let mut params: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+                if *variadic {
+                    params.push("...".to_string());
+                }
+                if params.is_empty() {
+                    params.push("void".to_string());
+                }
+                // The parameter list is separated from a plain return type, as in "int (void)"
+                let declarator = format!("{}({})", declarator, params.join(", "));
+                return match **ret {
+                    Type::Pointer(_) => ret.declaration(declarator),
+                    _ => format!("{} {}", ret.declaration(String::new()), declarator),
+                };
+            }
+        };
+        format!("{}{}", name, pad_declarator(declarator))
+    }
+}
+
+/// The declarator following a type name, separated from it by a space unless it is empty or an
+/// array suffix
+fn pad_declarator(declarator: String) -> String {
+    if declarator.is_empty() || declarator.starts_with('[') {
+        declarator
+    } else {
+        format!(" {}", declarator)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.declaration(String::new()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Complement,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    ShiftLeft,
+    ShiftRight,
+    And,
+    Or,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessOrEqual,
+    GreaterThan,
+    GreaterOrEqual,
+}
+
+impl BinaryOp {
+    /// Binding strength of the operator, higher binds tighter
+    pub fn precedence(&self) -> u32 {
+        match self {
+            BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Remainder => 50,
+            BinaryOp::Add | BinaryOp::Subtract => 45,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 40,
+            BinaryOp::LessThan | BinaryOp::LessOrEqual
+            | BinaryOp::GreaterThan | BinaryOp::GreaterOrEqual => 35,
+            BinaryOp::Equal | BinaryOp::NotEqual => 30,
+            BinaryOp::BitwiseAnd => 25,
+            BinaryOp::BitwiseXor => 20,
+            BinaryOp::BitwiseOr => 15,
+            BinaryOp::And => 10,
+            BinaryOp::Or => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageClass {
+    Static,
+    Extern,
 }
 
 #[derive(Debug, Clone)]
 pub enum Stmt {
-    Program{body: Box<Stmt>},
-    FuncDef{name: Box<Expr>, body: Box<Stmt>},
-    Return{value: Box<Expr>},
-}
\ No newline at end of file
+    /// A translation unit, made up of top level declarations
+    Program{body: Vec<Stmt>},
+    /// Functions have a function type, giving the types of their parameters
+    FuncDef{name: Box<Expr>, params: Vec<String>, ty: Type, storage: Option<StorageClass>, body: Vec<Stmt>},
+    /// A function declaration without a body
+    FuncDecl{name: Box<Expr>, params: Vec<String>, ty: Type, storage: Option<StorageClass>},
+    Declaration{name: String, ty: Type, init: Option<Initializer>, storage: Option<StorageClass>, line: usize},
+    /// A declaration of a structure tag and nothing else, the parser has already defined the
+    /// members of the type if they are given
+    StructDecl{ty: Type},
+    /// A return statement, whose value is omitted in a function returning void
+    Return{value: Option<Box<Expr>>},
+    Expression{expr: Box<Expr>},
+    If{condition: Box<Expr>, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>>},
+    Compound{body: Vec<Stmt>},
+    /// Loops, whose labels are filled in by the labeling pass and identify the loop to break and
+    /// continue statements
+    While{condition: Box<Expr>, body: Box<Stmt>, label: String},
+    DoWhile{body: Box<Stmt>, condition: Box<Expr>, label: String},
+    /// The init clause is the declarations of a declaration, an expression statement, or nothing
+    For{init: Vec<Stmt>, condition: Option<Box<Expr>>, post: Option<Box<Expr>>, body: Box<Stmt>, label: String},
+    /// The labeling pass also collects the value and label of each case in the body, and the
+    /// label of the default case if there is one
+    Switch{condition: Box<Expr>, body: Box<Stmt>, label: String, cases: Vec<(i64, String)>, default: Option<String>},
+    Case{value: Box<Expr>, body: Box<Stmt>, label: String},
+    Default{body: Box<Stmt>, label: String, line: usize},
+    /// A statement with a label which can be the target of a goto, the label is made unique by
+    /// the labeling pass
+    Labeled{label: String, body: Box<Stmt>, line: usize},
+    Goto{label: String, line: usize},
+    Break{label: String, line: usize},
+    Continue{label: String, line: usize},
+    Null,
+}
+
+/// The initializer of a variable
+#[derive(Debug, Clone)]
+pub enum Initializer {
+    /// An expression, or a string literal initializing a char array
+    Single(Box<Expr>),
+    /// A brace enclosed list of initializers, each of which may be preceded by designators naming
+    /// the member or element it starts at, with those after it carrying on from there. The type
+    /// checker flattens the list into the elements it initializes, moving its expressions there.
+    Compound{items: Vec<(Vec<Designator>, Initializer)>, elements: Vec<InitElement>},
+}
+
+/// One step of the path to the part of an object an initializer in a list starts at
+#[derive(Debug, Clone, PartialEq)]
+pub enum Designator {
+    /// `.member`
+    Member(String),
+    /// `[index]`, whose constant value is worked out by the parser
+    Index(usize),
+}
+
+/// A scalar, string literal or structure initializing part of a variable, where everything no
+/// element initializes is zero
+#[derive(Debug, Clone)]
+pub struct InitElement {
+    /// Offset in bytes of the object initialized from the start of the variable
+    pub offset: usize,
+    /// Type of the object initialized, for a bit-field that of its storage unit
+    pub ty: Type,
+    pub bits: Option<BitField>,
+    /// The value, already converted to the type of the object or the bit-field
+    pub expr: Expr,
+}
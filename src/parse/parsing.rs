@@ -1,82 +1,1141 @@
-use super::c_ast::{Stmt, Expr};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use super::c_ast::{Stmt, Expr, ExprKind, UnaryOp, BinaryOp, StorageClass, Type, Const, StructType, StructDef, VA_LIST_TAG, Initializer, Designator};
 use crate::lex::{Token, TokenType};
+use crate::validate::evaluating::{evaluate_constant, constant_warnings, Warning};
+
+/// Precedence of assignment, which binds looser than every binary operator
+const ASSIGNMENT_PRECEDENCE: u32 = 1;
+/// Precedence of the conditional operator, between assignment and ||
+const CONDITIONAL_PRECEDENCE: u32 = 3;
 
 pub struct Parser {
     /// Vector of tokens generated by Lexer
     tokens: Vec<Token>,
     /// Current position in the token vector
     current: usize,
+    /// Tags and ordinary identifiers declared in each enclosing scope, innermost last. Tags and
+    /// enumeration constants are resolved while parsing, since the size of a structure or the
+    /// value of a constant can be needed by an array declarator, and so are typedef names, since
+    /// whether `T * x;` is a declaration depends on whether T is one.
+    scopes: Vec<Scope>,
+    /// Warnings about the constant expressions evaluated while parsing
+    warnings: Vec<Warning>,
+}
+
+/// Names declared in a scope which parsing depends on
+#[derive(Default)]
+struct Scope {
+    tags: HashMap<String, Tag>,
+    identifiers: HashMap<String, Ordinary>,
+}
+
+/// What a tag names
+enum Tag {
+    Struct(Rc<StructType>),
+    /// An enumeration, which is given the integer type compatible with it
+    Enum(Type),
+}
+
+/// What an ordinary identifier names, as far as parsing needs to know
+enum Ordinary {
+    /// A variable or function, which is resolved after parsing
+    Object,
+    /// An enumeration constant, which is replaced by its value
+    Enumerator(Const),
+    /// A typedef name, which is replaced by the type it stands for
+    Typedef(Type),
+}
+
+/// A storage class specifier. Grammatically typedef is one, though rather than giving an object
+/// storage it makes the declaration one of a name for a type.
+#[derive(Clone, Copy, PartialEq)]
+enum Storage {
+    Class(StorageClass),
+    Typedef,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
+        // As in gcc, <stdarg.h> names va_list as __builtin_va_list, which is predeclared along
+        // with the structure it is an array of
+        let mut file_scope = Scope::default();
+        let va_list = Type::va_list();
+        if let Type::Array { element, .. } = &va_list {
+            if let Type::Struct(struct_type) = &**element {
+                file_scope.tags.insert(VA_LIST_TAG.to_string(), Tag::Struct(struct_type.clone()));
+            }
+        }
+        file_scope.identifiers.insert("__builtin_va_list".to_string(), Ordinary::Typedef(va_list));
         Self {
             tokens,
             current: 0,
+            scopes: vec![file_scope],
+            warnings: Vec::new(),
         }
     }
 
+    /// Warnings found evaluating constant expressions while parsing
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Evaluate a constant expression needed while parsing, such as the size of an array
+    fn evaluate(&mut self, expr: &Expr) -> Option<Const> {
+        constant_warnings(expr, &mut self.warnings);
+        evaluate_constant(expr)
+    }
+
     pub fn parse(&mut self) -> Result<Stmt, ParseError> {
-        let body = self.function()?;
-        Ok(Stmt::Program {
-            body:Box::new(body)
-        })
-    }
-
-    fn function(&mut self) -> Result<Stmt, ParseError> {
-        // Consume int
-        _=self.consume(TokenType::Int, ParseError::ExpectedType)?;
-        // Get identifier
-        let identifier = self.consume(TokenType::Identifier, ParseError::ExpectedIdentifier)?;
-        let name = match identifier.lexeme {
-            None => {return Err(ParseError::ExpectedIdentifier)}
-            Some(name) => {name}
-        };
-        let name_expr = Box::new(Expr::Identifier {value: name});
-        // Consume left paren
+        let mut body = Vec::new();
+        while !self.is_at_end() {
+            body.extend(self.declaration()?);
+        }
+        Ok(Stmt::Program { body })
+    }
+
+    /// Parse the body of a function definition, after its declarator
+    fn function(&mut self, name: Box<Expr>, params: Vec<String>, ty: Type, storage: Option<StorageClass>) -> Result<Stmt, ParseError> {
+        // A function type named by a typedef has no parameter names to define it with, and every
+        // parameter of a definition must be named
+        if let Type::Function { params: param_types, .. } = &ty {
+            if param_types.len() != params.len() {
+                return Err(ParseError::InvalidParams);
+            }
+        }
+        if params.iter().any(|param| param.is_empty()) {
+            return Err(ParseError::ExpectedIdentifier);
+        }
+        // The parameters hide any enumeration constants or typedef names of the same name within
+        // the body
+        self.scopes.push(Scope::default());
+        for param in &params {
+            self.declare(param.clone(), Ordinary::Object)?;
+        }
+        let body = self.block();
+        self.scopes.pop();
+        Ok(Stmt::FuncDef { name, params, ty, storage, body: body? })
+    }
+
+    /// Parse a parenthesized parameter list, returning the type specifiers and declarator of each
+    /// parameter and whether it ends with an ellipsis
+    fn params(&mut self) -> Result<(Vec<(Type, Declarator)>, bool), ParseError> {
         _=self.consume(TokenType::LeftParen, ParseError::ExpectedLeftParen)?;
-        // Consume void
-        _=self.consume(TokenType::Void, ParseError::InvalidParams)?;
-        // Consume right paren
+        let mut params = Vec::new();
+        let mut variadic = false;
+        // Both (void) and () declare a function without parameters
+        let next_is_paren = self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == TokenType::RightParen);
+        if self.check(TokenType::Void) && next_is_paren {
+            _ = self.advance();
+        } else if !self.check(TokenType::RightParen) {
+            loop {
+                // The ellipsis must follow at least one named parameter
+                if !params.is_empty() && self.check(TokenType::Ellipsis) {
+                    _ = self.advance();
+                    variadic = true;
+                    break;
+                }
+                if !self.check_type_specifier() {
+                    return Err(ParseError::InvalidParams);
+                }
+                let ty = self.type_specifiers()?;
+                params.push((ty, self.declarator()?));
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                _ = self.advance();
+            }
+        }
         _=self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+        Ok((params, variadic))
+    }
+
+    /// Parse a declarator, which may be abstract. Pointers bind looser than the function and array
+    /// suffixes, which parentheses can override, so `*a[3]` is an array of pointers but `(*p)[3]`
+    /// is a pointer to an array.
+    fn declarator(&mut self) -> Result<Declarator, ParseError> {
+        if self.check(TokenType::Star) {
+            _ = self.advance();
+            return Ok(Declarator::Pointer(Box::new(self.declarator()?)));
+        }
+        let mut declarator = if self.check(TokenType::Identifier) {
+            let identifier = self.advance();
+            let name = identifier.lexeme.ok_or(ParseError::ExpectedIdentifier)?;
+            Declarator::Identifier { name, line: identifier.line }
+        } else if self.check(TokenType::LeftParen) && self.tokens.get(self.current + 1).is_some_and(|token| {
+            // Otherwise the parenthesis starts the parameter list of an abstract declarator
+            matches!(token.token_type, TokenType::Star | TokenType::LeftParen | TokenType::LeftBracket | TokenType::Identifier)
+        }) {
+            _ = self.advance();
+            let inner = self.declarator()?;
+            _ = self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+            inner
+        } else {
+            Declarator::Abstract
+        };
+        loop {
+            if self.check(TokenType::LeftParen) {
+                let (params, variadic) = self.params()?;
+                declarator = Declarator::Function { params, inner: Box::new(declarator), variadic };
+            } else if self.check(TokenType::LeftBracket) {
+                _ = self.advance();
+                // The size may be left out, otherwise it must be a positive integer constant
+                // expression
+                let size = if self.check(TokenType::RightBracket) {
+                    None
+                } else {
+                    let size = self.expression()?;
+                    match self.evaluate(&size) {
+                        Some(value) if !value.ty().is_floating() && value.bits() > 0 => Some(value.bits() as usize),
+                        _ => return Err(ParseError::InvalidArraySize),
+                    }
+                };
+                _ = self.consume(TokenType::RightBracket, ParseError::ExpectedRightBracket)?;
+                declarator = Declarator::Array { inner: Box::new(declarator), size };
+            } else {
+                return Ok(declarator);
+            }
+        }
+    }
+
+    /// Parse a brace enclosed list of block items
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         // Consume left bracket
         _=self.consume(TokenType::LeftBrace, ParseError::ExpectedLeftBrace)?;
-        // Parse statement
-        let body = Box::new(self.statement()?);
+        // Parse block items until the closing bracket, names declared in the block are only
+        // visible within it
+        self.scopes.push(Scope::default());
+        let mut body = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            if self.is_at_end() {
+                return Err(ParseError::UnmatchedBrace);
+            }
+            body.extend(self.block_item()?);
+        }
         // Consume right bracket
         _=self.consume(TokenType::RightBrace, ParseError::UnmatchedBrace)?;
-        Ok(Stmt::FuncDef {
-            name:name_expr, body,
-        })
+        self.scopes.pop();
+        Ok(body)
+    }
+
+    /// Parse a statement, or the declarations of a declaration
+    fn block_item(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        // Labels have their own namespace, so a label can be spelled like a typedef name
+        let label = self.check(TokenType::Identifier) && self.check_next(TokenType::Colon);
+        if label || !self.check_specifier() {
+            return Ok(vec![self.statement()?]);
+        }
+        let declarations = self.declaration()?;
+        for declaration in &declarations {
+            match declaration {
+                Stmt::FuncDef { .. } => return Err(ParseError::NestedFunction),
+                // Functions declared in a block always have external linkage
+                Stmt::FuncDecl { storage: Some(StorageClass::Static), .. } => return Err(ParseError::InvalidStorageClass),
+                _ => {}
+            }
+        }
+        Ok(declarations)
+    }
+
+    /// Whether the next token can start the specifiers of a declaration
+    fn check_specifier(&self) -> bool {
+        self.check_type_specifier() || self.check(TokenType::Static) || self.check(TokenType::Extern) || self.check(TokenType::Typedef)
+    }
+
+    /// Whether the next token is a type specifier
+    fn check_type_specifier(&self) -> bool {
+        self.tokens.get(self.current).is_some_and(|token| self.is_type_specifier(token))
+    }
+
+    /// Whether a token is a type specifier, either a keyword or a typedef name
+    fn is_type_specifier(&self, token: &Token) -> bool {
+        // String and character literals keep their text as a lexeme too, so only identifiers count
+        is_type_keyword(token.token_type)
+            || (token.token_type == TokenType::Identifier && token.lexeme.as_ref().is_some_and(|name| self.typedef_type(name).is_some()))
+    }
+
+    /// The type a typedef name stands for, if the identifier is a typedef name in scope
+    fn typedef_type(&self, name: &str) -> Option<&Type> {
+        match self.scopes.iter().rev().find_map(|scope| scope.identifiers.get(name)) {
+            Some(Ordinary::Typedef(ty)) => Some(ty),
+            _ => None,
+        }
+    }
+
+    /// Parse the type and storage class specifiers of a declaration, which may come in any order,
+    /// returning the type, the storage class if there is one, and whether an enumeration
+    /// specifier declared its tag or constants
+    fn specifiers(&mut self) -> Result<(Type, Option<Storage>, bool), ParseError> {
+        let mut types = Vec::new();
+        let mut named = None;
+        let mut storage = None;
+        let mut declares_enum = false;
+        while self.check_specifier() {
+            if self.check(TokenType::Identifier) {
+                // A typedef name is only a type specifier if there isn't one already, otherwise it
+                // is the name being declared, hiding the typedef
+                if named.is_some() || !types.is_empty() {
+                    break;
+                }
+                let name = self.advance().lexeme.ok_or(ParseError::ExpectedIdentifier)?;
+                named = self.typedef_type(&name).cloned();
+                continue;
+            }
+            let token = self.advance();
+            let storage_class = match token.token_type {
+                TokenType::Static => Storage::Class(StorageClass::Static),
+                TokenType::Extern => Storage::Class(StorageClass::Extern),
+                TokenType::Typedef => Storage::Typedef,
+                TokenType::Struct | TokenType::Union => {
+                    if named.is_some() {
+                        return Err(ParseError::InvalidType);
+                    }
+                    named = Some(self.struct_specifier(token.token_type == TokenType::Union)?);
+                    continue;
+                }
+                TokenType::Enum => {
+                    if named.is_some() {
+                        return Err(ParseError::InvalidType);
+                    }
+                    let (ty, declared) = self.enum_specifier()?;
+                    named = Some(ty);
+                    declares_enum = declared;
+                    continue;
+                }
+                type_specifier => {
+                    types.push(type_specifier);
+                    continue;
+                }
+            };
+            if storage.is_some() {
+                return Err(ParseError::InvalidStorageClass);
+            }
+            storage = Some(storage_class);
+        }
+        // A structure, enumeration or typedef name can't be combined with any other type specifier
+        let ty = match named {
+            Some(_) if !types.is_empty() => return Err(ParseError::InvalidType),
+            Some(ty) => ty,
+            None => type_from_specifiers(&types)?,
+        };
+        Ok((ty, storage, declares_enum))
+    }
+
+    /// Parse a list of type specifiers, without any storage class
+    fn type_specifiers(&mut self) -> Result<Type, ParseError> {
+        match self.specifiers()? {
+            (ty, None, _) => Ok(ty),
+            (_, Some(_), _) => Err(ParseError::InvalidStorageClass),
+        }
+    }
+
+    /// Parse the rest of a structure specifier after `struct`, giving the type its tag refers to.
+    /// A definition, or a declaration of nothing but the tag as in `struct s;`, declares the tag
+    /// in the current scope, hiding any declaration in an enclosing scope. Any other use refers to
+    /// the innermost visible declaration, declaring the tag if there is none.
+    fn struct_specifier(&mut self, union: bool) -> Result<Type, ParseError> {
+        let tag = if self.check(TokenType::Identifier) {
+            Some(self.advance().lexeme.ok_or(ParseError::ExpectedIdentifier)?)
+        } else {
+            None
+        };
+        let defining = self.check(TokenType::LeftBrace);
+        let struct_type = match tag {
+            // Each anonymous structure is a distinct type, which can only be used where it is defined
+            None if defining => Rc::new(StructType::new(String::new(), union)),
+            None => return Err(ParseError::ExpectedIdentifier),
+            Some(tag) => {
+                let declared = if defining || self.check(TokenType::Semicolon) {
+                    self.current_scope().tags.get(&tag)
+                } else {
+                    self.scopes.iter().rev().find_map(|scope| scope.tags.get(&tag))
+                };
+                match declared {
+                    // Structures, unions and enumerations share the tags, so a tag can't be used
+                    // for more than one
+                    Some(Tag::Struct(struct_type)) if struct_type.union == union => struct_type.clone(),
+                    Some(_) => return Err(ParseError::WrongTagKind),
+                    None => {
+                        let struct_type = Rc::new(StructType::new(tag.clone(), union));
+                        self.current_scope().tags.insert(tag, Tag::Struct(struct_type.clone()));
+                        struct_type
+                    }
+                }
+            }
+        };
+        if defining {
+            if struct_type.definition.borrow().is_some() {
+                return Err(ParseError::StructRedefinition);
+            }
+            // The tag is declared before the members, so a member can point to the structure
+            let definition = self.struct_members(union)?;
+            *struct_type.definition.borrow_mut() = Some(definition);
+        }
+        Ok(Type::Struct(struct_type))
+    }
+
+    /// Parse the brace enclosed member declarations of a structure or union definition, and lay
+    /// them out
+    fn struct_members(&mut self, union: bool) -> Result<StructDef, ParseError> {
+        _ = self.consume(TokenType::LeftBrace, ParseError::ExpectedLeftBrace)?;
+        let mut members: Vec<(String, Type, Option<usize>)> = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            if self.is_at_end() {
+                return Err(ParseError::UnmatchedBrace);
+            }
+            if !self.check_type_specifier() {
+                return Err(ParseError::ExpectedType);
+            }
+            // Every declarator in the list shares the specifiers
+            let base = self.type_specifiers()?;
+            loop {
+                let declarator = self.declarator()?;
+                let Declared { name, ty, .. } = declarator_type(base.clone(), declarator)?;
+                let width = self.bit_field_width(&ty, name.is_some())?;
+                // Only a bit-field can be unnamed
+                let name = match name {
+                    Some((name, _)) => name,
+                    None if width.is_some() => String::new(),
+                    None => return Err(ParseError::ExpectedIdentifier),
+                };
+                // Members are objects, so a structure can't contain a function or itself
+                if !ty.is_complete() {
+                    return Err(ParseError::IncompleteMember);
+                }
+                if !name.is_empty() && members.iter().any(|(member, _, _)| *member == name) {
+                    return Err(ParseError::DuplicateMember);
+                }
+                members.push((name, ty, width));
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                _ = self.advance();
+            }
+            _ = self.consume(TokenType::Semicolon, ParseError::ExpectedSemicolon)?;
+        }
+        _ = self.consume(TokenType::RightBrace, ParseError::UnmatchedBrace)?;
+        if members.iter().all(|(name, _, _)| name.is_empty()) {
+            return Err(ParseError::EmptyStruct);
+        }
+        Ok(StructDef::layout(members, union))
+    }
+
+    /// Parse the width following a bit-field member of type ty, if there is one. It must be a
+    /// constant no wider than the type, which must be an integer type, and only an unnamed
+    /// bit-field can have width 0.
+    fn bit_field_width(&mut self, ty: &Type, named: bool) -> Result<Option<usize>, ParseError> {
+        if !self.check(TokenType::Colon) {
+            return Ok(None);
+        }
+        _ = self.advance();
+        if !ty.is_integer() {
+            return Err(ParseError::InvalidBitFieldType);
+        }
+        let width = self.binary(CONDITIONAL_PRECEDENCE)?;
+        match self.evaluate(&width) {
+            Some(value) if !value.ty().is_floating() && value.bits() >= named as i64 && value.bits() as usize <= 8 * ty.size() => {
+                Ok(Some(value.bits() as usize))
+            }
+            _ => Err(ParseError::InvalidBitFieldWidth),
+        }
+    }
+
+    /// Parse the rest of an enumeration specifier after `enum`, giving the integer type compatible
+    /// with it, and whether it declares anything on its own: the tag and constants of a
+    /// definition, or the tag alone as in `enum e;`. Unlike a structure, an enumeration must be
+    /// defined before its tag is used.
+    fn enum_specifier(&mut self) -> Result<(Type, bool), ParseError> {
+        let tag = if self.check(TokenType::Identifier) {
+            Some(self.advance().lexeme.ok_or(ParseError::ExpectedIdentifier)?)
+        } else {
+            None
+        };
+        if !self.check(TokenType::LeftBrace) {
+            let tag = tag.ok_or(ParseError::ExpectedIdentifier)?;
+            return match self.scopes.iter().rev().find_map(|scope| scope.tags.get(&tag)) {
+                Some(Tag::Enum(ty)) => Ok((ty.clone(), self.check(TokenType::Semicolon))),
+                Some(Tag::Struct(_)) => Err(ParseError::WrongTagKind),
+                None => Err(ParseError::UndefinedEnum),
+            };
+        }
+        if let Some(tag) = &tag {
+            match self.current_scope().tags.get(tag) {
+                Some(Tag::Enum(_)) => return Err(ParseError::EnumRedefinition),
+                Some(Tag::Struct(_)) => return Err(ParseError::WrongTagKind),
+                None => {}
+            }
+        }
+        let ty = self.enumerators()?;
+        if let Some(tag) = tag {
+            self.current_scope().tags.insert(tag, Tag::Enum(ty.clone()));
+        }
+        Ok((ty, true))
+    }
+
+    /// Parse the brace enclosed enumerators of an enumeration definition, declaring each in the
+    /// current scope, and give the integer type compatible with the enumeration. As in gcc, that
+    /// is unsigned int if no value is negative, or else int, unless a value needs a long.
+    /// Enumeration constants are ints, except those which don't fit, which have the enumeration's
+    /// type.
+    fn enumerators(&mut self) -> Result<Type, ParseError> {
+        _ = self.consume(TokenType::LeftBrace, ParseError::ExpectedLeftBrace)?;
+        let mut values: Vec<(String, i128)> = Vec::new();
+        let mut next: i128 = 0;
+        loop {
+            let name = self.consume(TokenType::Identifier, ParseError::ExpectedIdentifier)?.lexeme.ok_or(ParseError::ExpectedIdentifier)?;
+            if self.check(TokenType::Equal) {
+                _ = self.advance();
+                let value = self.binary(CONDITIONAL_PRECEDENCE)?;
+                next = match self.evaluate(&value) {
+                    Some(value) if value.ty().is_signed() => value.bits() as i128,
+                    Some(value) if value.ty().is_integer() => value.bits() as u64 as i128,
+                    _ => return Err(ParseError::InvalidEnumerator),
+                };
+            }
+            // Later enumerators can refer to the earlier ones
+            self.declare(name.clone(), Ordinary::Enumerator(enumerator_constant(next, None)?))?;
+            values.push((name, next));
+            next += 1;
+            if !self.check(TokenType::Comma) {
+                break;
+            }
+            _ = self.advance();
+            // The list may end with a comma
+            if self.check(TokenType::RightBrace) {
+                break;
+            }
+        }
+        _ = self.consume(TokenType::RightBrace, ParseError::UnmatchedBrace)?;
+        let min = values.iter().map(|(_, value)| *value).min().expect("there is at least one enumerator");
+        let max = values.iter().map(|(_, value)| *value).max().expect("there is at least one enumerator");
+        let ty = match (min < 0, max) {
+            (false, ..=0xFFFF_FFFF) => Type::UInt,
+            (false, _) => Type::ULong,
+            (true, _) if min >= i32::MIN as i128 && max <= i32::MAX as i128 => Type::Int,
+            (true, _) if max <= i64::MAX as i128 => Type::Long,
+            (true, _) => return Err(ParseError::InvalidEnumerator),
+        };
+        for (name, value) in values {
+            let constant = enumerator_constant(value, Some(&ty))?;
+            self.current_scope().identifiers.insert(name, Ordinary::Enumerator(constant));
+        }
+        Ok(ty)
+    }
+
+    /// Declare an ordinary identifier in the current scope. Only variables and functions, which
+    /// are checked once resolved, or typedefs of the same type, can be declared again in the same
+    /// scope.
+    fn declare(&mut self, name: String, ordinary: Ordinary) -> Result<(), ParseError> {
+        let scope = self.current_scope();
+        match (scope.identifiers.get(&name), &ordinary) {
+            (None, _) | (Some(Ordinary::Object), Ordinary::Object) => {}
+            (Some(Ordinary::Typedef(declared)), Ordinary::Typedef(ty)) if declared == ty => {}
+            _ => return Err(ParseError::ConflictingDeclaration),
+        }
+        scope.identifiers.insert(name, ordinary);
+        Ok(())
+    }
+
+    fn current_scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("there is always a file scope")
+    }
+
+    /// Parse a type name, as in a cast, made up of type specifiers and an abstract declarator
+    fn type_name(&mut self) -> Result<Type, ParseError> {
+        let base = self.type_specifiers()?;
+        let declarator = self.declarator()?;
+        match declarator_type(base, declarator)? {
+            Declared { name: None, ty, .. } => Ok(ty),
+            Declared { name: Some(_), .. } => Err(ParseError::UnexpectedToken),
+        }
+    }
+
+    /// Parse a declaration, giving a variable or function declaration for each declarator in its
+    /// list, or a function definition
+    fn declaration(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let (base, storage, declares_enum) = self.specifiers()?;
+        // A structure specifier can be declared on its own, and so can an enumeration, which only
+        // declares its tag and constants
+        if let (Type::Struct(_), true) = (&base, self.check(TokenType::Semicolon)) {
+            _ = self.advance();
+            return Ok(vec![Stmt::StructDecl { ty: base }]);
+        }
+        if declares_enum && self.check(TokenType::Semicolon) {
+            _ = self.advance();
+            return Ok(vec![Stmt::Null]);
+        }
+        let class = match storage {
+            Some(Storage::Class(class)) => Some(class),
+            _ => None,
+        };
+        // Every declarator in the list shares the specifiers, and is in scope from the end of its
+        // own declarator
+        let mut declarations = Vec::new();
+        loop {
+            let declarator = self.declarator()?;
+            let Declared { name: Some((name, line)), ty, params } = declarator_type(base.clone(), declarator)? else {
+                return Err(ParseError::ExpectedIdentifier);
+            };
+            if let Some(Storage::Typedef) = storage {
+                // A typedef only matters while parsing, where the name now stands for the type
+                self.declare(name, Ordinary::Typedef(ty))?;
+                declarations.push(Stmt::Null);
+            } else if let Type::Function { .. } = ty {
+                self.declare(name.clone(), Ordinary::Object)?;
+                let name_expr = Box::new(Expr::new(ExprKind::Identifier { value: name }, line));
+                // A function definition has a body instead of a semicolon, and must be the only
+                // declarator
+                if declarations.is_empty() && self.check(TokenType::LeftBrace) {
+                    return Ok(vec![self.function(name_expr, params, ty, class)?]);
+                }
+                declarations.push(Stmt::FuncDecl { name: name_expr, params, ty, storage: class });
+            } else {
+                self.declare(name.clone(), Ordinary::Object)?;
+                // Parse the optional initializer
+                let init = if self.check(TokenType::Equal) {
+                    _ = self.advance();
+                    Some(self.initializer()?)
+                } else {
+                    None
+                };
+                declarations.push(Stmt::Declaration { name, ty, init, storage: class, line });
+            }
+            if !self.check(TokenType::Comma) {
+                break;
+            }
+            _ = self.advance();
+        }
+        _ = self.consume(TokenType::Semicolon, ParseError::ExpectedSemicolon)?;
+        Ok(declarations)
+    }
+
+    /// Parse the initializer of a variable, an expression or a brace enclosed list of initializers,
+    /// which may end with a comma
+    fn initializer(&mut self) -> Result<Initializer, ParseError> {
+        if !self.check(TokenType::LeftBrace) {
+            // Commas separate the initializers in a list, so each one is an assignment expression
+            return Ok(Initializer::Single(Box::new(self.binary(ASSIGNMENT_PRECEDENCE)?)));
+        }
+        _ = self.advance();
+        let mut items = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            let designators = self.designators()?;
+            items.push((designators, self.initializer()?));
+            if !self.check(TokenType::Comma) {
+                break;
+            }
+            _ = self.advance();
+        }
+        _ = self.consume(TokenType::RightBrace, ParseError::UnmatchedBrace)?;
+        Ok(Initializer::Compound { items, elements: Vec::new() })
+    }
+
+    /// Parse the designators before an initializer in a list, such as `.a[2] =`, if there are any
+    fn designators(&mut self) -> Result<Vec<Designator>, ParseError> {
+        let mut designators = Vec::new();
+        loop {
+            if self.check(TokenType::Dot) {
+                _ = self.advance();
+                let identifier = self.consume(TokenType::Identifier, ParseError::ExpectedIdentifier)?;
+                designators.push(Designator::Member(identifier.lexeme.ok_or(ParseError::ExpectedIdentifier)?));
+            } else if self.check(TokenType::LeftBracket) {
+                _ = self.advance();
+                let index = self.expression()?;
+                _ = self.consume(TokenType::RightBracket, ParseError::ExpectedRightBracket)?;
+                // The index must be a non-negative integer constant expression
+                let index = match self.evaluate(&index) {
+                    Some(value) if !value.ty().is_floating() && value.bits() >= 0 => value.bits() as usize,
+                    _ => return Err(ParseError::InvalidDesignator),
+                };
+                designators.push(Designator::Index(index));
+            } else {
+                break;
+            }
+        }
+        if !designators.is_empty() {
+            _ = self.consume(TokenType::Equal, ParseError::ExpectedEqual)?;
+        }
+        Ok(designators)
     }
 
     fn statement(&mut self) -> Result<Stmt, ParseError> {
-        // Consume return
-        _=self.consume(TokenType::Return, ParseError::ExpectedReturn)?;
-        let expression = self.expression()?;
-        // consume semicolon
-        _=self.consume(TokenType::Semicolon, ParseError::ExpectedSemicolon)?;
-        Ok(Stmt::Return {value: Box::new(expression)})
+        if self.is_at_end() {
+            return Err(ParseError::UnexpectedEnd);
+        }
+        match self.peek().token_type {
+            TokenType::Return => {
+                _ = self.advance();
+                let value = if self.check(TokenType::Semicolon) {None} else {Some(Box::new(self.expression()?))};
+                // consume semicolon
+                _=self.consume(TokenType::Semicolon, ParseError::ExpectedSemicolon)?;
+                Ok(Stmt::Return {value})
+            }
+            TokenType::Semicolon => {
+                _ = self.advance();
+                Ok(Stmt::Null)
+            }
+            TokenType::LeftBrace => {
+                let body = self.block()?;
+                Ok(Stmt::Compound { body })
+            }
+            TokenType::While => {
+                _ = self.advance();
+                _=self.consume(TokenType::LeftParen, ParseError::ExpectedLeftParen)?;
+                let condition = Box::new(self.expression()?);
+                _=self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+                let body = Box::new(self.statement()?);
+                Ok(Stmt::While { condition, body, label: String::new() })
+            }
+            TokenType::Do => {
+                _ = self.advance();
+                let body = Box::new(self.statement()?);
+                _=self.consume(TokenType::While, ParseError::ExpectedWhile)?;
+                _=self.consume(TokenType::LeftParen, ParseError::ExpectedLeftParen)?;
+                let condition = Box::new(self.expression()?);
+                _=self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+                _=self.consume(TokenType::Semicolon, ParseError::ExpectedSemicolon)?;
+                Ok(Stmt::DoWhile { body, condition, label: String::new() })
+            }
+            TokenType::For => {
+                _ = self.advance();
+                // A variable declared by the loop is only visible within it
+                self.scopes.push(Scope::default());
+                let result = self.for_statement();
+                self.scopes.pop();
+                result
+            }
+            TokenType::Switch => {
+                _ = self.advance();
+                _=self.consume(TokenType::LeftParen, ParseError::ExpectedLeftParen)?;
+                let condition = Box::new(self.expression()?);
+                _=self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+                let body = Box::new(self.statement()?);
+                Ok(Stmt::Switch { condition, body, label: String::new(), cases: Vec::new(), default: None })
+            }
+            TokenType::Case => {
+                _ = self.advance();
+                let value = Box::new(self.expression()?);
+                _=self.consume(TokenType::Colon, ParseError::ExpectedColon)?;
+                let body = Box::new(self.statement()?);
+                Ok(Stmt::Case { value, body, label: String::new() })
+            }
+            TokenType::Default => {
+                let line = self.advance().line;
+                _=self.consume(TokenType::Colon, ParseError::ExpectedColon)?;
+                let body = Box::new(self.statement()?);
+                Ok(Stmt::Default { body, label: String::new(), line })
+            }
+            TokenType::Identifier if self.check_next(TokenType::Colon) => {
+                let identifier = self.advance();
+                _ = self.advance();
+                let label = identifier.lexeme.ok_or(ParseError::ExpectedIdentifier)?;
+                let body = Box::new(self.statement()?);
+                Ok(Stmt::Labeled { label, body, line: identifier.line })
+            }
+            TokenType::Goto => {
+                _ = self.advance();
+                let identifier = self.consume(TokenType::Identifier, ParseError::ExpectedIdentifier)?;
+                let label = identifier.lexeme.ok_or(ParseError::ExpectedIdentifier)?;
+                _=self.consume(TokenType::Semicolon, ParseError::ExpectedSemicolon)?;
+                Ok(Stmt::Goto { label, line: identifier.line })
+            }
+            TokenType::Break | TokenType::Continue => {
+                let token = self.advance();
+                _=self.consume(TokenType::Semicolon, ParseError::ExpectedSemicolon)?;
+                if token.token_type == TokenType::Break {
+                    Ok(Stmt::Break { label: String::new(), line: token.line })
+                } else {
+                    Ok(Stmt::Continue { label: String::new(), line: token.line })
+                }
+            }
+            TokenType::If => {
+                _ = self.advance();
+                _=self.consume(TokenType::LeftParen, ParseError::ExpectedLeftParen)?;
+                let condition = Box::new(self.expression()?);
+                _=self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+                let then_branch = Box::new(self.statement()?);
+                // An else belongs to the nearest if, which is the one parsed here since any
+                // nested if in the then branch has already consumed its own else
+                let else_branch = if self.check(TokenType::Else) {
+                    _ = self.advance();
+                    Some(Box::new(self.statement()?))
+                } else {
+                    None
+                };
+                Ok(Stmt::If { condition, then_branch, else_branch })
+            }
+            _ => {
+                let expression = self.expression()?;
+                _=self.consume(TokenType::Semicolon, ParseError::ExpectedSemicolon)?;
+                Ok(Stmt::Expression {expr: Box::new(expression)})
+            }
+        }
+    }
+
+    /// Parse the rest of a for statement after `for`
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        _=self.consume(TokenType::LeftParen, ParseError::ExpectedLeftParen)?;
+        // The init clause is a declaration, an expression, or empty, and includes its semicolon
+        let init = if self.check_specifier() {
+            let declarations = self.declaration()?;
+            // Only automatic variables may be declared
+            if !declarations.iter().all(|declaration| matches!(declaration, Stmt::Declaration { storage: None, .. })) {
+                return Err(ParseError::InvalidForInit);
+            }
+            declarations
+        } else {
+            let init = self.optional_expression(TokenType::Semicolon)?;
+            init.map(|expr| Stmt::Expression { expr }).into_iter().collect()
+        };
+        let condition = self.optional_expression(TokenType::Semicolon)?;
+        let post = self.optional_expression(TokenType::RightParen)?;
+        let body = Box::new(self.statement()?);
+        Ok(Stmt::For { init, condition, post, body, label: String::new() })
+    }
+
+    /// Parse an expression which may be omitted, followed by the terminator token
+    fn optional_expression(&mut self, terminator: TokenType) -> Result<Option<Box<Expr>>, ParseError> {
+        let expr = if self.check(terminator) {
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+        let error = if terminator == TokenType::RightParen {ParseError::UnmatchedParen} else {ParseError::ExpectedSemicolon};
+        _=self.consume(terminator, error)?;
+        Ok(expr)
     }
 
     fn expression(&mut self) -> Result<Expr, ParseError> {
-        // Parse int (only current expression)
-        let int = self.int()?;
-        Ok(int)
+        self.binary(0)
+    }
+
+    /// Parse a binary expression using precedence climbing, only operators binding at least as
+    /// tightly as min_precedence are consumed at this level
+    fn binary(&mut self, min_precedence: u32) -> Result<Expr, ParseError> {
+        let mut left = self.factor()?;
+        loop {
+            if let Some(compound_op) = self.peek_assignment_op() {
+                if ASSIGNMENT_PRECEDENCE < min_precedence {
+                    break;
+                }
+                let line = self.advance().line;
+                if !left.is_lvalue() {
+                    return Err(ParseError::InvalidLvalue);
+                }
+                // Assignment is right associative
+                let right = Box::new(self.binary(ASSIGNMENT_PRECEDENCE)?);
+                let kind = match compound_op {
+                    None => ExprKind::Assignment { left: Box::new(left), right },
+                    Some(op) => ExprKind::CompoundAssignment { op, left: Box::new(left), right },
+                };
+                left = Expr::new(kind, line);
+                continue;
+            }
+            if self.check(TokenType::Question) && CONDITIONAL_PRECEDENCE >= min_precedence {
+                let line = self.advance().line;
+                // Anything may appear between ? and :, as though it were parenthesized
+                let then_expr = Box::new(self.expression()?);
+                _ = self.consume(TokenType::Colon, ParseError::ExpectedColon)?;
+                // The conditional operator is right associative
+                let else_expr = Box::new(self.binary(CONDITIONAL_PRECEDENCE)?);
+                left = Expr::new(ExprKind::Conditional { condition: Box::new(left), then_expr, else_expr }, line);
+                continue;
+            }
+            let op = match self.peek_binary_op() {
+                Some(op) if op.precedence() >= min_precedence => op,
+                _ => break,
+            };
+            let line = self.advance().line;
+            // All binary operators are left associative
+            let right = self.binary(op.precedence() + 1)?;
+            left = Expr::new(ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) }, line);
+        }
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> Result<Expr, ParseError> {
+        if self.is_at_end() {
+            return Err(ParseError::UnexpectedEnd);
+        }
+        match self.peek().token_type {
+            TokenType::Ampersand | TokenType::Star => {
+                let token = self.advance();
+                let expr = Box::new(self.factor()?);
+                let kind = if token.token_type == TokenType::Ampersand {
+                    // Only objects have an address
+                    if !expr.is_lvalue() {
+                        return Err(ParseError::InvalidAddressOf);
+                    }
+                    ExprKind::AddressOf { expr }
+                } else {
+                    ExprKind::Dereference { expr }
+                };
+                Ok(Expr::new(kind, token.line))
+            }
+            TokenType::Minus | TokenType::Tilde | TokenType::Bang => {
+                let token = self.advance();
+                let op = match token.token_type {
+                    TokenType::Minus => UnaryOp::Negate,
+                    TokenType::Tilde => UnaryOp::Complement,
+                    _ => UnaryOp::Not,
+                };
+                let expr = self.factor()?;
+                Ok(Expr::new(ExprKind::Unary { op, expr: Box::new(expr) }, token.line))
+            }
+            TokenType::Sizeof => {
+                let line = self.advance().line;
+                // The operand is either a parenthesized type name or a unary expression
+                if self.check(TokenType::LeftParen) && self.tokens.get(self.current + 1).is_some_and(|token| self.is_type_specifier(token)) {
+                    _ = self.advance();
+                    let ty = self.type_name()?;
+                    _ = self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+                    return Ok(Expr::new(ExprKind::SizeOfType { ty }, line));
+                }
+                let expr = self.factor()?;
+                Ok(Expr::new(ExprKind::SizeOf { expr: Box::new(expr) }, line))
+            }
+            TokenType::Alignof => {
+                let line = self.advance().line;
+                _ = self.consume(TokenType::LeftParen, ParseError::ExpectedLeftParen)?;
+                let ty = self.type_name()?;
+                _ = self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+                Ok(Expr::new(ExprKind::AlignOf { ty }, line))
+            }
+            // A parenthesized type name is a cast
+            TokenType::LeftParen if self.tokens.get(self.current + 1).is_some_and(|token| self.is_type_specifier(token)) => {
+                let line = self.advance().line;
+                let target = self.type_name()?;
+                _ = self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+                let expr = self.factor()?;
+                Ok(Expr::new(ExprKind::Cast { target, expr: Box::new(expr) }, line))
+            }
+            TokenType::PlusPlus | TokenType::MinusMinus => {
+                // ++a is a += 1, and --a is a -= 1
+                let token = self.advance();
+                let op = if token.token_type == TokenType::PlusPlus {BinaryOp::Add} else {BinaryOp::Subtract};
+                let expr = self.factor()?;
+                if !expr.is_lvalue() {
+                    return Err(ParseError::InvalidLvalue);
+                }
+                let one = Expr::new(ExprKind::Constant { value: Const::Int(1) }, token.line);
+                Ok(Expr::new(ExprKind::CompoundAssignment { op, left: Box::new(expr), right: Box::new(one) }, token.line))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.check(TokenType::LeftBracket) {
+                let line = self.advance().line;
+                let index = Box::new(self.expression()?);
+                _ = self.consume(TokenType::RightBracket, ParseError::ExpectedRightBracket)?;
+                expr = Expr::new(ExprKind::Subscript { array: Box::new(expr), index }, line);
+            } else if self.check(TokenType::LeftParen) {
+                let line = self.peek().line;
+                let args = self.arguments()?;
+                expr = Expr::new(ExprKind::FunctionCall { function: Box::new(expr), args }, line);
+            } else if self.check(TokenType::PlusPlus) || self.check(TokenType::MinusMinus) {
+                let token = self.advance();
+                let op = if token.token_type == TokenType::PlusPlus {BinaryOp::Add} else {BinaryOp::Subtract};
+                if !expr.is_lvalue() {
+                    return Err(ParseError::InvalidLvalue);
+                }
+                expr = Expr::new(ExprKind::Postfix { op, expr: Box::new(expr) }, token.line);
+            } else if self.check(TokenType::Dot) || self.check(TokenType::Arrow) {
+                let token = self.advance();
+                let identifier = self.consume(TokenType::Identifier, ParseError::ExpectedIdentifier)?;
+                let member = identifier.lexeme.ok_or(ParseError::ExpectedIdentifier)?;
+                // p->m is (*p).m
+                if token.token_type == TokenType::Arrow {
+                    expr = Expr::new(ExprKind::Dereference { expr: Box::new(expr) }, token.line);
+                }
+                expr = Expr::new(ExprKind::Member { expr: Box::new(expr), member }, token.line);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        if self.is_at_end() {
+            return Err(ParseError::UnexpectedEnd);
+        }
+        match self.peek().token_type {
+            TokenType::Constant | TokenType::FloatConstant | TokenType::CharConstant => self.constant(),
+            TokenType::StringLiteral => {
+                // Adjacent string literals are concatenated
+                let line = self.peek().line;
+                let mut value = Vec::new();
+                while self.check(TokenType::StringLiteral) {
+                    let lexeme = self.advance().lexeme.ok_or(ParseError::UnexpectedToken)?;
+                    value.extend(unescape(&lexeme)?);
+                }
+                Ok(Expr::new(ExprKind::String { value }, line))
+            }
+            TokenType::Identifier => {
+                let identifier = self.advance();
+                let value = identifier.lexeme.ok_or(ParseError::ExpectedIdentifier)?;
+                // An enumeration constant is replaced by its value, unless something declared in
+                // an inner scope hides it
+                if let Some(Ordinary::Enumerator(constant)) = self.scopes.iter().rev().find_map(|scope| scope.identifiers.get(&value)) {
+                    return Ok(Expr::new(ExprKind::Constant { value: *constant }, identifier.line));
+                }
+                Ok(Expr::new(ExprKind::Identifier { value }, identifier.line))
+            }
+            TokenType::LeftParen => {
+                _ = self.advance();
+                let expr = self.expression()?;
+                _ = self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+                Ok(expr)
+            }
+            TokenType::VaStart | TokenType::VaArg | TokenType::VaEnd | TokenType::VaCopy => self.va_builtin(),
+            _ => Err(ParseError::UnexpectedToken),
+        }
+    }
+
+    /// Parse a use of one of the builtins for walking through variadic arguments, which look like
+    /// calls but for the type name va_arg takes
+    fn va_builtin(&mut self) -> Result<Expr, ParseError> {
+        let token = self.advance();
+        _ = self.consume(TokenType::LeftParen, ParseError::ExpectedLeftParen)?;
+        let ap = Box::new(self.binary(ASSIGNMENT_PRECEDENCE)?);
+        let kind = match token.token_type {
+            TokenType::VaEnd => ExprKind::VaEnd { ap },
+            TokenType::VaStart => {
+                // The last named parameter isn't needed to find the variadic arguments
+                _ = self.consume(TokenType::Comma, ParseError::ExpectedComma)?;
+                _ = self.binary(ASSIGNMENT_PRECEDENCE)?;
+                ExprKind::VaStart { ap }
+            }
+            TokenType::VaArg => {
+                _ = self.consume(TokenType::Comma, ParseError::ExpectedComma)?;
+                ExprKind::VaArg { ap, ty: self.type_name()? }
+            }
+            _ => {
+                _ = self.consume(TokenType::Comma, ParseError::ExpectedComma)?;
+                ExprKind::VaCopy { dst: ap, src: Box::new(self.binary(ASSIGNMENT_PRECEDENCE)?) }
+            }
+        };
+        _ = self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+        Ok(Expr::new(kind, token.line))
+    }
+
+    /// Parse the parenthesized, comma separated arguments of a function call
+    fn arguments(&mut self) -> Result<Vec<Expr>, ParseError> {
+        _=self.consume(TokenType::LeftParen, ParseError::ExpectedLeftParen)?;
+        let mut args = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                // Commas separate the arguments, so each one is an assignment expression
+                args.push(self.binary(ASSIGNMENT_PRECEDENCE)?);
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                _ = self.advance();
+            }
+        }
+        _=self.consume(TokenType::RightParen, ParseError::UnmatchedParen)?;
+        Ok(args)
     }
 
-    fn int(&mut self) -> Result<Expr, ParseError> {
+    fn constant(&mut self) -> Result<Expr, ParseError> {
         let token = self.advance();
-        let value = match &token.token_type {
+        let lexeme = match &token.token_type {
             TokenType::Constant => {
                 match token.lexeme {
                     None => {return Err(ParseError::InvalidInt)}
                     Some(v) => {v}
                 }
             }
+            TokenType::FloatConstant => {
+                let lexeme = token.lexeme.ok_or(ParseError::InvalidFloat)?;
+                // Each type is parsed directly, so a float isn't rounded twice
+                let value = match lexeme.strip_suffix(['f', 'F']) {
+                    Some(digits) => Const::Float(digits.parse().map_err(|_| ParseError::InvalidFloat)?),
+                    None => Const::Double(lexeme.parse().map_err(|_| ParseError::InvalidFloat)?),
+                };
+                return Ok(Expr::new(ExprKind::Constant { value }, token.line));
+            }
+            TokenType::CharConstant => {
+                // A character constant is an int, holding the value of a (signed) char
+                let lexeme = token.lexeme.ok_or(ParseError::InvalidChar)?;
+                let [value] = unescape(&lexeme)?[..] else {
+                    return Err(ParseError::InvalidChar);
+                };
+                let value = Const::Int(value as i8 as i32);
+                return Ok(Expr::new(ExprKind::Constant { value }, token.line));
+            }
             _=> {return Err(ParseError::UnexpectedToken);}
         };
-        Ok(Expr::new_int(&value))
+        let digits_end = lexeme.find(|c: char| !c.is_ascii_digit()).unwrap_or(lexeme.len());
+        let (digits, suffix) = lexeme.split_at(digits_end);
+        let unsigned = suffix.contains(['u', 'U']);
+        let long = suffix.contains(['l', 'L']);
+        let value = digits.parse::<u64>().map_err(|_| ParseError::InvalidInt)?;
+        // The constant has the first type allowed by its suffix which can represent its value
+        let value = match (unsigned, long) {
+            (false, false) if value <= i32::MAX as u64 => Const::Int(value as i32),
+            (false, _) if value <= i64::MAX as u64 => Const::Long(value as i64),
+            (true, false) if value <= u32::MAX as u64 => Const::UInt(value as u32),
+            (true, _) => Const::ULong(value),
+            _ => return Err(ParseError::InvalidInt),
+        };
+        Ok(Expr::new(ExprKind::Constant { value }, token.line))
+    }
+
+    /// If the next token is an assignment operator, the binary operator it applies (None for
+    /// plain assignment)
+    fn peek_assignment_op(&self) -> Option<Option<BinaryOp>> {
+        if self.is_at_end() {
+            return None;
+        }
+        let op = match self.peek().token_type {
+            TokenType::Equal => None,
+            TokenType::PlusEqual => Some(BinaryOp::Add),
+            TokenType::MinusEqual => Some(BinaryOp::Subtract),
+            TokenType::StarEqual => Some(BinaryOp::Multiply),
+            TokenType::SlashEqual => Some(BinaryOp::Divide),
+            TokenType::PercentEqual => Some(BinaryOp::Remainder),
+            TokenType::AmpersandEqual => Some(BinaryOp::BitwiseAnd),
+            TokenType::PipeEqual => Some(BinaryOp::BitwiseOr),
+            TokenType::CaretEqual => Some(BinaryOp::BitwiseXor),
+            TokenType::LessLessEqual => Some(BinaryOp::ShiftLeft),
+            TokenType::GreaterGreaterEqual => Some(BinaryOp::ShiftRight),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Binary operator corresponding to the next token, if it is one
+    fn peek_binary_op(&self) -> Option<BinaryOp> {
+        if self.is_at_end() {
+            return None;
+        }
+        let op = match self.peek().token_type {
+            TokenType::Plus => BinaryOp::Add,
+            TokenType::Minus => BinaryOp::Subtract,
+            TokenType::Star => BinaryOp::Multiply,
+            TokenType::Slash => BinaryOp::Divide,
+            TokenType::Percent => BinaryOp::Remainder,
+            TokenType::Ampersand => BinaryOp::BitwiseAnd,
+            TokenType::Pipe => BinaryOp::BitwiseOr,
+            TokenType::Caret => BinaryOp::BitwiseXor,
+            TokenType::LessLess => BinaryOp::ShiftLeft,
+            TokenType::GreaterGreater => BinaryOp::ShiftRight,
+            TokenType::AmpersandAmpersand => BinaryOp::And,
+            TokenType::PipePipe => BinaryOp::Or,
+            TokenType::EqualEqual => BinaryOp::Equal,
+            TokenType::BangEqual => BinaryOp::NotEqual,
+            TokenType::Less => BinaryOp::LessThan,
+            TokenType::LessEqual => BinaryOp::LessOrEqual,
+            TokenType::Greater => BinaryOp::GreaterThan,
+            TokenType::GreaterEqual => BinaryOp::GreaterOrEqual,
+            _ => return None,
+        };
+        Some(op)
     }
     
     // region helper functions
@@ -85,6 +1144,14 @@ impl Parser {
         self.tokens[self.current].clone()
     }
     
+    /// Line of the token currently being parsed, used to locate errors
+    pub fn line(&self) -> usize {
+        if self.tokens.is_empty() {
+            return 0;
+        }
+        self.tokens[self.current.min(self.tokens.len() - 1)].line
+    }
+
     fn is_at_end(&self)->bool{
         self.current >= self.tokens.len()
     }
@@ -106,6 +1173,14 @@ impl Parser {
         }
     }
     
+    /// Check the type of the token after the next one
+    fn check_next(&self, token_type: TokenType)->bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => token.token_type == token_type,
+            None => false,
+        }
+    }
+
     fn check(&self, token_type: TokenType)->bool {
         if self.is_at_end() {false} else {
             self.peek().token_type == token_type
@@ -114,6 +1189,191 @@ impl Parser {
 }
 
 
+/// The part of a declaration after its specifiers, giving the name declared and how its type is
+/// built from the type the specifiers name
+#[derive(Debug)]
+enum Declarator {
+    Identifier{name: String, line: usize},
+    /// A declarator without a name, as in a cast
+    Abstract,
+    Pointer(Box<Declarator>),
+    /// The size is None if it is left out
+    Array{inner: Box<Declarator>, size: Option<usize>},
+    Function{params: Vec<(Type, Declarator)>, inner: Box<Declarator>, variadic: bool},
+}
+
+/// What a declarator declares, once applied to the type named by the specifiers
+struct Declared {
+    /// The name declared and the line it is on, None for an abstract declarator
+    name: Option<(String, usize)>,
+    ty: Type,
+    /// Names of the parameters, if a function is declared
+    params: Vec<String>,
+}
+
+/// Apply a declarator to the type named by the specifiers
+fn declarator_type(base: Type, declarator: Declarator) -> Result<Declared, ParseError> {
+    match declarator {
+        Declarator::Identifier { name, line } => Ok(Declared { name: Some((name, line)), ty: base, params: Vec::new() }),
+        Declarator::Abstract => Ok(Declared { name: None, ty: base, params: Vec::new() }),
+        Declarator::Pointer(inner) => declarator_type(Type::Pointer(Box::new(base)), *inner),
+        Declarator::Array { inner, size } => {
+            if !base.is_complete() {
+                return Err(ParseError::InvalidType);
+            }
+            declarator_type(Type::Array { element: Box::new(base), size }, *inner)
+        }
+        Declarator::Function { params, inner, variadic } => {
+            // A function can't return an array or another function, only pointers to them
+            if matches!(base, Type::Array { .. } | Type::Function { .. }) {
+                return Err(ParseError::InvalidType);
+            }
+            let mut names = Vec::new();
+            let mut types = Vec::new();
+            for (param_base, param) in params {
+                let Declared { name, ty, .. } = declarator_type(param_base, param)?;
+                // Only the whole parameter list can be void
+                if ty == Type::Void {
+                    return Err(ParseError::InvalidParams);
+                }
+                // Parameters can be unnamed in a declaration, which is checked for when defining
+                // the function
+                names.push(name.map(|(name, _)| name).unwrap_or_default());
+                // A parameter declared as an array or a function is a pointer to its element type
+                // or to the function
+                types.push(match ty {
+                    Type::Array { element, .. } => Type::Pointer(element),
+                    ty @ Type::Function { .. } => Type::Pointer(Box::new(ty)),
+                    ty => ty,
+                });
+            }
+            let ty = Type::Function { params: types, ret: Box::new(base), variadic };
+            // The parameters are named by the declarator of the function itself, rather than of
+            // one it returns a pointer to, or of a pointer to one
+            match *inner {
+                Declarator::Identifier { name, line } => Ok(Declared { name: Some((name, line)), ty, params: names }),
+                inner => declarator_type(ty, inner),
+            }
+        }
+    }
+}
+
+/// Constant holding the value of an enumerator, an int if it fits, or else of the type of its
+/// enumeration if that is known yet, or the narrowest long type holding it
+fn enumerator_constant(value: i128, enum_ty: Option<&Type>) -> Result<Const, ParseError> {
+    if let Ok(value) = i32::try_from(value) {
+        return Ok(Const::Int(value));
+    }
+    let ty = match enum_ty {
+        Some(ty) => ty,
+        None if i64::try_from(value).is_ok() => &Type::Long,
+        None if u64::try_from(value).is_ok() => &Type::ULong,
+        None => return Err(ParseError::InvalidEnumerator),
+    };
+    Ok(Const::from_bits(ty, value as i64))
+}
+
+/// Whether a token is one of the keywords which make up the name of a type
+fn is_type_keyword(token_type: TokenType) -> bool {
+    matches!(token_type, TokenType::Int | TokenType::Long | TokenType::Short | TokenType::Char
+        | TokenType::Signed | TokenType::Unsigned | TokenType::Float | TokenType::Double | TokenType::Void
+        | TokenType::Struct | TokenType::Union | TokenType::Enum)
+}
+
+/// Type named by a list of type specifiers, which may be in any order
+fn type_from_specifiers(specifiers: &[TokenType]) -> Result<Type, ParseError> {
+    let count = |token_type: TokenType| specifiers.iter().filter(|&&specifier| specifier == token_type).count();
+    let (ints, longs, shorts, chars) = (count(TokenType::Int), count(TokenType::Long), count(TokenType::Short), count(TokenType::Char));
+    let (signeds, unsigneds) = (count(TokenType::Signed), count(TokenType::Unsigned));
+    if specifiers.is_empty() {
+        return Err(ParseError::ExpectedType);
+    }
+    // Void and the floating types can't be combined with any other specifier, long double isn't
+    // supported
+    match specifiers {
+        [TokenType::Float] => return Ok(Type::Float),
+        [TokenType::Double] => return Ok(Type::Double),
+        [TokenType::Void] => return Ok(Type::Void),
+        _ => {}
+    }
+    if specifiers.iter().any(|&specifier| matches!(specifier, TokenType::Float | TokenType::Double | TokenType::Void)) {
+        return Err(ParseError::InvalidType);
+    }
+    if ints > 1 || longs > 2 || shorts > 1 || chars > 1 || signeds + unsigneds > 1
+        || (shorts > 0 && longs > 0) || (chars > 0 && ints + shorts + longs > 0) {
+        return Err(ParseError::InvalidType);
+    }
+    let unsigned = unsigneds > 0;
+    let ty = if chars > 0 {
+        // Plain char is a distinct type from signed char, even though both are signed
+        if signeds > 0 {Type::SChar} else if unsigned {Type::UChar} else {Type::Char}
+    } else if shorts > 0 {
+        if unsigned {Type::UShort} else {Type::Short}
+    } else if longs > 0 {
+        if unsigned {Type::ULong} else {Type::Long}
+    } else if unsigned {
+        Type::UInt
+    } else {
+        Type::Int
+    };
+    Ok(ty)
+}
+
+/// Decode the escape sequences in the text of a character constant or string literal, giving its
+/// bytes
+fn unescape(text: &str) -> Result<Vec<u8>, ParseError> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut chars = text.bytes().peekable();
+    while let Some(c) = chars.next() {
+        if c != b'\\' {
+            bytes.push(c);
+            continue;
+        }
+        let escaped = chars.next().ok_or(ParseError::InvalidEscape)?;
+        let value = match escaped {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'v' => 0x0b,
+            b'\\' | b'\'' | b'"' | b'?' => escaped,
+            // Up to three octal digits
+            b'0'..=b'7' => {
+                let mut value = (escaped - b'0') as u32;
+                for _ in 0..2 {
+                    match chars.peek() {
+                        Some(&digit @ b'0'..=b'7') => {
+                            value = value * 8 + (digit - b'0') as u32;
+                            _ = chars.next();
+                        }
+                        _ => break,
+                    }
+                }
+                u8::try_from(value).map_err(|_| ParseError::InvalidEscape)?
+            }
+            // Any number of hex digits, whose value must fit in a char
+            b'x' => {
+                let mut value: u32 = 0;
+                let mut digits = 0;
+                while let Some(digit) = chars.peek().and_then(|&c| (c as char).to_digit(16)) {
+                    value = value.saturating_mul(16).saturating_add(digit);
+                    digits += 1;
+                    _ = chars.next();
+                }
+                if digits == 0 {
+                    return Err(ParseError::InvalidEscape);
+                }
+                u8::try_from(value).map_err(|_| ParseError::InvalidEscape)?
+            }
+            _ => return Err(ParseError::InvalidEscape),
+        };
+        bytes.push(value);
+    }
+    Ok(bytes)
+}
+
 #[derive(Debug)]
 pub enum ParseError {
     UnmatchedParen,
@@ -123,45 +1383,117 @@ pub enum ParseError {
     ExpectedType,
     ExpectedLeftParen,
     ExpectedLeftBrace,
-    ExpectedReturn,
+    ExpectedComma,
+    ExpectedEqual,
     InvalidParams,
     InvalidInt,
+    InvalidFloat,
     UnexpectedToken,
+    UnexpectedEnd,
+    InvalidLvalue,
+    ExpectedColon,
+    ExpectedWhile,
+    NestedFunction,
+    InvalidForInit,
+    InvalidStorageClass,
+    InvalidType,
+    InvalidAddressOf,
+    ExpectedRightBracket,
+    InvalidArraySize,
+    InvalidChar,
+    InvalidEscape,
+    StructRedefinition,
+    IncompleteMember,
+    DuplicateMember,
+    EmptyStruct,
+    WrongTagKind,
+    InvalidBitFieldType,
+    InvalidBitFieldWidth,
+    UndefinedEnum,
+    EnumRedefinition,
+    InvalidEnumerator,
+    ConflictingDeclaration,
+    InvalidDesignator,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnmatchedParen => write!(f, "expected ')'"),
+            ParseError::UnmatchedBrace => write!(f, "expected '}}'"),
+            ParseError::ExpectedIdentifier => write!(f, "expected identifier"),
+            ParseError::ExpectedSemicolon => write!(f, "expected ';'"),
+            ParseError::ExpectedType => write!(f, "expected type specifier"),
+            ParseError::ExpectedLeftParen => write!(f, "expected '('"),
+            ParseError::ExpectedLeftBrace => write!(f, "expected '{{'"),
+            ParseError::ExpectedComma => write!(f, "expected ','"),
+            ParseError::ExpectedEqual => write!(f, "expected '='"),
+            ParseError::InvalidParams => write!(f, "invalid parameter list"),
+            ParseError::InvalidInt => write!(f, "invalid integer constant"),
+            ParseError::InvalidFloat => write!(f, "invalid floating constant"),
+            ParseError::UnexpectedToken => write!(f, "unexpected token"),
+            ParseError::UnexpectedEnd => write!(f, "unexpected end of file"),
+            ParseError::InvalidLvalue => write!(f, "expression is not assignable"),
+            ParseError::ExpectedColon => write!(f, "expected ':'"),
+            ParseError::ExpectedWhile => write!(f, "expected 'while'"),
+            ParseError::NestedFunction => write!(f, "function definition is not allowed here"),
+            ParseError::InvalidForInit => write!(f, "declaration of non-variable in 'for' loop initial declaration"),
+            ParseError::InvalidStorageClass => write!(f, "invalid storage class specifiers"),
+            ParseError::InvalidType => write!(f, "invalid type specifiers"),
+            ParseError::InvalidAddressOf => write!(f, "cannot take the address of an rvalue"),
+            ParseError::ExpectedRightBracket => write!(f, "expected ']'"),
+            ParseError::InvalidArraySize => write!(f, "array size is not a positive integer constant"),
+            ParseError::InvalidChar => write!(f, "character constant must hold a single character"),
+            ParseError::InvalidEscape => write!(f, "invalid escape sequence"),
+            ParseError::StructRedefinition => write!(f, "redefinition of struct or union"),
+            ParseError::IncompleteMember => write!(f, "member has incomplete type"),
+            ParseError::DuplicateMember => write!(f, "duplicate member"),
+            ParseError::EmptyStruct => write!(f, "struct or union has no named members"),
+            ParseError::WrongTagKind => write!(f, "use of tag with a different kind of type than its declaration"),
+            ParseError::InvalidBitFieldType => write!(f, "bit-field has a non-integer type"),
+            ParseError::InvalidBitFieldWidth => write!(f, "bit-field width is not a valid integer constant"),
+            ParseError::UndefinedEnum => write!(f, "use of undefined enum"),
+            ParseError::EnumRedefinition => write!(f, "redefinition of enum"),
+            ParseError::InvalidEnumerator => write!(f, "enumerator value is not an integer constant in range"),
+            ParseError::ConflictingDeclaration => write!(f, "redeclared as a different kind of symbol"),
+            ParseError::InvalidDesignator => write!(f, "array index in initializer is not a non-negative integer constant"),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::lex;
     use super::*;
+
+    fn parse(source: &str) -> Result<Stmt, ParseError> {
+        let tokens = lex::Lexer::new(source.to_string()).tokenize().unwrap();
+        Parser::new(tokens).parse()
+    }
+
     #[test]
     fn test_parser() {
         // Create test source code
         let source_code = "int main(void){return 2;}";
-        // Create lex
-        let mut lexer = lex::Lexer::new(source_code.to_string());
-        // Tokenize the input source code
-        let tokens = lexer.tokenize().unwrap();
-        // Create parse
-        let mut parser = Parser::new(tokens);
-        // Parse the source code
-        let ast = parser.parse().unwrap();
+        // Lex and parse the source code
+        let ast = parse(source_code).unwrap();
         // Check that the top level of the ast is a program
         assert!(
             match ast {
                 Stmt::Program { body } => {
-                    match *body {
-                        Stmt::FuncDef { name, body } => {
-                            let name_ok = match *name {
-                                Expr::Identifier { value } => {
+                    match body.into_iter().next().unwrap() {
+                        Stmt::FuncDef { name, body, .. } => {
+                            let name_ok = match name.kind {
+                                ExprKind::Identifier { value } => {
                                     value == "main"
                                 }
                                 _=>false,
                             };
-                            name_ok && match *body {
-                                Stmt::Return { value } => {
-                                    match *value {
-                                        Expr::IntConstant { value } => {
-                                            value == 2
+                            name_ok && body.len() == 1 && match &body[0] {
+                                Stmt::Return { value: Some(value) } => {
+                                    match value.kind {
+                                        ExprKind::Constant { value } => {
+                                            value == Const::Int(2)
                                         }
                                         _ => {false}
                                     }
@@ -176,4 +1508,592 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn test_precedence() {
+        let ast = parse("int main(void){return 1 | 2 ^ 3 & 4 << 5 + -6;}").unwrap();
+        let value = match ast {
+            Stmt::Program { body } => match body.into_iter().next().unwrap() {
+                Stmt::FuncDef { mut body, .. } => match body.remove(0) {
+                    Stmt::Return { value: Some(value) } => value,
+                    _ => panic!("Expected return"),
+                },
+                _ => panic!("Expected function"),
+            },
+            _ => panic!("Expected program"),
+        };
+        // | binds loosest, then ^, &, <<, + and finally unary -
+        let ExprKind::Binary { op: BinaryOp::BitwiseOr, right, .. } = value.kind else { panic!("Expected |") };
+        let ExprKind::Binary { op: BinaryOp::BitwiseXor, right, .. } = right.kind else { panic!("Expected ^") };
+        let ExprKind::Binary { op: BinaryOp::BitwiseAnd, right, .. } = right.kind else { panic!("Expected &") };
+        let ExprKind::Binary { op: BinaryOp::ShiftLeft, right, .. } = right.kind else { panic!("Expected <<") };
+        let ExprKind::Binary { op: BinaryOp::Add, right, .. } = right.kind else { panic!("Expected +") };
+        assert!(matches!(right.kind, ExprKind::Unary { op: UnaryOp::Negate, .. }));
+    }
+
+    #[test]
+    fn test_left_associative() {
+        let ast = parse("int main(void){return 8 >> 2 >> 1;}").unwrap();
+        let Stmt::Program { body } = ast else { panic!("Expected program") };
+        let Stmt::FuncDef { mut body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        let Stmt::Return { value: Some(value) } = body.remove(0) else { panic!("Expected return") };
+        let ExprKind::Binary { op: BinaryOp::ShiftRight, left, .. } = value.kind else { panic!("Expected >>") };
+        assert!(matches!(left.kind, ExprKind::Binary { op: BinaryOp::ShiftRight, .. }));
+    }
+
+    #[test]
+    fn test_block_items() {
+        let Stmt::Program { body } = parse("int main(void){int a; int b = 2; ; a = b = 3; return a;}").unwrap() else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        assert_eq!(body.len(), 5);
+        assert!(matches!(&body[0], Stmt::Declaration { name, init: None, .. } if name == "a"));
+        assert!(matches!(&body[1], Stmt::Declaration { name, init: Some(_), .. } if name == "b"));
+        assert!(matches!(body[2], Stmt::Null));
+        // Assignment groups to the right
+        let Stmt::Expression { expr } = &body[3] else { panic!("Expected expression statement") };
+        let ExprKind::Assignment { right, .. } = &expr.kind else { panic!("Expected assignment") };
+        assert!(matches!(right.kind, ExprKind::Assignment { .. }));
+    }
+
+    #[test]
+    fn test_invalid_lvalue() {
+        assert!(matches!(parse("int main(void){int a; 2 = a; return a;}"), Err(ParseError::InvalidLvalue)));
+    }
+
+    #[test]
+    fn test_increments() {
+        let Stmt::Program { body } = parse("int main(void){int a = 1; a <<= ++a - a--; return a;}").unwrap() else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        let Stmt::Expression { expr } = &body[1] else { panic!("Expected expression statement") };
+        let ExprKind::CompoundAssignment { op: BinaryOp::ShiftLeft, right, .. } = &expr.kind else { panic!("Expected <<=") };
+        let ExprKind::Binary { op: BinaryOp::Subtract, left, right } = &right.kind else { panic!("Expected -") };
+        assert!(matches!(left.kind, ExprKind::CompoundAssignment { op: BinaryOp::Add, .. }));
+        assert!(matches!(right.kind, ExprKind::Postfix { op: BinaryOp::Subtract, .. }));
+    }
+
+    #[test]
+    fn test_increment_non_lvalue() {
+        for source in ["int main(void){return 1++;}", "int main(void){int a; return --(a + 1);}",
+                       "int main(void){int a; a++ += 1; return a;}"] {
+            assert!(matches!(parse(source), Err(ParseError::InvalidLvalue)));
+        }
+    }
+
+    #[test]
+    fn test_dangling_else() {
+        let Stmt::Program { body } = parse("int main(void){if (1) if (2) return 3; else return 4; return 5;}").unwrap() else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        // The else is attached to the inner if
+        let Stmt::If { then_branch, else_branch: None, .. } = &body[0] else { panic!("Expected if without else") };
+        assert!(matches!(**then_branch, Stmt::If { else_branch: Some(_), .. }));
+    }
+
+    #[test]
+    fn test_conditional_right_associative() {
+        let Stmt::Program { body } = parse("int main(void){int a; a = 1 ? 2 : 3 ? 4 : 5; return a || 1 ? a : 0;}").unwrap() else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        let Stmt::Expression { expr } = &body[1] else { panic!("Expected expression statement") };
+        let ExprKind::Assignment { right, .. } = &expr.kind else { panic!("Expected assignment") };
+        let ExprKind::Conditional { else_expr, .. } = &right.kind else { panic!("Expected conditional") };
+        assert!(matches!(else_expr.kind, ExprKind::Conditional { .. }));
+        // || binds tighter than ?:
+        let Stmt::Return { value: Some(value) } = &body[2] else { panic!("Expected return") };
+        let ExprKind::Conditional { condition, .. } = &value.kind else { panic!("Expected conditional") };
+        assert!(matches!(condition.kind, ExprKind::Binary { op: BinaryOp::Or, .. }));
+    }
+
+    #[test]
+    fn test_for_clauses() {
+        let Stmt::Program { body } = parse("int main(void){for (int i = 0; ; i++) ; for (;;) break; return 0;}").unwrap() else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        let Stmt::For { init, condition: None, post: Some(_), .. } = &body[0] else { panic!("Expected for") };
+        assert!(matches!(init[..], [Stmt::Declaration { .. }]));
+        let Stmt::For { init, condition: None, post: None, body: loop_body, .. } = &body[1] else { panic!("Expected for") };
+        assert!(init.is_empty());
+        assert!(matches!(**loop_body, Stmt::Break { .. }));
+    }
+
+    #[test]
+    fn test_labeled_statement() {
+        let Stmt::Program { body } = parse("int main(void){int a; a: a = a ? a : 1; goto a; return a;}").unwrap() else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        // Labels and variables live in separate namespaces
+        let Stmt::Labeled { label, body: labeled, .. } = &body[1] else { panic!("Expected labeled statement") };
+        assert_eq!(label, "a");
+        assert!(matches!(**labeled, Stmt::Expression { .. }));
+        assert!(matches!(&body[2], Stmt::Goto { label, .. } if label == "a"));
+    }
+
+    #[test]
+    fn test_functions() {
+        let Stmt::Program { body } = parse("int f(int a, int b); int g(); int main(void){return f(1, g() + 2);}").unwrap() else { panic!("Expected program") };
+        assert!(matches!(&body[0], Stmt::FuncDecl { params, .. } if params == &["a", "b"]));
+        assert!(matches!(&body[1], Stmt::FuncDecl { params, .. } if params.is_empty()));
+        let Stmt::FuncDef { body, .. } = &body[2] else { panic!("Expected function") };
+        let Stmt::Return { value: Some(value) } = &body[0] else { panic!("Expected return") };
+        let ExprKind::FunctionCall { function, args } = &value.kind else { panic!("Expected call") };
+        assert!(matches!(&function.kind, ExprKind::Identifier { value } if value == "f"));
+        assert!(matches!(&args[1].kind, ExprKind::Binary { left, .. } if matches!(left.kind, ExprKind::FunctionCall { .. })));
+    }
+
+    #[test]
+    fn test_invalid_functions() {
+        for (source, expected) in [
+            ("int main(void){int f(void){return 0;} return 0;}", ParseError::NestedFunction),
+            ("int main(void){for (int f(void);;) ; return 0;}", ParseError::InvalidForInit),
+            ("int f(int a int b);", ParseError::UnmatchedParen),
+            ("static extern int a;", ParseError::InvalidStorageClass),
+            ("int static int a;", ParseError::InvalidType),
+            ("static a;", ParseError::ExpectedType),
+            ("int main(void){static int f(void); return 0;}", ParseError::InvalidStorageClass),
+            ("int main(void){for (static int i = 0;;) ; return 0;}", ParseError::InvalidForInit),
+        ] {
+            let result = parse(source);
+            assert!(matches!(result, Err(ref error) if std::mem::discriminant(error) == std::mem::discriminant(&expected)));
+        }
+    }
+
+    #[test]
+    fn test_variadic_params() {
+        let Stmt::Program { body } = parse("int sum(int count, ...);").unwrap() else { panic!("Expected program") };
+        assert!(matches!(&body[0], Stmt::FuncDecl { params, ty: Type::Function { variadic: true, .. }, .. } if params.len() == 1));
+        // The ellipsis must follow a named parameter
+        assert!(matches!(parse("int sum(...);"), Err(ParseError::InvalidParams)));
+    }
+
+    #[test]
+    fn test_types() {
+        let Stmt::Program { body } = parse("unsigned long long a; long int signed b; char c; signed char d; unsigned e;\n\
+                                            short f(unsigned char x, long y) { return (short)(x + 10UL); }").unwrap() else { panic!("Expected program") };
+        let types: Vec<Type> = body.iter().filter_map(|item| match item {
+            Stmt::Declaration { ty, .. } => Some(ty.clone()),
+            _ => None,
+        }).collect();
+        assert_eq!(types, [Type::ULong, Type::Long, Type::Char, Type::SChar, Type::UInt]);
+        let Stmt::FuncDef { ty, body, .. } = &body[5] else { panic!("Expected function") };
+        assert_eq!(ty.to_string(), "short (unsigned char, long)");
+        let Stmt::Return { value: Some(value) } = &body[0] else { panic!("Expected return") };
+        let ExprKind::Cast { target: Type::Short, expr } = &value.kind else { panic!("Expected cast") };
+        assert!(matches!(&expr.kind, ExprKind::Binary { right, .. } if matches!(right.kind, ExprKind::Constant { value: Const::ULong(10) })));
+        for source in ["long short a;", "unsigned signed a;", "char int a;", "long long long a;"] {
+            assert!(matches!(parse(source), Err(ParseError::InvalidType)));
+        }
+    }
+
+    #[test]
+    fn test_constant_types() {
+        for (source, expected) in [
+            ("2147483647", Const::Int(2147483647)),
+            ("2147483648", Const::Long(2147483648)),
+            ("1l", Const::Long(1)),
+            ("4294967295u", Const::UInt(4294967295)),
+            ("4294967296U", Const::ULong(4294967296)),
+            ("1ul", Const::ULong(1)),
+            ("18446744073709551615LLU", Const::ULong(u64::MAX)),
+        ] {
+            let Stmt::Program { body } = parse(&format!("int main(void){{return {};}}", source)).unwrap() else { panic!("Expected program") };
+            let Stmt::FuncDef { body, .. } = &body[0] else { panic!("Expected function") };
+            assert!(matches!(&body[0], Stmt::Return { value: Some(value) } if matches!(value.kind, ExprKind::Constant { value } if value == expected)));
+        }
+        assert!(matches!(parse("int main(void){return 18446744073709551616;}"), Err(ParseError::InvalidInt)));
+    }
+
+    #[test]
+    fn test_literals() {
+        for (source, expected) in [
+            ("'a'", Const::Int(97)),
+            (r"'\n'", Const::Int(10)),
+            (r"'\''", Const::Int(39)),
+            (r"'\0'", Const::Int(0)),
+            (r"'\177'", Const::Int(127)),
+            (r"'\xff'", Const::Int(-1)),
+        ] {
+            let Stmt::Program { body } = parse(&format!("int main(void){{return {};}}", source)).unwrap() else { panic!("Expected program") };
+            let Stmt::FuncDef { body, .. } = &body[0] else { panic!("Expected function") };
+            assert!(matches!(&body[0], Stmt::Return { value: Some(value) } if matches!(value.kind, ExprKind::Constant { value } if value == expected)));
+        }
+        // Adjacent strings are joined, after their escapes are decoded
+        let Stmt::Program { body } = parse(r#"char *s = "a\tb" "\x41\101" "\"\\";"#).unwrap() else { panic!("Expected program") };
+        let Stmt::Declaration { init: Some(Initializer::Single(init)), .. } = &body[0] else { panic!("Expected declaration") };
+        assert!(matches!(&init.kind, ExprKind::String { value } if value == b"a\tbAA\"\\"));
+        assert!(matches!(parse("int c = 'ab';"), Err(ParseError::InvalidChar)));
+        assert!(matches!(parse(r"int c = '\q';"), Err(ParseError::InvalidEscape)));
+        assert!(matches!(parse(r#"char *s = "\x100";"#), Err(ParseError::InvalidEscape)));
+        assert!(matches!(parse(r#"char *s = "\x";"#), Err(ParseError::InvalidEscape)));
+    }
+
+    #[test]
+    fn test_sizeof() {
+        let Stmt::Program { body } = parse("void *f(void *p, int n); void g(void){return;}\n\
+                                            int main(void){return sizeof(int *) + sizeof (1) + sizeof -1 + _Alignof(long[2]);}").unwrap() else { panic!("Expected program") };
+        let Stmt::FuncDecl { ty, .. } = &body[0] else { panic!("Expected function") };
+        assert_eq!(ty.to_string(), "void *(void *, int)");
+        let Stmt::FuncDef { ty, body: g_body, .. } = &body[1] else { panic!("Expected function") };
+        assert_eq!(ty.to_string(), "void (void)");
+        assert!(matches!(g_body[0], Stmt::Return { value: None }));
+        let Stmt::FuncDef { body, .. } = &body[2] else { panic!("Expected function") };
+        let Stmt::Return { value: Some(value) } = &body[0] else { panic!("Expected return") };
+        let ExprKind::Binary { left, right, .. } = &value.kind else { panic!("Expected binary") };
+        assert!(matches!(&right.kind, ExprKind::AlignOf { ty: Type::Array { .. } }));
+        let ExprKind::Binary { left, right, .. } = &left.kind else { panic!("Expected binary") };
+        // sizeof binds tighter than binary operators, and applies to the negation
+        assert!(matches!(&right.kind, ExprKind::SizeOf { expr } if matches!(expr.kind, ExprKind::Unary { .. })));
+        let ExprKind::Binary { left, right, .. } = &left.kind else { panic!("Expected binary") };
+        assert!(matches!(&right.kind, ExprKind::SizeOf { expr } if matches!(expr.kind, ExprKind::Constant { .. })));
+        assert!(matches!(&left.kind, ExprKind::SizeOfType { ty: Type::Pointer(_) }));
+        assert!(matches!(parse("void a[3];"), Err(ParseError::InvalidType)));
+        assert!(matches!(parse("int f(void x);"), Err(ParseError::InvalidParams)));
+        assert!(matches!(parse("int f(int a, void);"), Err(ParseError::InvalidParams)));
+        assert!(matches!(parse("void int x;"), Err(ParseError::InvalidType)));
+        assert!(matches!(parse("int main(void){return _Alignof 1;}"), Err(ParseError::ExpectedLeftParen)));
+        // A size can be given by sizeof a type name
+        let Stmt::Program { body } = parse("char a[sizeof(long) * 2];").unwrap() else { panic!("Expected program") };
+        assert!(matches!(&body[0], Stmt::Declaration { ty: Type::Array { size: Some(16), .. }, .. }));
+    }
+
+    #[test]
+    fn test_structs() {
+        let Stmt::Program { body } = parse("struct s; struct s *p; struct s {char c; double d; struct s *next; short a[3];};\n\
+                                            int main(void){struct s {int x;} inner; return p->next.c + inner.x;}").unwrap() else {
+            panic!("Expected program");
+        };
+        // The pointer declared before the definition refers to the same, now complete, type
+        let Stmt::Declaration { ty: Type::Pointer(pointee), .. } = &body[1] else { panic!("Expected declaration") };
+        assert_eq!(pointee.size(), 32);
+        assert_eq!(pointee.alignment(), 8);
+        let Type::Struct(struct_type) = &**pointee else { panic!("Expected struct") };
+        let offsets: Vec<usize> = struct_type.definition.borrow().as_ref().unwrap().members.iter().map(|member| member.offset).collect();
+        assert_eq!(offsets, vec![0, 8, 16, 24]);
+        assert!(matches!(&body[2], Stmt::StructDecl { ty } if ty == &**pointee));
+        // A tag declared in a block hides the one at file scope
+        let Stmt::FuncDef { body, .. } = &body[3] else { panic!("Expected function") };
+        let Stmt::Declaration { ty, .. } = &body[0] else { panic!("Expected declaration") };
+        assert_ne!(ty, &**pointee);
+        assert_eq!(ty.size(), 4);
+        let Stmt::Return { value: Some(value) } = &body[1] else { panic!("Expected return") };
+        let ExprKind::Binary { left, .. } = &value.kind else { panic!("Expected binary") };
+        let ExprKind::Member { expr, member } = &left.kind else { panic!("Expected member") };
+        assert_eq!(member, "c");
+        let ExprKind::Member { expr, member } = &expr.kind else { panic!("Expected member") };
+        assert_eq!(member, "next");
+        assert!(matches!(expr.kind, ExprKind::Dereference { .. }));
+        assert!(matches!(parse("struct s {int a;}; struct s {int a;};"), Err(ParseError::StructRedefinition)));
+        assert!(matches!(parse("struct s {int a; long a;};"), Err(ParseError::DuplicateMember)));
+        assert!(matches!(parse("struct s {struct s inner;};"), Err(ParseError::IncompleteMember)));
+        assert!(matches!(parse("struct s {};"), Err(ParseError::EmptyStruct)));
+        assert!(matches!(parse("struct s; struct s a[2];"), Err(ParseError::InvalidType)));
+        assert!(matches!(parse("long struct s x;"), Err(ParseError::InvalidType)));
+        // An inner declaration of just the tag makes a new type, even if the outer one is complete
+        assert!(matches!(parse("struct s {int a;}; int main(void){struct s; struct s x[1]; return 0;}"), Err(ParseError::InvalidType)));
+    }
+
+    #[test]
+    fn test_unions_and_bit_fields() {
+        let layout = |source: &str| {
+            let Stmt::Program { body } = parse(source).unwrap() else { panic!("Expected program") };
+            let Stmt::StructDecl { ty: ty @ Type::Struct(struct_type) } = &body[0] else { panic!("Expected struct") };
+            let members = struct_type.definition.borrow().as_ref().unwrap().members.iter()
+                .map(|member| (member.offset, member.bits.map(|bits| (bits.offset, bits.width)))).collect::<Vec<_>>();
+            (ty.size(), ty.alignment(), members)
+        };
+        // The members of a union overlap, and it is as big as the largest
+        assert_eq!(layout("union u {char c[5]; int i; short s;};"), (8, 4, vec![(0, None), (0, None), (0, None)]));
+        // A bit-field starts a new storage unit rather than straddle one
+        assert_eq!(layout("struct s {char c; short s : 9; char d : 7; long l : 3;};"),
+                   (8, 8, vec![(0, None), (2, Some((0, 9))), (3, Some((1, 7))), (0, Some((32, 3)))]));
+        assert_eq!(layout("struct s {long a : 40; int b : 30;};"), (16, 8, vec![(0, Some((0, 40))), (8, Some((0, 30)))]));
+        // Unnamed bit-fields take up space without affecting the alignment, and width 0 ends the unit
+        assert_eq!(layout("struct s {char c; int : 4;};"), (2, 1, vec![(0, None)]));
+        assert_eq!(layout("struct s {int a : 3; char : 0; char b : 2;};"), (4, 4, vec![(0, Some((0, 3))), (1, Some((0, 2)))]));
+        assert_eq!(layout("union u {int x : 3; char c; long l : 33;};"), (8, 8, vec![(0, Some((0, 3))), (0, None), (0, Some((0, 33)))]));
+        assert!(matches!(parse("struct s; union s *p;"), Err(ParseError::WrongTagKind)));
+        assert!(matches!(parse("union u {int a;}; union u {int a;};"), Err(ParseError::StructRedefinition)));
+        assert!(matches!(parse("struct s {double d : 3;};"), Err(ParseError::InvalidBitFieldType)));
+        assert!(matches!(parse("struct s {char c : 9;};"), Err(ParseError::InvalidBitFieldWidth)));
+        assert!(matches!(parse("struct s {int a; int b : 0;};"), Err(ParseError::InvalidBitFieldWidth)));
+        assert!(matches!(parse("struct s {int : 3;};"), Err(ParseError::EmptyStruct)));
+        assert!(matches!(parse("struct s {int;};"), Err(ParseError::ExpectedIdentifier)));
+    }
+
+    #[test]
+    fn test_enums() {
+        let Stmt::Program { body } = parse("enum e {A, B = 5, C, D = C * 2}; enum e x[D];\n\
+                                            int main(void){int B = A; enum {A = -1} y; return A + B;}").unwrap() else {
+            panic!("Expected program");
+        };
+        assert!(matches!(body[0], Stmt::Null));
+        // The constants can size an array, and an enumeration without negative values is unsigned
+        let Stmt::Declaration { ty, .. } = &body[1] else { panic!("Expected declaration") };
+        assert_eq!(ty, &Type::Array { element: Box::new(Type::UInt), size: Some(12) });
+        let Stmt::FuncDef { body, .. } = &body[2] else { panic!("Expected function") };
+        let Stmt::Declaration { init: Some(Initializer::Single(init)), .. } = &body[0] else { panic!("Expected declaration") };
+        assert!(matches!(init.kind, ExprKind::Constant { value: Const::Int(0) }));
+        let Stmt::Declaration { ty, .. } = &body[1] else { panic!("Expected declaration") };
+        assert_eq!(ty, &Type::Int);
+        // The variable B hides the constant, and the inner A hides the outer one
+        let Stmt::Return { value: Some(value) } = &body[2] else { panic!("Expected return") };
+        let ExprKind::Binary { left, right, .. } = &value.kind else { panic!("Expected binary") };
+        assert!(matches!(left.kind, ExprKind::Constant { value: Const::Int(-1) }));
+        assert!(matches!(right.kind, ExprKind::Identifier { .. }));
+        let Stmt::Program { body } = parse("enum a {X = -1, Y = 4294967295}; enum b {Z = 4294967296}; enum a p; enum b q;").unwrap() else {
+            panic!("Expected program");
+        };
+        assert!(matches!(&body[2], Stmt::Declaration { ty: Type::Long, .. }));
+        assert!(matches!(&body[3], Stmt::Declaration { ty: Type::ULong, .. }));
+        assert!(matches!(parse("enum e x;"), Err(ParseError::UndefinedEnum)));
+        assert!(matches!(parse("enum e {A}; enum e {B};"), Err(ParseError::EnumRedefinition)));
+        assert!(matches!(parse("struct e; enum e {A};"), Err(ParseError::WrongTagKind)));
+        assert!(matches!(parse("enum {A}; int A;"), Err(ParseError::ConflictingDeclaration)));
+        assert!(matches!(parse("enum {A, A};"), Err(ParseError::ConflictingDeclaration)));
+        assert!(matches!(parse("int x; enum {A = x};"), Err(ParseError::InvalidEnumerator)));
+        // Only an enumeration specifier which declares something can stand on its own
+        let Stmt::Program { body } = parse("enum e {A}; enum e; static enum {B}; enum e x, y;").unwrap() else {
+            panic!("Expected program");
+        };
+        assert!(matches!(&body[..], [Stmt::Null, Stmt::Null, Stmt::Null, Stmt::Declaration { .. }, Stmt::Declaration { .. }]));
+        assert!(matches!(parse("enum e {A}; typedef enum e t; t;"), Err(ParseError::ExpectedIdentifier)));
+        assert!(parse("enum e {A}; int f(void){enum e; return A;}").is_ok());
+        assert!(matches!(parse("enum {A = -1, B = 18446744073709551615ul};"), Err(ParseError::InvalidEnumerator)));
+    }
+
+    #[test]
+    fn test_constant_warnings() {
+        // Shifting out every bit gives 0, and is warned about, but a negative count isn't constant
+        let mut parser = Parser::new(lex::Lexer::new("enum {A = 1 << 40, B = 2};\nint a[B + (1L >> 64) + (4 >> 1)];".to_string()).tokenize().unwrap());
+        let Stmt::Program { body } = parser.parse().unwrap() else { panic!("Expected program") };
+        let Stmt::Declaration { ty, .. } = &body[1] else { panic!("Expected declaration") };
+        assert_eq!(ty, &Type::Array { element: Box::new(Type::Int), size: Some(4) });
+        assert_eq!(parser.warnings(), [
+            Warning::ShiftCountTooLarge { left: true, line: 1 }, Warning::ShiftCountTooLarge { left: false, line: 2 },
+        ]);
+        let mut parser = Parser::new(lex::Lexer::new("enum {A = 1 >> -1};".to_string()).tokenize().unwrap());
+        assert!(matches!(parser.parse(), Err(ParseError::InvalidEnumerator)));
+        assert_eq!(parser.warnings(), [Warning::NegativeShiftCount { left: false, line: 1 }]);
+    }
+
+    #[test]
+    fn test_typedefs() {
+        let Stmt::Program { body } = parse("typedef int T; typedef T *P; typedef T T; P p;\n\
+                                            int main(void){T * x; {int T = 2; T * 3;} (T)1; return sizeof(P);}").unwrap() else {
+            panic!("Expected program");
+        };
+        assert!(matches!(body[..3], [Stmt::Null, Stmt::Null, Stmt::Null]));
+        let Stmt::Declaration { ty, .. } = &body[3] else { panic!("Expected declaration") };
+        assert_eq!(ty, &Type::Pointer(Box::new(Type::Int)));
+        let Stmt::FuncDef { body, .. } = &body[4] else { panic!("Expected function") };
+        // T * x declares a pointer, unless a variable hides the typedef
+        assert!(matches!(&body[0], Stmt::Declaration { ty: Type::Pointer(_), .. }));
+        let Stmt::Compound { body: inner } = &body[1] else { panic!("Expected block") };
+        assert!(matches!(&inner[0], Stmt::Declaration { ty: Type::Int, .. }));
+        assert!(matches!(&inner[1], Stmt::Expression { expr } if matches!(expr.kind, ExprKind::Binary { op: BinaryOp::Multiply, .. })));
+        let Stmt::Expression { expr } = &body[2] else { panic!("Expected expression") };
+        assert!(matches!(&expr.kind, ExprKind::Cast { target: Type::Int, .. }));
+        // A typedef name after another type specifier is the name being declared
+        let Stmt::Program { body } = parse("typedef int T; int main(void){unsigned T = 1; return T;}").unwrap() else {
+            panic!("Expected program");
+        };
+        let Stmt::FuncDef { body, .. } = &body[1] else { panic!("Expected function") };
+        assert!(matches!(&body[0], Stmt::Declaration { ty: Type::UInt, .. }));
+        // Literals spelled like a typedef name are still literals
+        let Stmt::Program { body } = parse("typedef int T; int main(void){char *s = (\"T\"); \"T\"; return ('T');}").unwrap() else {
+            panic!("Expected program");
+        };
+        let Stmt::FuncDef { body, .. } = &body[1] else { panic!("Expected function") };
+        assert!(matches!(&body[0], Stmt::Declaration { init: Some(Initializer::Single(init)), .. } if matches!(init.kind, ExprKind::String { .. })));
+        assert!(matches!(&body[1], Stmt::Expression { expr } if matches!(expr.kind, ExprKind::String { .. })));
+        assert!(matches!(&body[2], Stmt::Return { value: Some(value) } if matches!(value.kind, ExprKind::Constant { .. })));
+        // A label spelled like a typedef name is a label
+        let Stmt::Program { body } = parse("typedef int T; int main(void){ goto T; T: return 1; }").unwrap() else {
+            panic!("Expected program");
+        };
+        let Stmt::FuncDef { body, .. } = &body[1] else { panic!("Expected function") };
+        assert!(matches!(&body[1], Stmt::Labeled { label, .. } if label == "T"));
+        assert!(matches!(parse("typedef int T; typedef long T;"), Err(ParseError::ConflictingDeclaration)));
+        assert!(matches!(parse("int T; typedef int T;"), Err(ParseError::ConflictingDeclaration)));
+        assert!(matches!(parse("typedef int T; T long x;"), Err(ParseError::InvalidType)));
+        assert!(matches!(parse("typedef int F(int a); F f {return 0;}"), Err(ParseError::InvalidParams)));
+        assert!(matches!(parse("int main(void){for (typedef int T; ;) ; return 0;}"), Err(ParseError::InvalidForInit)));
+    }
+
+    #[test]
+    fn test_declarators() {
+        let Stmt::Program { body } = parse("int **a; long *(b); unsigned *f(char *c, int **d);\n\
+                                            int main(void){ return (int)*(long *)&a; }").unwrap() else { panic!("Expected program") };
+        let Stmt::Declaration { ty, .. } = &body[0] else { panic!("Expected declaration") };
+        assert_eq!(ty.to_string(), "int **");
+        let Stmt::Declaration { ty, .. } = &body[1] else { panic!("Expected declaration") };
+        assert_eq!(ty.to_string(), "long *");
+        let Stmt::FuncDecl { ty, params, .. } = &body[2] else { panic!("Expected function") };
+        assert_eq!(ty.to_string(), "unsigned int *(char *, int **)");
+        assert_eq!(params, &["c", "d"]);
+        let Stmt::FuncDef { body, .. } = &body[3] else { panic!("Expected function") };
+        let Stmt::Return { value: Some(value) } = &body[0] else { panic!("Expected return") };
+        let ExprKind::Cast { expr, .. } = &value.kind else { panic!("Expected cast") };
+        let ExprKind::Dereference { expr } = &expr.kind else { panic!("Expected dereference") };
+        let ExprKind::Cast { target, expr } = &expr.kind else { panic!("Expected cast") };
+        assert_eq!(target.to_string(), "long *");
+        assert!(matches!(expr.kind, ExprKind::AddressOf { .. }));
+        assert!(matches!(parse("int main(void){return &1;}"), Err(ParseError::InvalidAddressOf)));
+        assert!(matches!(parse("int f(void)[3];"), Err(ParseError::InvalidType)));
+        assert!(matches!(parse("int *;"), Err(ParseError::ExpectedIdentifier)));
+    }
+
+    #[test]
+    fn test_declarator_lists() {
+        let Stmt::Program { body } = parse("int i, *p = &i, f(void);\n\
+                                            struct s {float a, b, c; unsigned x : 3, : 2, y : 3;};\n\
+                                            typedef long L, *P;\n\
+                                            int main(void){L a = 1, b = a; for (int j = 0, k = 0; j < k; j++) ; return b;}").unwrap() else {
+            panic!("Expected program");
+        };
+        // Each declarator shares the specifiers, and is declared by the time the next is parsed
+        let Stmt::Declaration { name, ty: Type::Int, init: None, .. } = &body[0] else { panic!("Expected declaration") };
+        assert_eq!(name, "i");
+        let Stmt::Declaration { ty: Type::Pointer(_), init: Some(Initializer::Single(init)), .. } = &body[1] else { panic!("Expected declaration") };
+        assert!(matches!(init.kind, ExprKind::AddressOf { .. }));
+        assert!(matches!(&body[2], Stmt::FuncDecl { ty: Type::Function { .. }, .. }));
+        let Stmt::StructDecl { ty: Type::Struct(struct_type) } = &body[3] else { panic!("Expected structure") };
+        let members: Vec<(String, usize)> = struct_type.definition.borrow().as_ref().unwrap().members.iter()
+            .map(|member| (member.name.clone(), member.offset)).collect();
+        let expected = [("a", 0), ("b", 4), ("c", 8), ("x", 12), ("y", 12)].map(|(name, offset)| (name.to_string(), offset));
+        assert_eq!(members, expected);
+        assert!(matches!(body[4..6], [Stmt::Null, Stmt::Null]));
+        let Stmt::FuncDef { body, .. } = &body[6] else { panic!("Expected function") };
+        assert!(matches!(&body[..2], [Stmt::Declaration { ty: Type::Long, .. }, Stmt::Declaration { ty: Type::Long, init: Some(_), .. }]));
+        let Stmt::For { init, .. } = &body[2] else { panic!("Expected for") };
+        assert!(matches!(init[..], [Stmt::Declaration { .. }, Stmt::Declaration { .. }]));
+        // A function definition can't share its declaration
+        assert!(matches!(parse("int x, f(void){return 0;}"), Err(ParseError::ExpectedSemicolon)));
+        assert!(matches!(parse("int i, ;"), Err(ParseError::ExpectedIdentifier)));
+        assert!(matches!(parse("int main(void){for (int i = 0, f(void); ;) ; return 0;}"), Err(ParseError::InvalidForInit)));
+        assert!(matches!(parse("struct s {int a, a;};"), Err(ParseError::DuplicateMember)));
+    }
+
+    #[test]
+    fn test_function_pointers() {
+        let Stmt::Program { body } = parse("int (*f)(int a); int (*t[2])(void); void g(int h(long), char *); int (*pick(int i))(int);\n\
+                                            int main(void){ return (*t[1])() + f(2)(3); }").unwrap() else { panic!("Expected program") };
+        let types: Vec<String> = body[..4].iter().map(|item| match item {
+            Stmt::Declaration { ty, .. } | Stmt::FuncDecl { ty, .. } => ty.to_string(),
+            _ => panic!("Expected declaration"),
+        }).collect();
+        // A parameter of function type is a pointer to the function
+        assert_eq!(types, ["int (*)(int)", "int (*[2])(void)", "void (int (*)(long), char *)", "int (*(int))(int)"]);
+        // Unnamed parameters are allowed in a declaration, and a function returning a pointer to
+        // a function is named by its own parameters
+        assert!(matches!(&body[2], Stmt::FuncDecl { params, .. } if params == &["h", ""]));
+        assert!(matches!(&body[3], Stmt::FuncDecl { params, .. } if params == &["i"]));
+        // Any postfix expression can be called
+        let Stmt::FuncDef { body, .. } = &body[4] else { panic!("Expected function") };
+        let Stmt::Return { value: Some(value) } = &body[0] else { panic!("Expected return") };
+        let ExprKind::Binary { left, right, .. } = &value.kind else { panic!("Expected addition") };
+        assert!(matches!(&left.kind, ExprKind::FunctionCall { function, .. } if matches!(function.kind, ExprKind::Dereference { .. })));
+        assert!(matches!(&right.kind, ExprKind::FunctionCall { function, .. } if matches!(function.kind, ExprKind::FunctionCall { .. })));
+        assert!(matches!(parse("int f(int)(int);"), Err(ParseError::InvalidType)));
+        assert!(matches!(parse("int f(int){return 0;}"), Err(ParseError::ExpectedIdentifier)));
+    }
+
+    #[test]
+    fn test_variadic_builtins() {
+        let Stmt::Program { body } = parse("typedef __builtin_va_list va_list;\n\
+                                            int f(int n, ...){va_list ap, aq; __builtin_va_start(ap, n); __builtin_va_copy(aq, ap);\n\
+                                            long x = __builtin_va_arg(ap, long); __builtin_va_end(ap); return x;}").unwrap() else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = &body[1] else { panic!("Expected function") };
+        // va_list is an array of one predeclared structure
+        let Stmt::Declaration { ty, .. } = &body[0] else { panic!("Expected declaration") };
+        assert_eq!(ty.to_string(), "struct __va_list_tag[1]");
+        let Stmt::Expression { expr } = &body[2] else { panic!("Expected expression") };
+        assert!(matches!(&expr.kind, ExprKind::VaStart { ap } if matches!(ap.kind, ExprKind::Identifier { .. })));
+        let Stmt::Expression { expr } = &body[3] else { panic!("Expected expression") };
+        assert!(matches!(expr.kind, ExprKind::VaCopy { .. }));
+        let Stmt::Declaration { init: Some(Initializer::Single(init)), .. } = &body[4] else { panic!("Expected declaration") };
+        assert!(matches!(&init.kind, ExprKind::VaArg { ty: Type::Long, .. }));
+        assert!(matches!(parse("int f(int n, ...){__builtin_va_list ap; __builtin_va_start(ap); return 0;}"), Err(ParseError::ExpectedComma)));
+    }
+
+    #[test]
+    fn test_arrays() {
+        let Stmt::Program { body } = parse("int a[3]; long b[2][1 + 4]; int *(*c)[3]; int f(int d[4], char e[2][3]);\n\
+                                            int main(void){ return a[1] + 2[a] + b[0][1]; }").unwrap() else { panic!("Expected program") };
+        let Stmt::Declaration { ty, .. } = &body[0] else { panic!("Expected declaration") };
+        assert_eq!(ty.to_string(), "int[3]");
+        let Stmt::Declaration { ty, .. } = &body[1] else { panic!("Expected declaration") };
+        assert_eq!(ty.to_string(), "long[2][5]");
+        assert_eq!(ty.size(), 80);
+        let Stmt::Declaration { ty, .. } = &body[2] else { panic!("Expected declaration") };
+        assert_eq!(ty.to_string(), "int *(*)[3]");
+        // Array parameters are adjusted to pointers
+        let Stmt::FuncDecl { ty, .. } = &body[3] else { panic!("Expected function") };
+        assert_eq!(ty.to_string(), "int (int *, char (*)[3])");
+        let Stmt::FuncDef { body, .. } = &body[4] else { panic!("Expected function") };
+        let Stmt::Return { value: Some(value) } = &body[0] else { panic!("Expected return") };
+        let ExprKind::Binary { right, .. } = &value.kind else { panic!("Expected binary") };
+        let ExprKind::Subscript { array, .. } = &right.kind else { panic!("Expected subscript") };
+        assert!(matches!(array.kind, ExprKind::Subscript { .. }));
+        assert!(matches!(parse("int a[0];"), Err(ParseError::InvalidArraySize)));
+        assert!(matches!(parse("int a[1.5];"), Err(ParseError::InvalidArraySize)));
+        assert!(matches!(parse("int f(void)[3];"), Err(ParseError::InvalidType)));
+        assert!(matches!(parse("int main(void){int a[2]; return a[1;}"), Err(ParseError::ExpectedRightBracket)));
+    }
+
+    #[test]
+    fn test_unsized_arrays() {
+        let Stmt::Program { body } = parse("extern int a[]; long (*p)[]; extern char b[][4]; int main(int argc, char *argv[]); void f(int m[][3]);").unwrap() else {
+            panic!("Expected program");
+        };
+        // Only the outermost array can leave out its size, making it incomplete
+        let types: Vec<String> = body.iter().map(|item| match item {
+            Stmt::Declaration { ty, .. } | Stmt::FuncDecl { ty, .. } => ty.to_string(),
+            _ => panic!("Expected declaration"),
+        }).collect();
+        assert_eq!(types, ["int[]", "long (*)[]", "char[][4]", "int (int, char **)", "void (int (*)[3])"]);
+        let Stmt::Declaration { ty, .. } = &body[0] else { panic!("Expected declaration") };
+        assert!(!ty.is_complete());
+        let Stmt::Declaration { ty, .. } = &body[2] else { panic!("Expected declaration") };
+        assert!(matches!(ty, Type::Array { element, size: None } if element.is_complete()));
+        assert!(matches!(parse("int a[3][];"), Err(ParseError::InvalidType)));
+        assert!(matches!(parse("int a[;"), Err(ParseError::UnexpectedToken)));
+    }
+
+    #[test]
+    fn test_initializers() {
+        let Stmt::Program { body } = parse("enum e {TWO = 2}; struct s {int a[3]; int b;};\n\
+                                            struct s x = {{1, 2,}, .a[TWO] = 3, .b = -1,}; int y = {};").unwrap() else { panic!("Expected program") };
+        let Stmt::Declaration { init: Some(Initializer::Compound { items, .. }), .. } = &body[2] else { panic!("Expected list") };
+        assert_eq!(items.len(), 3);
+        let (designators, Initializer::Compound { items: inner, .. }) = &items[0] else { panic!("Expected nested list") };
+        assert!(designators.is_empty());
+        assert_eq!(inner.len(), 2);
+        // Designators are a path into the object, with constant indexes
+        assert_eq!(items[1].0, vec![Designator::Member("a".to_string()), Designator::Index(2)]);
+        assert!(matches!(&items[1].1, Initializer::Single(expr) if matches!(expr.kind, ExprKind::Constant { value: Const::Int(3) })));
+        assert_eq!(items[2].0, vec![Designator::Member("b".to_string())]);
+        let Stmt::Declaration { init: Some(Initializer::Compound { items, .. }), .. } = &body[3] else { panic!("Expected list") };
+        assert!(items.is_empty());
+        assert!(matches!(parse("int a[2] = {[0] 1};"), Err(ParseError::ExpectedEqual)));
+        assert!(matches!(parse("int a[2] = {[-1] = 1};"), Err(ParseError::InvalidDesignator)));
+        assert!(matches!(parse("int a[2] = {[0.5] = 1};"), Err(ParseError::InvalidDesignator)));
+        assert!(matches!(parse("int a[2] = {1, 2;"), Err(ParseError::UnmatchedBrace)));
+        assert!(matches!(parse("int a[2] = {1 2};"), Err(ParseError::UnmatchedBrace)));
+        assert!(matches!(parse("int a[2] = {, 1};"), Err(ParseError::UnexpectedToken)));
+    }
+
+    #[test]
+    fn test_floating() {
+        for (source, expected) in [
+            ("1.5", Const::Double(1.5)),
+            (".25", Const::Double(0.25)),
+            ("1e3", Const::Double(1000.0)),
+            ("2.5f", Const::Float(2.5)),
+            ("1E-2F", Const::Float(0.01)),
+        ] {
+            let Stmt::Program { body } = parse(&format!("double main(void){{return {};}}", source)).unwrap() else { panic!("Expected program") };
+            let Stmt::FuncDef { ty, body, .. } = &body[0] else { panic!("Expected function") };
+            assert_eq!(ty.to_string(), "double (void)");
+            assert!(matches!(&body[0], Stmt::Return { value: Some(value) } if matches!(value.kind, ExprKind::Constant { value } if value == expected)));
+        }
+        for source in ["long double a;", "float int a;", "unsigned double a;"] {
+            assert!(matches!(parse(source), Err(ParseError::InvalidType)));
+        }
+    }
 }
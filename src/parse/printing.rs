@@ -1,4 +1,4 @@
-use super::c_ast::{Stmt, Expr};
+use super::c_ast::{Stmt, Expr, ExprKind, Type, Initializer, Designator};
 
 
 pub struct Printer {
@@ -31,11 +31,13 @@ impl Printer {
             Stmt::Program { body } => {
                 self.output.push_str("Program(\n");
                 self.current_level +=1;
-                self.stmt_to_string(body);
+                for item in body {
+                    self.stmt_to_string(item);
+                }
                 self.current_level-=1;
-                self.output.push_str(")");
+                self.output.push(')');
             }
-            Stmt::FuncDef { name, body } => {
+            Stmt::FuncDef { name, params, ty, storage, body } => {
                 self.output.push_str("Function(\n");
                 self.current_level +=1;
                 self.get_to_level();
@@ -43,9 +45,21 @@ impl Printer {
                 self.expr_to_string(name);
                 self.output.push_str(",\n");
                 self.get_to_level();
+                self.output.push_str(&format!("type={},\n", ty));
+                if !params.is_empty() {
+                    self.get_to_level();
+                    self.output.push_str(&format!("params={:?},\n", params));
+                }
+                if let Some(storage) = storage {
+                    self.get_to_level();
+                    self.output.push_str(&format!("storage={:?},\n", storage));
+                }
+                self.get_to_level();
                 self.output.push_str("body={\n");
                 self.current_level+=1;
-                self.stmt_to_string(body);
+                for item in body {
+                    self.stmt_to_string(item);
+                }
                 self.current_level-=1;
                 self.get_to_level();
                 self.output.push_str("}\n");
@@ -53,34 +67,323 @@ impl Printer {
                 self.get_to_level();
                 self.output.push_str(")\n");
             }
-            Stmt::Return { value } => {
+            Stmt::FuncDecl { name, params, ty, storage } => {
+                self.output.push_str("FunctionDecl(");
+                self.expr_to_string(name);
+                self.output.push_str(&format!(", type={}, params={:?}", ty, params));
+                if let Some(storage) = storage {
+                    self.output.push_str(&format!(", storage={:?}", storage));
+                }
+                self.output.push_str(")\n");
+            }
+            Stmt::Return { value: None } => self.output.push_str("Return()\n"),
+            Stmt::Return { value: Some(value) } => {
                 self.output.push_str("Return(\n");
                 self.current_level += 1;
                 self.get_to_level();
                 self.expr_to_string(value);
-                self.output.push_str("\n");
+                self.output.push('\n');
                 self.current_level-=1;
                 self.get_to_level();
                 self.output.push_str(")\n");
             }
+            Stmt::Declaration { name, ty, init, storage, .. } => {
+                self.output.push_str("Declaration(");
+                if let Some(storage) = storage {
+                    self.output.push_str(&format!("{:?} ", storage));
+                }
+                self.output.push_str(&format!("{} \"{}\"", ty, name));
+                if let Some(init) = init {
+                    self.output.push_str(" = ");
+                    self.initializer_to_string(init);
+                }
+                self.output.push_str(")\n");
+            }
+            Stmt::StructDecl { ty: ty @ Type::Struct(struct_type), .. } => {
+                self.output.push_str(&format!("StructDecl({}", ty));
+                if let Some(definition) = &*struct_type.definition.borrow() {
+                    for member in &definition.members {
+                        self.output.push_str(&format!(", {} \"{}\" @{}", member.ty, member.name, member.offset));
+                        if let Some(bits) = member.bits {
+                            self.output.push_str(&format!(" bits {}:{}", bits.offset, bits.width));
+                        }
+                    }
+                }
+                self.output.push_str(")\n");
+            }
+            Stmt::StructDecl { ty, .. } => self.output.push_str(&format!("StructDecl({})\n", ty)),
+            Stmt::Expression { expr } => {
+                self.output.push_str("Expression(");
+                self.expr_to_string(expr);
+                self.output.push_str(")\n");
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.output.push_str("If(");
+                self.expr_to_string(condition);
+                self.output.push_str(",\n");
+                self.current_level += 1;
+                self.stmt_to_string(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt_to_string(else_branch);
+                }
+                self.current_level -= 1;
+                self.get_to_level();
+                self.output.push_str(")\n");
+            }
+            Stmt::Compound { body } => {
+                self.output.push_str("Block(\n");
+                self.current_level += 1;
+                for item in body {
+                    self.stmt_to_string(item);
+                }
+                self.current_level -= 1;
+                self.get_to_level();
+                self.output.push_str(")\n");
+            }
+            Stmt::While { condition, body, .. } => {
+                self.output.push_str("While(");
+                self.expr_to_string(condition);
+                self.output.push_str(",\n");
+                self.current_level += 1;
+                self.stmt_to_string(body);
+                self.current_level -= 1;
+                self.get_to_level();
+                self.output.push_str(")\n");
+            }
+            Stmt::DoWhile { body, condition, .. } => {
+                self.output.push_str("DoWhile(\n");
+                self.current_level += 1;
+                self.stmt_to_string(body);
+                self.get_to_level();
+                self.expr_to_string(condition);
+                self.output.push('\n');
+                self.current_level -= 1;
+                self.get_to_level();
+                self.output.push_str(")\n");
+            }
+            Stmt::For { init, condition, post, body, .. } => {
+                self.output.push_str("For(\n");
+                self.current_level += 1;
+                if init.is_empty() {
+                    self.get_to_level();
+                    self.output.push_str("Null\n");
+                }
+                for init in init {
+                    self.stmt_to_string(init);
+                }
+                for clause in [condition, post] {
+                    self.get_to_level();
+                    match clause {
+                        Some(clause) => self.expr_to_string(clause),
+                        None => self.output.push_str("Null"),
+                    }
+                    self.output.push('\n');
+                }
+                self.stmt_to_string(body);
+                self.current_level -= 1;
+                self.get_to_level();
+                self.output.push_str(")\n");
+            }
+            Stmt::Switch { condition, body, .. } => {
+                self.output.push_str("Switch(");
+                self.expr_to_string(condition);
+                self.output.push_str(",\n");
+                self.current_level += 1;
+                self.stmt_to_string(body);
+                self.current_level -= 1;
+                self.get_to_level();
+                self.output.push_str(")\n");
+            }
+            Stmt::Case { value, body, .. } => {
+                self.output.push_str("Case(");
+                self.expr_to_string(value);
+                self.output.push_str(",\n");
+                self.current_level += 1;
+                self.stmt_to_string(body);
+                self.current_level -= 1;
+                self.get_to_level();
+                self.output.push_str(")\n");
+            }
+            Stmt::Default { body, .. } => {
+                self.output.push_str("Default(\n");
+                self.current_level += 1;
+                self.stmt_to_string(body);
+                self.current_level -= 1;
+                self.get_to_level();
+                self.output.push_str(")\n");
+            }
+            Stmt::Labeled { label, body, .. } => {
+                self.output.push_str(&format!("Label(\"{}\",\n", label));
+                self.current_level += 1;
+                self.stmt_to_string(body);
+                self.current_level -= 1;
+                self.get_to_level();
+                self.output.push_str(")\n");
+            }
+            Stmt::Goto { label, .. } => {
+                self.output.push_str(&format!("Goto(\"{}\")\n", label));
+            }
+            Stmt::Break { .. } => {
+                self.output.push_str("Break\n");
+            }
+            Stmt::Continue { .. } => {
+                self.output.push_str("Continue\n");
+            }
+            Stmt::Null => {
+                self.output.push_str("Null\n");
+            }
         }
     }
     
     fn expr_to_string(&mut self, expr: &Expr) {
-        match expr {
-            Expr::IntConstant { value } => {
+        match &expr.kind {
+            ExprKind::Constant { value } => {
                 self.output.push_str("Constant(");
                 self.output.push_str(&value.to_string());
                 self.output.push(')');
             }
-            Expr::Identifier { value } => {
+            ExprKind::String { value } => {
+                self.output.push_str(&format!("String({:?})", String::from_utf8_lossy(value)));
+            }
+            ExprKind::Cast { target, expr } => {
+                self.output.push_str(&format!("Cast({}, ", target));
+                self.expr_to_string(expr);
+                self.output.push(')');
+            }
+            ExprKind::Identifier { value } => {
                 self.output.push('"');
                 self.output.push_str(&value.to_string());
                 self.output.push('"');
             }
+            ExprKind::Unary { op, expr } => {
+                self.output.push_str(&format!("Unary({:?}, ", op));
+                self.expr_to_string(expr);
+                self.output.push(')');
+            }
+            ExprKind::Binary { op, left, right } => {
+                self.output.push_str(&format!("Binary({:?}, ", op));
+                self.expr_to_string(left);
+                self.output.push_str(", ");
+                self.expr_to_string(right);
+                self.output.push(')');
+            }
+            ExprKind::CompoundAssignment { op, left, right } => {
+                self.output.push_str(&format!("CompoundAssign({:?}, ", op));
+                self.expr_to_string(left);
+                self.output.push_str(", ");
+                self.expr_to_string(right);
+                self.output.push(')');
+            }
+            ExprKind::Postfix { op, expr } => {
+                self.output.push_str(&format!("Postfix({:?}, ", op));
+                self.expr_to_string(expr);
+                self.output.push(')');
+            }
+            ExprKind::AddressOf { expr } => {
+                self.output.push_str("AddressOf(");
+                self.expr_to_string(expr);
+                self.output.push(')');
+            }
+            ExprKind::Dereference { expr } => {
+                self.output.push_str("Dereference(");
+                self.expr_to_string(expr);
+                self.output.push(')');
+            }
+            ExprKind::Subscript { array, index } => {
+                self.output.push_str("Subscript(");
+                self.expr_to_string(array);
+                self.output.push_str(", ");
+                self.expr_to_string(index);
+                self.output.push(')');
+            }
+            ExprKind::SizeOf { expr } => {
+                self.output.push_str("SizeOf(");
+                self.expr_to_string(expr);
+                self.output.push(')');
+            }
+            ExprKind::Member { expr, member } => {
+                self.output.push_str("Member(");
+                self.expr_to_string(expr);
+                self.output.push_str(&format!(", \"{}\")", member));
+            }
+            ExprKind::VaStart { ap } => {
+                self.output.push_str("VaStart(");
+                self.expr_to_string(ap);
+                self.output.push(')');
+            }
+            ExprKind::VaEnd { ap } => {
+                self.output.push_str("VaEnd(");
+                self.expr_to_string(ap);
+                self.output.push(')');
+            }
+            ExprKind::VaArg { ap, ty } => {
+                self.output.push_str("VaArg(");
+                self.expr_to_string(ap);
+                self.output.push_str(&format!(", {})", ty));
+            }
+            ExprKind::VaCopy { dst, src } => {
+                self.output.push_str("VaCopy(");
+                self.expr_to_string(dst);
+                self.output.push_str(", ");
+                self.expr_to_string(src);
+                self.output.push(')');
+            }
+            ExprKind::SizeOfType { ty } => self.output.push_str(&format!("SizeOf({})", ty)),
+            ExprKind::AlignOf { ty } => self.output.push_str(&format!("AlignOf({})", ty)),
+            ExprKind::Conditional { condition, then_expr, else_expr } => {
+                self.output.push_str("Conditional(");
+                self.expr_to_string(condition);
+                self.output.push_str(", ");
+                self.expr_to_string(then_expr);
+                self.output.push_str(", ");
+                self.expr_to_string(else_expr);
+                self.output.push(')');
+            }
+            ExprKind::FunctionCall { function, args } => {
+                self.output.push_str("Call(");
+                self.expr_to_string(function);
+                for arg in args {
+                    self.output.push_str(", ");
+                    self.expr_to_string(arg);
+                }
+                self.output.push(')');
+            }
+            ExprKind::Assignment { left, right } => {
+                self.output.push_str("Assign(");
+                self.expr_to_string(left);
+                self.output.push_str(", ");
+                self.expr_to_string(right);
+                self.output.push(')');
+            }
         }
     }
     
+    /// Brace enclosed lists are printed on one line, with the designators of each initializer
+    fn initializer_to_string(&mut self, init: &Initializer) {
+        match init {
+            Initializer::Single(expr) => self.expr_to_string(expr),
+            Initializer::Compound { items, .. } => {
+                self.output.push('{');
+                for (i, (designators, init)) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    for designator in designators {
+                        match designator {
+                            Designator::Member(member) => self.output.push_str(&format!(".{}", member)),
+                            Designator::Index(index) => self.output.push_str(&format!("[{}]", index)),
+                        }
+                    }
+                    if !designators.is_empty() {
+                        self.output.push_str(" = ");
+                    }
+                    self.initializer_to_string(init);
+                }
+                self.output.push('}');
+            }
+        }
+    }
+
     fn get_to_level(&mut self){
         for _ in 0..self.current_level {
             self.output.push_str("    ");
@@ -96,6 +399,7 @@ impl Printer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parse::c_ast::{BinaryOp, UnaryOp, Const};
     use crate::lex;
     use crate::parse;
     #[test]
@@ -103,13 +407,24 @@ mod tests {
         // Create Printer object
         let mut printer = Printer::new();
         // Test Constant Int Printer
-        printer.expr_to_string(&Expr::IntConstant { value: 1 });
+        printer.expr_to_string(&Expr::new(ExprKind::Constant { value: Const::Int(1) }, 1));
         assert_eq!(printer.output, "Constant(1)");
         printer.clear();
         // Test Identifier Printer
-        printer.expr_to_string(&Expr::Identifier { value: "main".to_string() });
+        printer.expr_to_string(&Expr::new(ExprKind::Identifier { value: "main".to_string() }, 1));
         assert_eq!(printer.output, "\"main\"");
         printer.clear();
+        // Test Operator Printer
+        let one = Expr::new(ExprKind::Constant { value: Const::Int(1) }, 1);
+        let two = Expr::new(ExprKind::Constant { value: Const::Int(2) }, 1);
+        let complement = Expr::new(ExprKind::Unary { op: UnaryOp::Complement, expr: Box::new(one) }, 1);
+        printer.expr_to_string(&Expr::new(ExprKind::Binary {
+            op: BinaryOp::ShiftLeft,
+            left: Box::new(complement),
+            right: Box::new(two),
+        }, 1));
+        assert_eq!(printer.output, "Binary(ShiftLeft, Unary(Complement, Constant(1)), Constant(2))");
+        printer.clear();
     }
     
     #[test]
@@ -126,6 +441,7 @@ mod tests {
 "Program(
     Function(
         name=\"main\",
+        type=int (void),
         body={
             Return(
                 Constant(2)
@@ -135,4 +451,17 @@ mod tests {
 )");
         
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_initializer_to_string() {
+        let mut lexer = lex::Lexer::new(String::from("int a[3][2] = {[1] = {1, 2}, 3, [2][1] = 4,};"));
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = parse::parsing::Parser::new(tokens);
+        let ast = parser.parse().unwrap();
+        let mut printer = Printer::new();
+        printer.stmt_to_string(&ast);
+        assert_eq!(printer.output, "Program(
+    Declaration(int[3][2] \"a\" = {[1] = {Constant(1), Constant(2)}, Constant(3), [2][1] = Constant(4)})
+)");
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+use std::ops::{Add, Sub, Mul, Div};
+use crate::parse::c_ast::{Expr, ExprKind, UnaryOp, BinaryOp, Const, Type};
+
+/// Evaluate an integer constant expression, returning None if the expression isn't constant or
+/// its value is undefined (such as dividing by zero). The expression doesn't need to have been
+/// type checked, the type of the result follows the usual arithmetic conversions.
+pub fn evaluate_constant(expr: &Expr) -> Option<Const> {
+    let value = match &expr.kind {
+        ExprKind::Constant { value } => *value,
+        ExprKind::Cast { target, expr } => evaluate_constant(expr)?.convert(target),
+        // The size of an expression's type is only known once it has been type checked
+        ExprKind::SizeOfType { ty } if ty.is_complete() => Const::ULong(ty.size() as u64),
+        ExprKind::AlignOf { ty } if ty.is_complete() => Const::ULong(ty.alignment() as u64),
+        ExprKind::SizeOf { .. } | ExprKind::SizeOfType { .. } | ExprKind::AlignOf { .. } => return None,
+        ExprKind::Unary { op: UnaryOp::Not, expr } => Const::Int(evaluate_constant(expr)?.is_zero() as i32),
+        ExprKind::Unary { op, expr } => {
+            match (op, evaluate_constant(expr)?) {
+                (UnaryOp::Negate, Const::Float(value)) => Const::Float(-value),
+                (UnaryOp::Negate, Const::Double(value)) => Const::Double(-value),
+                (UnaryOp::Complement, Const::Float(_) | Const::Double(_)) => return None,
+                (UnaryOp::Negate, value) => Const::from_bits(&value.ty(), value.bits().wrapping_neg()),
+                (UnaryOp::Complement, value) => Const::from_bits(&value.ty(), !value.bits()),
+                (UnaryOp::Not, _) => unreachable!("logical not is handled above"),
+            }
+        }
+        ExprKind::Binary { op: BinaryOp::And, left, right } => {
+            // Only the operands which would be evaluated need to be constant
+            Const::Int((!evaluate_constant(left)?.is_zero() && !evaluate_constant(right)?.is_zero()) as i32)
+        }
+        ExprKind::Binary { op: BinaryOp::Or, left, right } => {
+            Const::Int((!evaluate_constant(left)?.is_zero() || !evaluate_constant(right)?.is_zero()) as i32)
+        }
+        ExprKind::Binary { op: op @ (BinaryOp::ShiftLeft | BinaryOp::ShiftRight), left, right } => {
+            // The result has the type of the left operand, whatever the type of the count
+            let left = evaluate_constant(left)?;
+            let count = evaluate_constant(right)?;
+            let ty = left.ty();
+            if ty.is_floating() || count.ty().is_floating() {
+                return None;
+            }
+            let count = count.bits();
+            if count < 0 {
+                return None;
+            }
+            // As in gcc, shifting out every bit gives 0, though the behavior is undefined and is
+            // warned about
+            let bits = match op {
+                _ if count >= (ty.size() * 8) as i64 => 0,
+                BinaryOp::ShiftLeft => left.bits() << count,
+                // Bits are sign or zero extended already, so an arithmetic shift suits both
+                _ => left.bits() >> count,
+            };
+            Const::from_bits(&ty, bits)
+        }
+        ExprKind::Binary { op, left, right } => {
+            let left = evaluate_constant(left)?;
+            let right = evaluate_constant(right)?;
+            let ty = Type::common(&left.ty(), &right.ty());
+            match (left.convert(&ty), right.convert(&ty)) {
+                (Const::Double(left), Const::Double(right)) => floating_binary(*op, left, right, Const::Double)?,
+                (Const::Float(left), Const::Float(right)) => floating_binary(*op, left, right, Const::Float)?,
+                (left, right) => binary(*op, &ty, left.bits(), right.bits())?,
+            }
+        }
+        ExprKind::Conditional { condition, then_expr, else_expr } => {
+            let then_value = evaluate_constant(then_expr);
+            let else_value = evaluate_constant(else_expr);
+            // The result has the common type of both branches, even though only one is evaluated
+            let ty = match (then_value, else_value) {
+                (Some(then_value), Some(else_value)) => Type::common(&then_value.ty(), &else_value.ty()),
+                (Some(value), None) | (None, Some(value)) => value.ty(),
+                (None, None) => return None,
+            };
+            let value = if !evaluate_constant(condition)?.is_zero() {then_value} else {else_value};
+            value?.convert(&ty)
+        }
+        // Anything reading or writing an object, or calling a function, isn't constant
+        ExprKind::Identifier { .. } | ExprKind::String { .. } | ExprKind::Assignment { .. } | ExprKind::CompoundAssignment { .. }
+        | ExprKind::Postfix { .. } | ExprKind::FunctionCall { .. } | ExprKind::AddressOf { .. }
+        | ExprKind::Dereference { .. } | ExprKind::Subscript { .. } | ExprKind::Member { .. } | ExprKind::VaStart { .. }
+        | ExprKind::VaArg { .. } | ExprKind::VaEnd { .. } | ExprKind::VaCopy { .. } => return None,
+    };
+    Some(value)
+}
+
+/// Apply a binary operator to floating operands of the same type, comparisons follow IEEE 754 so
+/// every comparison with a NaN is false other than !=
+fn floating_binary<F>(op: BinaryOp, left: F, right: F, make: fn(F) -> Const) -> Option<Const>
+where F: PartialOrd + Add<Output = F> + Sub<Output = F> + Mul<Output = F> + Div<Output = F> {
+    let comparison = |result: bool| Some(Const::Int(result as i32));
+    match op {
+        BinaryOp::Add => Some(make(left + right)),
+        BinaryOp::Subtract => Some(make(left - right)),
+        BinaryOp::Multiply => Some(make(left * right)),
+        BinaryOp::Divide => Some(make(left / right)),
+        BinaryOp::Equal => comparison(left == right),
+        BinaryOp::NotEqual => comparison(left != right),
+        BinaryOp::LessThan => comparison(left < right),
+        BinaryOp::LessOrEqual => comparison(left <= right),
+        BinaryOp::GreaterThan => comparison(left > right),
+        BinaryOp::GreaterOrEqual => comparison(left >= right),
+        // Remainder and bitwise operators don't apply to floating operands
+        _ => None,
+    }
+}
+
+/// Apply a binary operator to integer operands already converted to their common type
+fn binary(op: BinaryOp, ty: &Type, left: i64, right: i64) -> Option<Const> {
+    let signed = ty.is_signed();
+    let ordering = if signed {left.cmp(&right)} else {(left as u64).cmp(&(right as u64))};
+    let bits = match op {
+        BinaryOp::Add => left.wrapping_add(right),
+        BinaryOp::Subtract => left.wrapping_sub(right),
+        BinaryOp::Multiply => left.wrapping_mul(right),
+        BinaryOp::Divide | BinaryOp::Remainder => {
+            let bits = match (op, signed) {
+                (BinaryOp::Divide, true) => left.checked_div(right)?,
+                (BinaryOp::Divide, false) => (left as u64).checked_div(right as u64)? as i64,
+                (_, true) => left.checked_rem(right)?,
+                (_, false) => (left as u64).checked_rem(right as u64)? as i64,
+            };
+            // Dividing the most negative value by -1 overflows the type, which is undefined
+            if ty.convert_bits(bits) != bits {
+                return None;
+            }
+            bits
+        }
+        BinaryOp::BitwiseAnd => left & right,
+        BinaryOp::BitwiseOr => left | right,
+        BinaryOp::BitwiseXor => left ^ right,
+        BinaryOp::Equal => return Some(Const::Int(ordering.is_eq() as i32)),
+        BinaryOp::NotEqual => return Some(Const::Int(ordering.is_ne() as i32)),
+        BinaryOp::LessThan => return Some(Const::Int(ordering.is_lt() as i32)),
+        BinaryOp::LessOrEqual => return Some(Const::Int(ordering.is_le() as i32)),
+        BinaryOp::GreaterThan => return Some(Const::Int(ordering.is_gt() as i32)),
+        BinaryOp::GreaterOrEqual => return Some(Const::Int(ordering.is_ge() as i32)),
+        BinaryOp::And | BinaryOp::Or | BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+            unreachable!("logical operators and shifts are handled by the caller")
+        }
+    };
+    Some(Const::from_bits(ty, bits))
+}
+
+/// Warn about a shift of a value of type ty by a count which is known to be negative, or not less
+/// than the width of the type, since the result of such a shift is undefined
+pub fn check_shift_count(op: BinaryOp, count: &Expr, ty: &Type) -> Option<Warning> {
+    let value = evaluate_constant(count)?;
+    let left = op == BinaryOp::ShiftLeft;
+    // Negative counts are only possible if the count has a signed type
+    if value.ty().is_signed() && value.bits() < 0 {
+        Some(Warning::NegativeShiftCount { left, line: count.line })
+    } else if value.bits() as u64 >= 8 * ty.size() as u64 {
+        Some(Warning::ShiftCountTooLarge { left, line: count.line })
+    } else {
+        None
+    }
+}
+
+/// Warnings about the shifts within a constant expression which hasn't been type checked, where
+/// the shifted value is the promoted type of its constant value
+pub fn constant_warnings(expr: &Expr, warnings: &mut Vec<Warning>) {
+    match &expr.kind {
+        ExprKind::Binary { op, left, right } => {
+            if let (BinaryOp::ShiftLeft | BinaryOp::ShiftRight, Some(value)) = (op, evaluate_constant(left)) {
+                warnings.extend(check_shift_count(*op, right, &value.ty().promote()));
+            }
+            constant_warnings(left, warnings);
+            constant_warnings(right, warnings);
+        }
+        ExprKind::Cast { expr, .. } | ExprKind::Unary { expr, .. } => constant_warnings(expr, warnings),
+        ExprKind::Conditional { condition, then_expr, else_expr } => {
+            for expr in [condition, then_expr, else_expr] {
+                constant_warnings(expr, warnings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Something the program does which is allowed, but probably a mistake
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    NegativeShiftCount{left: bool, line: usize},
+    ShiftCountTooLarge{left: bool, line: usize},
+}
+
+impl Warning {
+    /// Line of the source the warning is about
+    pub fn line(&self) -> usize {
+        match self {
+            Warning::NegativeShiftCount { line, .. } | Warning::ShiftCountTooLarge { line, .. } => *line,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::NegativeShiftCount { left, .. } => write!(f, "{} shift count is negative", if *left {"left"} else {"right"}),
+            Warning::ShiftCountTooLarge { left, .. } => write!(f, "{} shift count >= width of type", if *left {"left"} else {"right"}),
+        }
+    }
+}
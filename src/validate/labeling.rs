@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::parse::c_ast::Stmt;
+use super::evaluating::evaluate_constant;
+
+/// Gives every loop and switch a unique label, and attaches to each break and continue the label
+/// of the statement it applies to. Each switch also collects the cases in its body. Labels which
+/// are the target of a goto are renamed so they are unique across functions.
+pub struct Labeler {
+    /// Loops and switches enclosing the current statement, innermost last
+    enclosing: Vec<Enclosing>,
+    /// Cases found so far in each enclosing switch, innermost last
+    switches: Vec<SwitchCases>,
+    /// Unique name of each goto label in the current function, keyed by its name in the source
+    labels: HashMap<String, String>,
+    /// Counter used to generate unique labels
+    counter: usize,
+}
+
+/// Statements which a break can jump out of
+enum Enclosing {
+    Loop(String),
+    Switch(String),
+}
+
+#[derive(Default)]
+struct SwitchCases {
+    /// Value and label of each case
+    cases: Vec<(i64, String)>,
+    /// Label of the default case
+    default: Option<String>,
+}
+
+impl Labeler {
+    pub fn new() -> Labeler {
+        Labeler { enclosing: Vec::new(), switches: Vec::new(), labels: HashMap::new(), counter: 0 }
+    }
+
+    fn make_label(&mut self, prefix: &str) -> String {
+        let label = format!("{}.{}", prefix, self.counter);
+        self.counter += 1;
+        label
+    }
+
+    pub fn label(&mut self, stmt: &mut Stmt) -> Result<(), LabelError> {
+        self.label_stmt(stmt)
+    }
+
+    fn label_stmt(&mut self, stmt: &mut Stmt) -> Result<(), LabelError> {
+        match stmt {
+            Stmt::Program { body } => body.iter_mut().try_for_each(|item| self.label_stmt(item)),
+            Stmt::FuncDef { body, .. } => {
+                // Labels have function scope, so a goto can jump forward to a label not yet seen
+                self.labels.clear();
+                body.iter_mut().try_for_each(|item| self.collect_labels(item))?;
+                body.iter_mut().try_for_each(|item| self.label_stmt(item))
+            }
+            Stmt::Compound { body } => {
+                body.iter_mut().try_for_each(|item| self.label_stmt(item))
+            }
+            Stmt::If { then_branch, else_branch, .. } => {
+                self.label_stmt(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.label_stmt(else_branch)?;
+                }
+                Ok(())
+            }
+            Stmt::While { body, label, .. } | Stmt::DoWhile { body, label, .. }
+            | Stmt::For { body, label, .. } => {
+                *label = self.make_label("loop");
+                self.enclosing.push(Enclosing::Loop(label.clone()));
+                let result = self.label_stmt(body);
+                self.enclosing.pop();
+                result
+            }
+            Stmt::Switch { body, label, cases, default, .. } => {
+                *label = self.make_label("switch");
+                self.enclosing.push(Enclosing::Switch(label.clone()));
+                self.switches.push(SwitchCases::default());
+                let result = self.label_stmt(body);
+                self.enclosing.pop();
+                let switch_cases = self.switches.pop().unwrap_or_default();
+                *cases = switch_cases.cases;
+                *default = switch_cases.default;
+                result
+            }
+            Stmt::Case { value, body, label } => {
+                let line = value.line;
+                // Case values must be integer constants
+                let case_value = evaluate_constant(value).filter(|value| !value.ty().is_floating());
+                let Some(case_value) = case_value.map(|value| value.bits()) else {
+                    return Err(LabelError::NonConstantCase { line });
+                };
+                *label = self.make_label("case");
+                let switch = self.switches.last_mut().ok_or(LabelError::CaseOutsideSwitch { line })?;
+                if switch.cases.iter().any(|(existing, _)| *existing == case_value) {
+                    return Err(LabelError::DuplicateCase { value: case_value, line });
+                }
+                switch.cases.push((case_value, label.clone()));
+                self.label_stmt(body)
+            }
+            Stmt::Default { body, label, line } => {
+                *label = self.make_label("default");
+                let switch = self.switches.last_mut().ok_or(LabelError::CaseOutsideSwitch { line: *line })?;
+                if switch.default.is_some() {
+                    return Err(LabelError::DuplicateDefault { line: *line });
+                }
+                switch.default = Some(label.clone());
+                self.label_stmt(body)
+            }
+            Stmt::Labeled { body, .. } => self.label_stmt(body),
+            Stmt::Goto { label, line } => match self.labels.get(label) {
+                Some(target) => {
+                    *label = target.clone();
+                    Ok(())
+                }
+                None => Err(LabelError::UndefinedLabel { name: label.clone(), line: *line }),
+            },
+            Stmt::Break { label, line } => match self.enclosing.last() {
+                Some(Enclosing::Loop(target) | Enclosing::Switch(target)) => {
+                    *label = target.clone();
+                    Ok(())
+                }
+                None => Err(LabelError::BreakOutsideLoop { line: *line }),
+            },
+            Stmt::Continue { label, line } => {
+                // Continue skips over any switches to the innermost loop
+                let target = self.enclosing.iter().rev().find_map(|enclosing| match enclosing {
+                    Enclosing::Loop(target) => Some(target),
+                    Enclosing::Switch(_) => None,
+                });
+                match target {
+                    Some(target) => {
+                        *label = target.clone();
+                        Ok(())
+                    }
+                    None => Err(LabelError::ContinueOutsideLoop { line: *line }),
+                }
+            }
+            Stmt::FuncDecl { .. } | Stmt::Declaration { .. } | Stmt::StructDecl { .. } | Stmt::Return { .. }
+            | Stmt::Expression { .. } | Stmt::Null => Ok(()),
+        }
+    }
+
+    /// Rename every labeled statement in a function body, recording the new names
+    fn collect_labels(&mut self, stmt: &mut Stmt) -> Result<(), LabelError> {
+        match stmt {
+            Stmt::Labeled { label, body, line } => {
+                if self.labels.contains_key(label) {
+                    return Err(LabelError::DuplicateLabel { name: label.clone(), line: *line });
+                }
+                let unique = self.make_label(&format!("label.{}", label));
+                self.labels.insert(label.clone(), unique.clone());
+                *label = unique;
+                self.collect_labels(body)
+            }
+            Stmt::Compound { body } => body.iter_mut().try_for_each(|item| self.collect_labels(item)),
+            Stmt::If { then_branch, else_branch, .. } => {
+                self.collect_labels(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.collect_labels(else_branch)?;
+                }
+                Ok(())
+            }
+            Stmt::While { body, .. } | Stmt::DoWhile { body, .. } | Stmt::For { body, .. }
+            | Stmt::Switch { body, .. } | Stmt::Case { body, .. } | Stmt::Default { body, .. } => {
+                self.collect_labels(body)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LabelError {
+    BreakOutsideLoop{line: usize},
+    ContinueOutsideLoop{line: usize},
+    CaseOutsideSwitch{line: usize},
+    NonConstantCase{line: usize},
+    DuplicateCase{value: i64, line: usize},
+    DuplicateDefault{line: usize},
+    DuplicateLabel{name: String, line: usize},
+    UndefinedLabel{name: String, line: usize},
+}
+
+impl LabelError {
+    /// Line of the source the error was found on
+    pub fn line(&self) -> usize {
+        match self {
+            LabelError::BreakOutsideLoop { line } | LabelError::ContinueOutsideLoop { line }
+            | LabelError::CaseOutsideSwitch { line } | LabelError::NonConstantCase { line }
+            | LabelError::DuplicateCase { line, .. } | LabelError::DuplicateDefault { line }
+            | LabelError::DuplicateLabel { line, .. } | LabelError::UndefinedLabel { line, .. } => *line,
+        }
+    }
+}
+
+impl fmt::Display for LabelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LabelError::BreakOutsideLoop { .. } => write!(f, "break statement not within a loop or switch"),
+            LabelError::ContinueOutsideLoop { .. } => write!(f, "continue statement not within a loop"),
+            LabelError::CaseOutsideSwitch { .. } => write!(f, "case label not within a switch statement"),
+            LabelError::NonConstantCase { .. } => write!(f, "case label does not reduce to an integer constant"),
+            LabelError::DuplicateCase { value, .. } => write!(f, "duplicate case value {}", value),
+            LabelError::DuplicateDefault { .. } => write!(f, "multiple default labels in one switch"),
+            LabelError::DuplicateLabel { name, .. } => write!(f, "duplicate label '{}'", name),
+            LabelError::UndefinedLabel { name, .. } => write!(f, "label '{}' used but not defined", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex;
+    use crate::parse::parsing::Parser;
+
+    fn label(source: &str) -> Result<Stmt, LabelError> {
+        let mut lexer = lex::Lexer::new(source.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let mut ast = Parser::new(tokens).parse().unwrap();
+        Labeler::new().label(&mut ast)?;
+        Ok(ast)
+    }
+
+    #[test]
+    fn test_nested_loops() {
+        let ast = label("int main(void){while (1) { for (;;) continue; break; } return 0;}").unwrap();
+        let Stmt::Program { body } = ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        let Stmt::While { body: outer_body, label: outer, .. } = &body[0] else { panic!("Expected while") };
+        let Stmt::Compound { body: items } = &**outer_body else { panic!("Expected block") };
+        let Stmt::For { body: inner_body, label: inner, .. } = &items[0] else { panic!("Expected for") };
+        assert_ne!(outer, inner);
+        // Each jump is bound to its innermost enclosing loop
+        assert!(matches!(&**inner_body, Stmt::Continue { label, .. } if label == inner));
+        assert!(matches!(&items[1], Stmt::Break { label, .. } if label == outer));
+    }
+
+    #[test]
+    fn test_outside_loop() {
+        let result = label("int main(void){\nif (1)\nbreak;\nreturn 0;}");
+        assert!(matches!(result, Err(LabelError::BreakOutsideLoop { line: 3 })));
+        let result = label("int main(void){ { continue; } return 0;}");
+        assert!(matches!(result, Err(LabelError::ContinueOutsideLoop { .. })));
+    }
+
+    #[test]
+    fn test_switch_cases() {
+        let ast = label("int main(void){switch (3) { case 1 + 1: for (;;) { continue; case 4: break; } default: case -1: break; } return 0;}").unwrap();
+        let Stmt::Program { body } = ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        let Stmt::Switch { cases, default, .. } = &body[0] else { panic!("Expected switch") };
+        // Cases nested in other statements still belong to the switch
+        let values: Vec<i64> = cases.iter().map(|(value, _)| *value).collect();
+        assert_eq!(values, vec![2, 4, -1]);
+        assert!(default.is_some());
+    }
+
+    #[test]
+    fn test_invalid_cases() {
+        let result = label("int main(void){int a; switch (a) { case 1: case 2 - 1: break; } return 0;}");
+        assert!(matches!(result, Err(LabelError::DuplicateCase { value: 1, .. })));
+        let result = label("int main(void){int a; switch (a) { default: default: ; } return 0;}");
+        assert!(matches!(result, Err(LabelError::DuplicateDefault { .. })));
+        let result = label("int main(void){int a; switch (a) { case a: ; } return 0;}");
+        assert!(matches!(result, Err(LabelError::NonConstantCase { .. })));
+        let result = label("int main(void){case 1: return 0;}");
+        assert!(matches!(result, Err(LabelError::CaseOutsideSwitch { .. })));
+        // continue inside a switch still needs a loop
+        let result = label("int main(void){switch (1) { case 1: continue; } return 0;}");
+        assert!(matches!(result, Err(LabelError::ContinueOutsideLoop { .. })));
+    }
+
+    #[test]
+    fn test_goto_labels() {
+        let ast = label("int main(void){goto end; while (1) { end: break; } return 0;}").unwrap();
+        let Stmt::Program { body } = ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        let Stmt::Goto { label: target, .. } = &body[0] else { panic!("Expected goto") };
+        let Stmt::While { body: loop_body, .. } = &body[1] else { panic!("Expected while") };
+        let Stmt::Compound { body: items } = &**loop_body else { panic!("Expected block") };
+        // A forward goto is bound to the renamed label
+        assert!(matches!(&items[0], Stmt::Labeled { label, .. } if label == target && label != "end"));
+    }
+
+    #[test]
+    fn test_invalid_labels() {
+        let result = label("int main(void){a: ;\na: return 0;}");
+        assert!(matches!(result, Err(LabelError::DuplicateLabel { line: 2, .. })));
+        let result = label("int main(void){goto missing; return 0;}");
+        assert!(matches!(result, Err(LabelError::UndefinedLabel { ref name, .. }) if name == "missing"));
+    }
+}
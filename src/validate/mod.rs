@@ -0,0 +1,4 @@
+pub(crate) mod resolving;
+pub(crate) mod labeling;
+pub(crate) mod evaluating;
+pub(crate) mod typechecking;
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::parse::c_ast::{Stmt, Expr, ExprKind, StorageClass, Initializer};
+
+/// Resolves every use of a local variable to the declaration in scope, renaming each local to a
+/// name unique within the program so later stages don't need to know about block scopes.
+/// Functions and variables with linkage keep their names, since every declaration of them refers
+/// to the same entity.
+pub struct Resolver {
+    /// Stack of scopes, innermost last, each mapping source names to what they refer to
+    scopes: Vec<HashMap<String, ScopeEntry>>,
+    /// Counter used to generate unique variable names
+    counter: usize,
+}
+
+/// What a name declared in some scope refers to
+struct ScopeEntry {
+    unique_name: String,
+    /// Whether the name has linkage, and so refers to the same entity in every scope
+    has_linkage: bool,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver { scopes: Vec::new(), counter: 0 }
+    }
+
+    pub fn resolve(&mut self, stmt: &mut Stmt) -> Result<(), ResolveError> {
+        self.resolve_stmt(stmt)
+    }
+
+    fn resolve_stmt(&mut self, stmt: &mut Stmt) -> Result<(), ResolveError> {
+        match stmt {
+            Stmt::Program { body } => self.resolve_block(body),
+            Stmt::FuncDef { name, params, body, .. } => {
+                self.declare_function(name)?;
+                // The parameters are in the same scope as the outermost block of the body
+                self.scopes.push(HashMap::new());
+                let result = self.resolve_function(params, name.line, body);
+                self.scopes.pop();
+                result
+            }
+            Stmt::FuncDecl { name, params, .. } => {
+                self.declare_function(name)?;
+                // Parameter names in a declaration only need to be distinct, and can be left out
+                self.scopes.push(HashMap::new());
+                let result = params.iter_mut().filter(|param| !param.is_empty())
+                    .try_for_each(|param| self.declare_variable(param, name.line));
+                self.scopes.pop();
+                result
+            }
+            Stmt::Compound { body } => self.resolve_block(body),
+            Stmt::Declaration { name, init, storage, line, .. } => {
+                // Variables at file scope, or declared extern, have linkage
+                let file_scope = self.scopes.len() == 1;
+                if file_scope || *storage == Some(StorageClass::Extern) {
+                    self.declare_linked(name, *line)?;
+                } else {
+                    self.declare_variable(name, *line)?;
+                }
+                // The variable is already in scope within its own initializer
+                if let Some(init) = init {
+                    self.resolve_initializer(init)?;
+                }
+                Ok(())
+            }
+            Stmt::Return { value: Some(value) } => self.resolve_expr(value),
+            Stmt::Return { value: None } => Ok(()),
+            Stmt::Expression { expr } => self.resolve_expr(expr),
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.resolve_expr(condition)?;
+                self.resolve_stmt(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch)?;
+                }
+                Ok(())
+            }
+            Stmt::While { condition, body, .. } | Stmt::DoWhile { body, condition, .. }
+            | Stmt::Switch { condition, body, .. } | Stmt::Case { value: condition, body, .. } => {
+                self.resolve_expr(condition)?;
+                self.resolve_stmt(body)
+            }
+            Stmt::For { init, condition, post, body, .. } => {
+                // A declaration in the init clause is scoped to the loop
+                self.scopes.push(HashMap::new());
+                let result = self.resolve_for(init, condition, post, body);
+                self.scopes.pop();
+                result
+            }
+            Stmt::Default { body, .. } | Stmt::Labeled { body, .. } => self.resolve_stmt(body),
+            // Structure tags are resolved by the parser
+            Stmt::StructDecl { .. } | Stmt::Goto { .. } | Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Null => Ok(()),
+        }
+    }
+
+    fn resolve_initializer(&mut self, init: &mut Initializer) -> Result<(), ResolveError> {
+        match init {
+            Initializer::Single(expr) => self.resolve_expr(expr),
+            Initializer::Compound { items, .. } => items.iter_mut().try_for_each(|(_, init)| self.resolve_initializer(init)),
+        }
+    }
+
+    fn resolve_for(&mut self, init: &mut [Stmt], condition: &mut Option<Box<Expr>>,
+                   post: &mut Option<Box<Expr>>, body: &mut Stmt) -> Result<(), ResolveError> {
+        for init in init {
+            self.resolve_stmt(init)?;
+        }
+        for clause in [condition, post].into_iter().flatten() {
+            self.resolve_expr(clause)?;
+        }
+        self.resolve_stmt(body)
+    }
+
+    fn resolve_function(&mut self, params: &mut [String], line: usize, body: &mut [Stmt]) -> Result<(), ResolveError> {
+        for param in params.iter_mut() {
+            self.declare_variable(param, line)?;
+        }
+        body.iter_mut().try_for_each(|item| self.resolve_stmt(item))
+    }
+
+    /// Add a variable to the current scope, renaming it to a unique name
+    fn declare_variable(&mut self, name: &mut String, line: usize) -> Result<(), ResolveError> {
+        let scope = self.scopes.last_mut().expect("declaration outside of a block");
+        if scope.contains_key(name) {
+            return Err(ResolveError::DuplicateDeclaration { name: name.clone(), line });
+        }
+        let unique_name = format!("{}.{}", name, self.counter);
+        self.counter += 1;
+        scope.insert(name.clone(), ScopeEntry { unique_name: unique_name.clone(), has_linkage: false });
+        *name = unique_name;
+        Ok(())
+    }
+
+    fn declare_function(&mut self, name: &Expr) -> Result<(), ResolveError> {
+        match &name.kind {
+            ExprKind::Identifier { value } => self.declare_linked(value, name.line),
+            _ => Ok(()),
+        }
+    }
+
+    /// Add a name with linkage to the current scope, it may be declared any number of times but
+    /// can't share a scope with a variable without linkage of the same name
+    fn declare_linked(&mut self, name: &str, line: usize) -> Result<(), ResolveError> {
+        let scope = self.scopes.last_mut().expect("declaration outside of a scope");
+        if scope.get(name).is_some_and(|entry| !entry.has_linkage) {
+            return Err(ResolveError::DuplicateDeclaration { name: name.to_string(), line });
+        }
+        scope.insert(name.to_string(), ScopeEntry { unique_name: name.to_string(), has_linkage: true });
+        Ok(())
+    }
+
+    /// Unique name of the innermost declaration of name in scope, which hides any outer ones
+    fn lookup(&self, name: &str) -> Option<&String> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).map(|entry| &entry.unique_name)
+    }
+
+    /// Resolve the items of a block in a new scope, which ends with the block
+    fn resolve_block(&mut self, body: &mut [Stmt]) -> Result<(), ResolveError> {
+        self.scopes.push(HashMap::new());
+        let result = body.iter_mut().try_for_each(|item| self.resolve_stmt(item));
+        self.scopes.pop();
+        result
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) -> Result<(), ResolveError> {
+        let line = expr.line;
+        match &mut expr.kind {
+            ExprKind::Constant { .. } | ExprKind::String { .. } | ExprKind::SizeOfType { .. } | ExprKind::AlignOf { .. } => Ok(()),
+            ExprKind::FunctionCall { function, args } => {
+                // Calling a name which isn't declared at all is reported as a missing function
+                // declaration, rather than a missing variable
+                match &mut function.kind {
+                    ExprKind::Identifier { value } => match self.lookup(value) {
+                        Some(unique_name) => *value = unique_name.clone(),
+                        None => return Err(ResolveError::UndeclaredFunction { name: value.clone(), line }),
+                    },
+                    _ => self.resolve_expr(function)?,
+                }
+                args.iter_mut().try_for_each(|arg| self.resolve_expr(arg))
+            }
+            ExprKind::Identifier { value } => {
+                match self.lookup(value) {
+                    Some(unique_name) => {
+                        *value = unique_name.clone();
+                        Ok(())
+                    }
+                    None => Err(ResolveError::UndeclaredVariable { name: value.clone(), line }),
+                }
+            }
+            ExprKind::Unary { expr, .. } | ExprKind::Postfix { expr, .. } | ExprKind::Cast { expr, .. }
+            | ExprKind::AddressOf { expr } | ExprKind::Dereference { expr } | ExprKind::SizeOf { expr }
+            | ExprKind::Member { expr, .. } | ExprKind::VaStart { ap: expr } | ExprKind::VaArg { ap: expr, .. }
+            | ExprKind::VaEnd { ap: expr } => {
+                self.resolve_expr(expr)
+            }
+            ExprKind::Binary { left, right, .. } | ExprKind::Assignment { left, right }
+            | ExprKind::Subscript { array: left, index: right } | ExprKind::VaCopy { dst: left, src: right }
+            | ExprKind::CompoundAssignment { left, right, .. } => {
+                self.resolve_expr(left)?;
+                self.resolve_expr(right)
+            }
+            ExprKind::Conditional { condition, then_expr, else_expr } => {
+                self.resolve_expr(condition)?;
+                self.resolve_expr(then_expr)?;
+                self.resolve_expr(else_expr)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ResolveError {
+    DuplicateDeclaration{name: String, line: usize},
+    UndeclaredVariable{name: String, line: usize},
+    UndeclaredFunction{name: String, line: usize},
+}
+
+impl ResolveError {
+    /// Line of the source the error was found on
+    pub fn line(&self) -> usize {
+        match self {
+            ResolveError::DuplicateDeclaration { line, .. } | ResolveError::UndeclaredVariable { line, .. }
+            | ResolveError::UndeclaredFunction { line, .. } => *line,
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::DuplicateDeclaration { name, .. } => write!(f, "redeclaration of '{}'", name),
+            ResolveError::UndeclaredVariable { name, .. } => write!(f, "'{}' undeclared", name),
+            ResolveError::UndeclaredFunction { name, .. } => write!(f, "implicit declaration of function '{}'", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex;
+    use crate::parse::parsing::Parser;
+
+    fn resolve(source: &str) -> Result<Stmt, ResolveError> {
+        let mut lexer = lex::Lexer::new(source.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let mut ast = Parser::new(tokens).parse().unwrap();
+        Resolver::new().resolve(&mut ast)?;
+        Ok(ast)
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let ast = resolve("int main(void){int a = 1; {int a = a + 1; a = 3;} return a;}").unwrap();
+        let Stmt::Program { body } = ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = body.into_iter().next().unwrap() else { panic!("Expected function") };
+        let Stmt::Declaration { name: outer, .. } = &body[0] else { panic!("Expected declaration") };
+        let Stmt::Compound { body: inner } = &body[1] else { panic!("Expected block") };
+        let Stmt::Declaration { name: shadow, init: Some(Initializer::Single(init)), .. } = &inner[0] else { panic!("Expected declaration") };
+        assert_ne!(outer, shadow);
+        // The initializer refers to the inner variable, which is in scope after its declarator
+        let ExprKind::Binary { left, .. } = &init.kind else { panic!("Expected binary") };
+        assert!(matches!(&left.kind, ExprKind::Identifier { value } if value == shadow));
+        // Once the block ends the outer variable is visible again
+        let Stmt::Return { value: Some(value) } = &body[2] else { panic!("Expected return") };
+        assert!(matches!(&value.kind, ExprKind::Identifier { value } if value == outer));
+    }
+
+    #[test]
+    fn test_duplicate_declaration() {
+        let result = resolve("int main(void){\nint a;\n{int a;}\nint a;\nreturn 0;}");
+        assert!(matches!(result, Err(ResolveError::DuplicateDeclaration { ref name, line: 4 }) if name == "a"));
+    }
+
+    #[test]
+    fn test_out_of_scope() {
+        let result = resolve("int main(void){\n{int a = 1;}\nreturn a;\n}");
+        assert!(matches!(result, Err(ResolveError::UndeclaredVariable { ref name, line: 3 }) if name == "a"));
+        // Expressions nested in initializer lists are resolved too
+        let result = resolve("int main(void){\nint a[2][2] = {{1}, {2,\nb}};\nreturn 0;\n}");
+        assert!(matches!(result, Err(ResolveError::UndeclaredVariable { ref name, line: 3 }) if name == "b"));
+    }
+
+    #[test]
+    fn test_functions() {
+        let ast = resolve("int f(int a); int main(void){int g = f(1); {int f(int b); return f(g);}}").unwrap();
+        let Stmt::Program { body } = ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = &body[1] else { panic!("Expected function") };
+        // Functions keep their names in every scope
+        let Stmt::Declaration { init: Some(Initializer::Single(init)), .. } = &body[0] else { panic!("Expected declaration") };
+        let ExprKind::FunctionCall { function, .. } = &init.kind else { panic!("Expected call") };
+        assert!(matches!(&function.kind, ExprKind::Identifier { value } if value == "f"));
+        // Parameters share a scope with the outermost block of the body
+        let result = resolve("int f(int a){int a; return a;}");
+        assert!(matches!(result, Err(ResolveError::DuplicateDeclaration { .. })));
+        let result = resolve("int main(void){int f; int f(void); return 0;}");
+        assert!(matches!(result, Err(ResolveError::DuplicateDeclaration { .. })));
+        let result = resolve("int main(void){return f();}");
+        assert!(matches!(result, Err(ResolveError::UndeclaredFunction { .. })));
+        // Variables with linkage keep their names, static locals are still renamed
+        let ast = resolve("int a; int a; int main(void){extern int a; static int b; return a + b;}").unwrap();
+        let Stmt::Program { body } = ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = &body[2] else { panic!("Expected function") };
+        assert!(matches!(&body[0], Stmt::Declaration { name, .. } if name == "a"));
+        assert!(matches!(&body[1], Stmt::Declaration { name, .. } if name != "b"));
+        let result = resolve("int main(void){int a; extern int a; return a;}");
+        assert!(matches!(result, Err(ResolveError::DuplicateDeclaration { .. })));
+    }
+}
@@ -0,0 +1,1705 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use crate::parse::c_ast::{Stmt, Expr, ExprKind, StorageClass, Type, Const, UnaryOp, BinaryOp, Initializer, Designator, InitElement, Member};
+use super::evaluating::{evaluate_constant, check_shift_count, Warning};
+
+/// Checks that every declaration of a name agrees on its type, and that functions and variables
+/// are each used as such. Runs after resolution, so every variable has a unique name. Every
+/// expression is annotated with its type, and implicit conversions are made explicit as casts.
+/// The symbol table it builds is handed on to the assembler.
+pub struct TypeChecker {
+    /// Every variable and function in the program, keyed by unique name
+    symbols: HashMap<String, Symbol>,
+    /// Return type of the function being checked, which returned values are converted to
+    return_ty: Type,
+    /// Whether the function being checked takes variadic arguments, which va_start needs
+    variadic: bool,
+    /// Warnings about the program, which doesn't stop it compiling
+    warnings: Vec<Warning>,
+}
+
+pub struct Symbol {
+    pub ty: Type,
+    pub attrs: IdentAttrs,
+}
+
+/// How a symbol is stored and linked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdentAttrs {
+    /// Global functions and variables are visible to other translation units
+    Function{defined: bool, global: bool},
+    /// Variables with static storage duration, which live for the whole program
+    Static{init: InitialValue, global: bool},
+    /// Variables with automatic storage duration, which live on the stack
+    Local,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitialValue {
+    /// A file scope declaration without an initializer, which defines the variable as zero unless
+    /// another declaration initializes it
+    Tentative,
+    Initial(StaticInit),
+    /// An extern declaration, which is defined elsewhere
+    NoInitializer,
+}
+
+/// Initial value of a variable with static storage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StaticInit {
+    /// The bits of the initial value, already converted to the variable's type
+    Scalar(i64),
+    /// The bytes of a string literal initializing a char array, truncated or padded with zeros to
+    /// the size of the array
+    String(Vec<u8>),
+    /// A pointer to a string literal, holding its bytes
+    StringAddress(Vec<u8>),
+    /// A pointer to the named function
+    FunctionAddress(String),
+    /// A pointer into the named object with static storage, at an offset in bytes from its start
+    ObjectAddress(String, i64),
+    /// The values a brace enclosed initializer gives parts of an aggregate, with their offsets in
+    /// bytes and their types, in order of offset. Everything else is zero.
+    Aggregate(Vec<(usize, Type, StaticInit)>),
+}
+
+impl TypeChecker {
+    pub fn new() -> TypeChecker {
+        TypeChecker { symbols: HashMap::new(), return_ty: Type::Int, variadic: false, warnings: Vec::new() }
+    }
+
+    pub fn check(&mut self, stmt: &mut Stmt) -> Result<(), TypeError> {
+        self.check_stmt(stmt)
+    }
+
+    /// Warnings found while checking the program, which don't stop it compiling
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// The symbol table built while checking the program
+    pub fn into_symbols(self) -> HashMap<String, Symbol> {
+        self.symbols
+    }
+
+    fn check_stmt(&mut self, stmt: &mut Stmt) -> Result<(), TypeError> {
+        match stmt {
+            Stmt::Program { body } => body.iter_mut().try_for_each(|item| match item {
+                Stmt::Declaration { name, ty, init, storage, line } => {
+                    self.declare_file_scope_variable(name, ty, init.as_mut(), *storage, *line)
+                }
+                _ => self.check_stmt(item),
+            }),
+            Stmt::Compound { body } => body.iter_mut().try_for_each(|item| self.check_stmt(item)),
+            Stmt::FuncDef { name, params, ty, storage, body } => {
+                self.declare_function(name, ty, *storage, true)?;
+                let Type::Function { params: param_types, ret, variadic } = ty else {
+                    unreachable!("functions have a function type");
+                };
+                // Parameters and returned values are copied, so their size must be known where the
+                // function is defined
+                for value_ty in param_types.iter().chain([&**ret]).filter(|ty| **ty != Type::Void) {
+                    complete_type(value_ty, name.line)?;
+                }
+                for (param, param_ty) in params.iter().zip(param_types) {
+                    self.symbols.insert(param.clone(), Symbol { ty: param_ty.clone(), attrs: IdentAttrs::Local });
+                }
+                self.return_ty = (**ret).clone();
+                self.variadic = *variadic;
+                body.iter_mut().try_for_each(|item| self.check_stmt(item))
+            }
+            Stmt::FuncDecl { name, ty, storage, .. } => self.declare_function(name, ty, *storage, false),
+            Stmt::Declaration { name, ty, init, storage, line } => {
+                self.declare_local_variable(name, ty, init.as_mut(), *storage, *line)
+            }
+            Stmt::Return { value: Some(value) } => {
+                if self.return_ty == Type::Void {
+                    return Err(TypeError::VoidReturnValue { line: value.line });
+                }
+                self.check_expr(value)?;
+                convert_by_assignment(value, &self.return_ty.clone())
+            }
+            Stmt::Return { value: None } => Ok(()),
+            Stmt::Expression { expr } => self.check_expr(expr),
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.check_condition(condition)?;
+                self.check_stmt(then_branch)?;
+                match else_branch {
+                    Some(else_branch) => self.check_stmt(else_branch),
+                    None => Ok(()),
+                }
+            }
+            Stmt::Switch { condition, body, cases, .. } => {
+                self.check_expr(condition)?;
+                if !condition.ty().is_arithmetic() || condition.ty().is_floating() {
+                    return Err(TypeError::NonIntegerSwitch { ty: condition.ty().clone(), line: condition.line });
+                }
+                // The controlling expression is promoted, and each case value converted to its type
+                let ty = condition.ty().promote();
+                convert_to(condition, &ty);
+                for (value, _) in cases.iter_mut() {
+                    *value = ty.convert_bits(*value);
+                }
+                self.check_stmt(body)
+            }
+            Stmt::While { condition, body, .. } | Stmt::DoWhile { body, condition, .. } => {
+                self.check_condition(condition)?;
+                self.check_stmt(body)
+            }
+            Stmt::Case { value, body, .. } => {
+                self.check_expr(value)?;
+                self.check_stmt(body)
+            }
+            Stmt::For { init, condition, post, body, .. } => {
+                for init in init {
+                    self.check_stmt(init)?;
+                }
+                if let Some(condition) = condition {
+                    self.check_condition(condition)?;
+                }
+                if let Some(post) = post {
+                    self.check_expr(post)?;
+                }
+                self.check_stmt(body)
+            }
+            Stmt::Default { body, .. } | Stmt::Labeled { body, .. } => self.check_stmt(body),
+            Stmt::StructDecl { .. } | Stmt::Goto { .. } | Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Null => Ok(()),
+        }
+    }
+
+    /// Record a declaration or definition of a function, checking it against any earlier ones
+    fn declare_function(&mut self, name: &Expr, ty: &Type, storage: Option<StorageClass>, defining: bool) -> Result<(), TypeError> {
+        let ExprKind::Identifier { value } = &name.kind else {
+            return Ok(());
+        };
+        let line = name.line;
+        let mut defined = defining;
+        let mut global = storage != Some(StorageClass::Static);
+        if let Some(symbol) = self.symbols.get(value) {
+            let IdentAttrs::Function { defined: already_defined, global: already_global } = symbol.attrs else {
+                return Err(TypeError::ConflictingTypes { name: value.clone(), line });
+            };
+            if symbol.ty != *ty {
+                return Err(TypeError::ConflictingTypes { name: value.clone(), line });
+            }
+            if already_defined && defining {
+                return Err(TypeError::Redefinition { name: value.clone(), line });
+            }
+            // A later declaration can't make a global function static, but takes on the internal
+            // linkage of an earlier static declaration
+            if already_global && !global {
+                return Err(TypeError::ConflictingLinkage { name: value.clone(), line });
+            }
+            defined |= already_defined;
+            global = already_global;
+        }
+        self.symbols.insert(value.clone(), Symbol { ty: ty.clone(), attrs: IdentAttrs::Function { defined, global } });
+        Ok(())
+    }
+
+    /// Record a declaration of a variable at file scope, merging it with any earlier ones
+    fn declare_file_scope_variable(&mut self, name: &str, ty: &mut Type, init: Option<&mut Initializer>, storage: Option<StorageClass>,
+                                   line: usize) -> Result<(), TypeError> {
+        check_variable_type(name, ty, storage, init.is_some(), line)?;
+        let mut init = match init {
+            Some(init) => InitialValue::Initial(self.constant_initializer(name, ty, init, line)?),
+            None if storage == Some(StorageClass::Extern) => InitialValue::NoInitializer,
+            None => InitialValue::Tentative,
+        };
+        let mut global = storage != Some(StorageClass::Static);
+        let mut merged = ty.clone();
+        if let Some(symbol) = self.symbols.get(name) {
+            let IdentAttrs::Static { init: earlier_init, global: earlier_global } = symbol.attrs.clone() else {
+                return Err(TypeError::ConflictingTypes { name: name.to_string(), line });
+            };
+            let Some(composite) = composite_type(&symbol.ty, &merged) else {
+                return Err(TypeError::ConflictingTypes { name: name.to_string(), line });
+            };
+            merged = composite;
+            // An extern declaration takes on the linkage of an earlier one, otherwise they must
+            // agree
+            if storage == Some(StorageClass::Extern) {
+                global = earlier_global;
+            } else if earlier_global != global {
+                return Err(TypeError::ConflictingLinkage { name: name.to_string(), line });
+            }
+            init = match (earlier_init, init) {
+                (InitialValue::Initial(_), InitialValue::Initial(_)) => {
+                    return Err(TypeError::Redefinition { name: name.to_string(), line });
+                }
+                (InitialValue::Initial(value), _) | (_, InitialValue::Initial(value)) => InitialValue::Initial(value),
+                (InitialValue::Tentative, _) | (_, InitialValue::Tentative) => InitialValue::Tentative,
+                _ => InitialValue::NoInitializer,
+            };
+        }
+        self.symbols.insert(name.to_string(), Symbol { ty: merged, attrs: IdentAttrs::Static { init, global } });
+        Ok(())
+    }
+
+    /// Record a declaration of a variable at block scope
+    fn declare_local_variable(&mut self, name: &str, ty: &mut Type, init: Option<&mut Initializer>, storage: Option<StorageClass>,
+                              line: usize) -> Result<(), TypeError> {
+        check_variable_type(name, ty, storage, init.is_some(), line)?;
+        match storage {
+            Some(StorageClass::Extern) => {
+                if init.is_some() {
+                    return Err(TypeError::ExternInitializer { name: name.to_string(), line });
+                }
+                // Refers to a file scope variable, which may be declared later
+                match self.symbols.get(name) {
+                    Some(Symbol { attrs: IdentAttrs::Function { .. }, .. }) => {
+                        Err(TypeError::ConflictingTypes { name: name.to_string(), line })
+                    }
+                    Some(symbol) if composite_type(&symbol.ty, ty).is_none() => Err(TypeError::ConflictingTypes { name: name.to_string(), line }),
+                    Some(_) => Ok(()),
+                    None => {
+                        let attrs = IdentAttrs::Static { init: InitialValue::NoInitializer, global: true };
+                        self.symbols.insert(name.to_string(), Symbol { ty: ty.clone(), attrs });
+                        Ok(())
+                    }
+                }
+            }
+            Some(StorageClass::Static) => {
+                // Static locals are initialized once, before the program starts
+                let value = match init {
+                    Some(init) => self.constant_initializer(name, ty, init, line)?,
+                    None => StaticInit::Scalar(0),
+                };
+                let attrs = IdentAttrs::Static { init: InitialValue::Initial(value), global: false };
+                self.symbols.insert(name.to_string(), Symbol { ty: ty.clone(), attrs });
+                Ok(())
+            }
+            None => {
+                self.symbols.insert(name.to_string(), Symbol { ty: ty.clone(), attrs: IdentAttrs::Local });
+                if let Some(init) = init {
+                    self.initialize_variable(name, ty, init, line)?;
+                    // The variable is in scope within its initializer, before its type is complete
+                    self.symbols.insert(name.to_string(), Symbol { ty: ty.clone(), attrs: IdentAttrs::Local });
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Type check the initializer of a variable of type ty. An array declared without a size is
+    /// given one by its initializer, from the length of a string literal or the number of
+    /// elements of a list.
+    fn initialize_variable(&mut self, name: &str, ty: &mut Type, init: &mut Initializer, line: usize) -> Result<(), TypeError> {
+        let length = match init {
+            Initializer::Single(init) => {
+                self.check_initializer(init, ty)?;
+                match &init.kind {
+                    ExprKind::String { value } => value.len() + 1,
+                    _ => 0,
+                }
+            }
+            Initializer::Compound { items, elements } => self.initialize_list(ty, 0, std::mem::take(items), elements, line)?,
+        };
+        // Empty braces leave it without a size
+        if let (Type::Array { size: size @ None, .. }, 1..) = (&mut *ty, length) {
+            *size = Some(length);
+        }
+        if !ty.is_complete() {
+            return Err(TypeError::IncompleteVariable { name: name.to_string(), ty: ty.clone(), line });
+        }
+        Ok(())
+    }
+
+    /// Type check the initializer of a variable, converting it to the variable's type. A char array
+    /// is initialized from a string literal, which is copied rather than converted to a pointer.
+    fn check_initializer(&mut self, init: &mut Expr, ty: &Type) -> Result<(), TypeError> {
+        match (&init.kind, ty) {
+            (ExprKind::String { .. }, Type::Array { element, .. }) if element.is_character() => self.check_object(init),
+            _ => {
+                self.check_expr(init)?;
+                convert_by_assignment(init, ty)
+            }
+        }
+    }
+
+    /// Value of the initializer of a variable with static storage, every part of which must be
+    /// constant
+    fn constant_initializer(&mut self, name: &str, ty: &mut Type, init: &mut Initializer, line: usize) -> Result<StaticInit, TypeError> {
+        self.initialize_variable(name, ty, init, line)?;
+        match init {
+            Initializer::Single(init) => constant_value(name, init, &self.symbols),
+            Initializer::Compound { elements, .. } => aggregate_value(name, elements, &self.symbols),
+        }
+    }
+
+    /// Flatten the brace enclosed list initializing an object of type ty at offset into the
+    /// elements it initializes, returning the number of elements of an array it initializes.
+    /// Braces around a scalar hold a single expression, and a string literal initializing a char
+    /// array may be enclosed in them.
+    fn initialize_list(&mut self, ty: &Type, offset: usize, items: Vec<(Vec<Designator>, Initializer)>,
+                       elements: &mut Vec<InitElement>, line: usize) -> Result<usize, TypeError> {
+        let mut items = VecDeque::from(items);
+        for (_, init) in items.iter_mut() {
+            if let Initializer::Single(expr) = init {
+                self.check_object(expr)?;
+            }
+        }
+        let braced_string = match (ty, items.front()) {
+            (Type::Array { element, .. }, Some((designators, Initializer::Single(expr)))) if items.len() == 1 && element.is_character() && designators.is_empty() => {
+                match &expr.kind {
+                    ExprKind::String { value } => Some(value.len() + 1),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let mut object = Member { name: String::new(), ty: ty.clone(), offset, bits: None };
+        match ty {
+            Type::Array { .. } | Type::Struct(_) if braced_string.is_none() => self.initialize_members(&object, &mut items, true, false, elements, line),
+            _ => {
+                // The string gives an array without a size its length
+                if let (Type::Array { size: size @ None, .. }, Some(length)) = (&mut object.ty, braced_string) {
+                    *size = Some(length);
+                }
+                // Empty braces leave a scalar zero
+                if let Some((designators, init)) = items.pop_front() {
+                    if let Some(designator) = designators.first() {
+                        return Err(designator_error(ty, designator, line));
+                    }
+                    self.initialize(&object, init, &mut items, elements, line)?;
+                }
+                if !items.is_empty() {
+                    return Err(TypeError::ExcessInitializers { line });
+                }
+                Ok(braced_string.unwrap_or(1))
+            }
+        }
+    }
+
+    /// Initialize the members of a structure or union, or the elements of an array, at offset
+    /// from the items of a list, in order from the first or from where a designator says. Its
+    /// own braced list must be used up, while an aggregate whose braces are left out takes items
+    /// until it is full or one has a designator, which belongs to the enclosing list. If it was
+    /// reached through the designators of the first item, the rest of them lead on from it.
+    /// Returns one past the last position initialized, which sizes an array declared without one.
+    fn initialize_members(&mut self, aggregate: &Member, items: &mut VecDeque<(Vec<Designator>, Initializer)>, braced: bool,
+                          designated: bool, elements: &mut Vec<InitElement>, line: usize) -> Result<usize, TypeError> {
+        let ty = &aggregate.ty;
+        let mut position = 0;
+        let mut end = 0;
+        let mut first = true;
+        while let Some((designators, _)) = items.front_mut() {
+            if !designators.is_empty() {
+                // Without its braces, an aggregate only takes the designators leading into it
+                let own = braced || (designated && first);
+                if !own {
+                    break;
+                }
+                position = designated_position(ty, &designators.remove(0), line)?;
+            }
+            let Some(object) = subobject(ty, aggregate.offset, position) else {
+                if braced {
+                    return Err(TypeError::ExcessInitializers { line });
+                }
+                break;
+            };
+            match items.front().map(|(designators, _)| designators.first()) {
+                Some(Some(designator)) => {
+                    if !matches!(object.ty, Type::Array { .. } | Type::Struct(_)) {
+                        return Err(designator_error(&object.ty, designator, line));
+                    }
+                    self.initialize_members(&object, items, false, true, elements, line)?;
+                }
+                _ => {
+                    let (_, init) = items.pop_front().expect("the list has an item");
+                    self.initialize(&object, init, items, elements, line)?;
+                }
+            }
+            end = end.max(position + 1);
+            // Only one member of a union is initialized
+            position = match ty {
+                Type::Struct(struct_type) if struct_type.union => usize::MAX,
+                _ => position + 1,
+            };
+            first = false;
+        }
+        Ok(end)
+    }
+
+    /// Initialize part of an object from one initializer of a list. An expression which can't
+    /// initialize an aggregate on its own starts it with its braces left out, so it also takes
+    /// the items after it which it needs.
+    fn initialize(&mut self, object: &Member, init: Initializer, items: &mut VecDeque<(Vec<Designator>, Initializer)>,
+                  elements: &mut Vec<InitElement>, line: usize) -> Result<(), TypeError> {
+        let mut expr = match init {
+            Initializer::Single(expr) => expr,
+            Initializer::Compound { items, .. } => return self.initialize_list(&object.ty, object.offset, items, elements, line).map(|_| ()),
+        };
+        let whole = match (&expr.kind, &object.ty) {
+            (ExprKind::String { .. }, Type::Array { element, .. }) => element.is_character(),
+            (_, Type::Struct(_)) => expr.ty() == &object.ty,
+            _ => false,
+        };
+        if !whole {
+            if let Type::Array { .. } | Type::Struct(_) = object.ty {
+                items.push_front((Vec::new(), Initializer::Single(expr)));
+                return self.initialize_members(object, items, false, false, elements, line).map(|_| ());
+            }
+            convert_to_pointer(&mut expr);
+            convert_by_assignment(&mut expr, &object.value_type())?;
+        }
+        elements.push(InitElement { offset: object.offset, ty: object.ty.clone(), bits: object.bits, expr: *expr });
+        Ok(())
+    }
+
+    /// Type check an expression used for its value, where an array is converted to a pointer to
+    /// its first element, and a function to a pointer to the function
+    fn check_expr(&mut self, expr: &mut Expr) -> Result<(), TypeError> {
+        self.check_object(expr)?;
+        convert_to_pointer(expr);
+        Ok(())
+    }
+
+    /// Type check the va_list operand of one of the builtins for variadic arguments, which like any
+    /// other array is converted to a pointer
+    fn check_va_list(&mut self, ap: &mut Expr) -> Result<(), TypeError> {
+        self.check_expr(ap)?;
+        if !ap.ty().is_va_list_pointer() {
+            return Err(TypeError::NotAVaList { ty: ap.ty().clone(), line: ap.line });
+        }
+        Ok(())
+    }
+
+    /// Type check an expression whose value is compared to zero, which must have scalar type
+    fn check_condition(&mut self, expr: &mut Expr) -> Result<(), TypeError> {
+        self.check_expr(expr)?;
+        if !expr.ty().is_scalar() {
+            return Err(TypeError::NonScalar { ty: expr.ty().clone(), line: expr.line });
+        }
+        Ok(())
+    }
+
+    /// Type check the target of an assignment, which must be an lvalue which isn't an array or a
+    /// function
+    fn check_assignable(&mut self, expr: &mut Expr) -> Result<(), TypeError> {
+        self.check_object(expr)?;
+        match expr.ty() {
+            Type::Array { .. } => Err(TypeError::ArrayAssignment { ty: expr.ty().clone(), line: expr.line }),
+            Type::Function { .. } => Err(TypeError::FunctionAssignment { ty: expr.ty().clone(), line: expr.line }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Type check an expression without converting arrays and functions to pointers, which is only
+    /// done for the operand of & and the target of an assignment
+    fn check_object(&mut self, expr: &mut Expr) -> Result<(), TypeError> {
+        let line = expr.line;
+        // The size and alignment operators are replaced by their value
+        let mut value = None;
+        let ty = match &mut expr.kind {
+            ExprKind::Constant { value } => value.ty(),
+            ExprKind::String { value } => Type::Array { element: Box::new(Type::Char), size: Some(value.len() + 1) },
+            ExprKind::Identifier { value } => match self.symbols.get(value) {
+                Some(symbol) => symbol.ty.clone(),
+                None => unreachable!("'{}' has been resolved", value),
+            },
+            ExprKind::Cast { target, expr } => {
+                self.check_expr(expr)?;
+                // Anything can be converted to void, otherwise only scalars convert to each other,
+                // and pointers only to and from integers and other pointers
+                let invalid = *target != Type::Void && (!target.is_scalar() || !expr.ty().is_scalar()
+                    || (target.is_pointer() && expr.ty().is_floating()) || (target.is_floating() && expr.ty().is_pointer()));
+                if invalid {
+                    return Err(TypeError::InvalidCast { from: expr.ty().clone(), to: target.clone(), line });
+                }
+                target.clone()
+            }
+            ExprKind::AddressOf { expr } => {
+                self.check_object(expr)?;
+                if is_bit_field(expr) {
+                    return Err(TypeError::AddressOfBitField { line });
+                }
+                Type::Pointer(Box::new(expr.ty().clone()))
+            }
+            ExprKind::Subscript { array, index } => {
+                self.check_expr(array)?;
+                self.check_expr(index)?;
+                // Either operand may be the pointer, as a[i] is *(a + i)
+                if index.ty().is_pointer() {
+                    std::mem::swap(array, index);
+                }
+                let Type::Pointer(element) = array.ty().clone() else {
+                    return Err(TypeError::InvalidOperands { left: array.ty().clone(), right: index.ty().clone(), line });
+                };
+                if !element.is_complete() || !index.ty().is_integer() {
+                    return Err(TypeError::InvalidOperands { left: array.ty().clone(), right: index.ty().clone(), line });
+                }
+                convert_to(index, &Type::Long);
+                *element
+            }
+            ExprKind::Dereference { expr } => {
+                self.check_expr(expr)?;
+                match expr.ty() {
+                    // A dereferenced function pointer designates the function, which is converted
+                    // straight back to a pointer unless it is called or its address taken
+                    Type::Pointer(inner) if inner.is_complete() || matches!(**inner, Type::Function { .. }) => (**inner).clone(),
+                    // Such as reaching a member through a pointer to a declared but undefined
+                    // structure
+                    Type::Pointer(inner) if matches!(**inner, Type::Struct(_)) => {
+                        return Err(TypeError::IncompleteType { ty: (**inner).clone(), line });
+                    }
+                    ty => return Err(TypeError::InvalidDereference { ty: ty.clone(), line }),
+                }
+            }
+            ExprKind::Member { expr, member } => {
+                self.check_expr(expr)?;
+                let Type::Struct(struct_type) = expr.ty() else {
+                    return Err(TypeError::NotAStruct { ty: expr.ty().clone(), member: member.clone(), line });
+                };
+                complete_type(expr.ty(), line)?;
+                match struct_type.member(member) {
+                    Some(member) => member.value_type(),
+                    None => return Err(TypeError::NoMember { ty: expr.ty().clone(), member: member.clone(), line }),
+                }
+            }
+            ExprKind::SizeOf { expr } => {
+                // The operand is never evaluated, as there are no variable length arrays whose size
+                // is only known at run time
+                self.check_object(expr)?;
+                if is_bit_field(expr) {
+                    return Err(TypeError::SizeOfBitField { line });
+                }
+                value = Some(Const::ULong(complete_type(expr.ty(), line)?.size() as u64));
+                Type::ULong
+            }
+            ExprKind::SizeOfType { ty } => {
+                value = Some(Const::ULong(complete_type(ty, line)?.size() as u64));
+                Type::ULong
+            }
+            ExprKind::AlignOf { ty } => {
+                value = Some(Const::ULong(complete_type(ty, line)?.alignment() as u64));
+                Type::ULong
+            }
+            ExprKind::FunctionCall { function, args } => {
+                // A function called by name is converted to a pointer like any other use of it
+                self.check_expr(function)?;
+                let Type::Pointer(inner) = function.ty() else {
+                    return Err(TypeError::NotAFunction { ty: function.ty().clone(), line });
+                };
+                let Type::Function { params, ret, variadic } = &**inner else {
+                    return Err(TypeError::NotAFunction { ty: function.ty().clone(), line });
+                };
+                let (params, ret, variadic) = (params.clone(), (**ret).clone(), *variadic);
+                if ret != Type::Void {
+                    complete_type(&ret, line)?;
+                }
+                // Variadic functions take at least as many arguments as they have parameters
+                let count_matches = if variadic {args.len() >= params.len()} else {args.len() == params.len()};
+                if !count_matches {
+                    return Err(TypeError::WrongArgumentCount {
+                        name: callee_name(function), expected: params.len(), found: args.len(), line,
+                    });
+                }
+                for (i, arg) in args.iter_mut().enumerate() {
+                    self.check_expr(arg)?;
+                    // Variadic arguments are given the default argument promotions, since there is no
+                    // parameter type to convert to
+                    let ty = params.get(i).cloned().unwrap_or_else(|| match arg.ty() {
+                        Type::Float => Type::Double,
+                        ty => ty.promote(),
+                    });
+                    convert_by_assignment(arg, &ty)?;
+                }
+                ret
+            }
+            ExprKind::VaStart { ap } => {
+                if !self.variadic {
+                    return Err(TypeError::VaStartNotVariadic { line });
+                }
+                self.check_va_list(ap)?;
+                Type::Void
+            }
+            ExprKind::VaArg { ap, ty } => {
+                self.check_va_list(ap)?;
+                // Arguments are passed by value, so are never arrays
+                if let Type::Array { .. } = ty {
+                    return Err(TypeError::InvalidVaArgType { ty: ty.clone(), line });
+                }
+                complete_type(ty, line)?.clone()
+            }
+            ExprKind::VaEnd { ap } => {
+                self.check_va_list(ap)?;
+                Type::Void
+            }
+            ExprKind::VaCopy { dst, src } => {
+                self.check_va_list(dst)?;
+                self.check_va_list(src)?;
+                Type::Void
+            }
+            ExprKind::Unary { op: UnaryOp::Not, expr } => {
+                self.check_condition(expr)?;
+                Type::Int
+            }
+            ExprKind::Unary { op, expr } => {
+                self.check_expr(expr)?;
+                arithmetic_operand(expr)?;
+                if *op == UnaryOp::Complement {
+                    integer_operand(expr)?;
+                }
+                let ty = expr.ty().promote();
+                convert_to(expr, &ty);
+                ty
+            }
+            ExprKind::Postfix { expr, .. } => {
+                self.check_assignable(expr)?;
+                if !is_object_pointer(expr.ty()) {
+                    arithmetic_operand(expr)?;
+                }
+                expr.ty().clone()
+            }
+            ExprKind::Binary { op: BinaryOp::And | BinaryOp::Or, left, right } => {
+                self.check_condition(left)?;
+                self.check_condition(right)?;
+                Type::Int
+            }
+            ExprKind::Binary { op: op @ (BinaryOp::ShiftLeft | BinaryOp::ShiftRight), left, right } => {
+                // Each operand is promoted on its own, the result has the type of the left
+                self.check_expr(left)?;
+                self.check_expr(right)?;
+                for operand in [&**left, &**right] {
+                    arithmetic_operand(operand)?;
+                    integer_operand(operand)?;
+                }
+                let ty = left.ty().promote();
+                convert_to(left, &ty);
+                convert_to(right, &right.ty().promote());
+                self.warnings.extend(check_shift_count(*op, right, &ty));
+                ty
+            }
+            ExprKind::Binary { op, left, right } => {
+                self.check_expr(left)?;
+                self.check_expr(right)?;
+                if left.ty().is_pointer() || right.ty().is_pointer() {
+                    pointer_binary(*op, left, right)
+                        .ok_or_else(|| TypeError::InvalidOperands { left: left.ty().clone(), right: right.ty().clone(), line })?
+                } else {
+                    arithmetic_operand(left)?;
+                    arithmetic_operand(right)?;
+                    if integer_operator(*op) {
+                        integer_operand(left)?;
+                        integer_operand(right)?;
+                    }
+                    let common = Type::common(left.ty(), right.ty());
+                    convert_to(left, &common);
+                    convert_to(right, &common);
+                    match op {
+                        BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::LessThan | BinaryOp::LessOrEqual
+                        | BinaryOp::GreaterThan | BinaryOp::GreaterOrEqual => Type::Int,
+                        _ => common,
+                    }
+                }
+            }
+            ExprKind::Assignment { left, right } => {
+                self.check_assignable(left)?;
+                self.check_expr(right)?;
+                let ty = left.ty().clone();
+                convert_by_assignment(right, &ty)?;
+                ty
+            }
+            ExprKind::CompoundAssignment { op, left, right } => {
+                // The operation is done in the common type of both operands (or the promoted left
+                // operand for shifts) which the right operand is converted to, and the result is
+                // converted back to the type of the left
+                self.check_assignable(left)?;
+                self.check_expr(right)?;
+                // A pointer can be moved by an integer, which is converted to long
+                if is_object_pointer(left.ty()) && matches!(op, BinaryOp::Add | BinaryOp::Subtract) {
+                    if !right.ty().is_integer() {
+                        return Err(TypeError::InvalidOperands { left: left.ty().clone(), right: right.ty().clone(), line });
+                    }
+                    convert_to(right, &Type::Long);
+                } else {
+                    arithmetic_operand(left)?;
+                    arithmetic_operand(right)?;
+                    if integer_operator(*op) {
+                        integer_operand(left)?;
+                        integer_operand(right)?;
+                    }
+                    let operation_ty = match op {
+                        BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                            self.warnings.extend(check_shift_count(*op, right, &left.ty().promote()));
+                            right.ty().promote()
+                        }
+                        _ => Type::common(left.ty(), right.ty()),
+                    };
+                    convert_to(right, &operation_ty);
+                }
+                left.ty().clone()
+            }
+            ExprKind::Conditional { condition, then_expr, else_expr } => {
+                self.check_condition(condition)?;
+                self.check_expr(then_expr)?;
+                self.check_expr(else_expr)?;
+                // Either both results are void, or neither is
+                let voids = [&then_expr, &else_expr].iter().filter(|expr| *expr.ty() == Type::Void).count();
+                let common = if voids > 0 {
+                    if voids == 1 {
+                        return Err(TypeError::InvalidOperands { left: then_expr.ty().clone(), right: else_expr.ty().clone(), line });
+                    }
+                    Type::Void
+                } else if matches!(then_expr.ty(), Type::Struct(_)) || matches!(else_expr.ty(), Type::Struct(_)) {
+                    // Structures are only compatible with themselves
+                    if then_expr.ty() != else_expr.ty() {
+                        return Err(TypeError::InvalidOperands { left: then_expr.ty().clone(), right: else_expr.ty().clone(), line });
+                    }
+                    then_expr.ty().clone()
+                } else if then_expr.ty().is_pointer() || else_expr.ty().is_pointer() {
+                    common_pointer_type(then_expr, else_expr).ok_or_else(|| TypeError::InvalidOperands {
+                        left: then_expr.ty().clone(), right: else_expr.ty().clone(), line,
+                    })?
+                } else {
+                    Type::common(then_expr.ty(), else_expr.ty())
+                };
+                convert_to(then_expr, &common);
+                convert_to(else_expr, &common);
+                common
+            }
+        };
+        if let Some(value) = value {
+            expr.kind = ExprKind::Constant { value };
+        }
+        expr.ty = Some(ty);
+        Ok(())
+    }
+}
+
+/// Convert an already type checked array to a pointer to its first element, or a function to a
+/// pointer to it, as is done wherever they are used for their value
+fn convert_to_pointer(expr: &mut Expr) {
+    let ty = match expr.ty() {
+        Type::Array { element, .. } => Type::Pointer(element.clone()),
+        ty @ Type::Function { .. } => Type::Pointer(Box::new(ty.clone())),
+        _ => return,
+    };
+    let line = expr.line;
+    let inner = std::mem::replace(expr, Expr::new(ExprKind::Constant { value: Const::Int(0) }, line));
+    *expr = Expr { kind: ExprKind::AddressOf { expr: Box::new(inner) }, line, ty: Some(ty) };
+}
+
+/// Value of a type checked initializer of a variable with static storage, which must be a
+/// constant, a string literal initializing a char array, the address of a string literal or
+/// function, or an address within an object with static storage
+fn constant_value(name: &str, init: &Expr, symbols: &HashMap<String, Symbol>) -> Result<StaticInit, TypeError> {
+    match &init.kind {
+        ExprKind::String { value } => return Ok(StaticInit::String(value.clone())),
+        ExprKind::AddressOf { expr } => match &expr.kind {
+            ExprKind::String { value } => return Ok(StaticInit::StringAddress(value.clone())),
+            ExprKind::Identifier { value } if matches!(expr.ty(), Type::Function { .. }) => {
+                return Ok(StaticInit::FunctionAddress(value.clone()));
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+    if let Some((object, offset)) = static_address(init, symbols) {
+        return Ok(StaticInit::ObjectAddress(object, offset));
+    }
+    match evaluate_constant(init) {
+        Some(value) => Ok(StaticInit::Scalar(value.bits())),
+        None => Err(TypeError::NonConstantInitializer { name: name.to_string(), line: init.line }),
+    }
+}
+
+/// The object with static storage a pointer expression points into, and the offset in bytes it
+/// points to, if both are known before the program runs
+fn static_address(expr: &Expr, symbols: &HashMap<String, Symbol>) -> Option<(String, i64)> {
+    match &expr.kind {
+        ExprKind::AddressOf { expr } => static_lvalue(expr, symbols),
+        ExprKind::Cast { target: Type::Pointer(_), expr } if expr.ty().is_pointer() => static_address(expr, symbols),
+        ExprKind::Binary { op: op @ (BinaryOp::Add | BinaryOp::Subtract), left, right } => {
+            let (pointer, index) = if left.ty().is_pointer() {(left, right)} else {(right, left)};
+            let Type::Pointer(pointee) = pointer.ty() else {
+                return None;
+            };
+            let (object, offset) = static_address(pointer, symbols)?;
+            let index = evaluate_constant(index)?.bits() * pointee.size() as i64;
+            Some((object, if *op == BinaryOp::Add {offset + index} else {offset - index}))
+        }
+        _ => None,
+    }
+}
+
+/// The object with static storage an lvalue is part of, and the offset in bytes of the part
+fn static_lvalue(expr: &Expr, symbols: &HashMap<String, Symbol>) -> Option<(String, i64)> {
+    match &expr.kind {
+        ExprKind::Identifier { value } => match symbols.get(value) {
+            Some(Symbol { attrs: IdentAttrs::Static { .. }, .. }) => Some((value.clone(), 0)),
+            _ => None,
+        },
+        ExprKind::Member { expr: object, member } => {
+            let Type::Struct(struct_type) = object.ty() else {
+                return None;
+            };
+            let (name, offset) = static_lvalue(object, symbols)?;
+            Some((name, offset + struct_type.member(member)?.offset as i64))
+        }
+        ExprKind::Subscript { array, index } => {
+            let (name, offset) = static_address(array, symbols)?;
+            Some((name, offset + evaluate_constant(index)?.bits() * expr.ty().size() as i64))
+        }
+        ExprKind::Dereference { expr } => static_address(expr, symbols),
+        _ => None,
+    }
+}
+
+/// Value of a variable with static storage from the elements of its brace enclosed initializer.
+/// An element replaces whatever earlier ones initialized in the same place, except that bit-fields
+/// are merged into the bytes they share, since other members can lie within their storage unit.
+/// Zeros are left out, as everything is zero unless initialized.
+fn aggregate_value(name: &str, elements: &[InitElement], symbols: &HashMap<String, Symbol>) -> Result<StaticInit, TypeError> {
+    let mut values: BTreeMap<usize, (Type, StaticInit)> = BTreeMap::new();
+    for element in elements {
+        let value = constant_value(name, &element.expr, symbols)?;
+        let (Some(bits), StaticInit::Scalar(field)) = (element.bits, &value) else {
+            replace_value(&mut values, element.offset, element.ty.clone(), value);
+            continue;
+        };
+        let mask = (u64::MAX >> (64 - bits.width)) << bits.offset;
+        let field = (*field as u64) << bits.offset & mask;
+        for byte in bits.offset / 8..(bits.offset + bits.width).div_ceil(8) {
+            let (byte_mask, byte_field) = ((mask >> (8 * byte)) as u8, (field >> (8 * byte)) as u8);
+            let offset = element.offset + byte;
+            let old = match values.get(&offset) {
+                Some((ty, StaticInit::Scalar(old))) if ty.size() == 1 => *old as u8,
+                _ => 0,
+            };
+            replace_value(&mut values, offset, Type::UChar, StaticInit::Scalar(((old & !byte_mask) | byte_field) as i64));
+        }
+    }
+    Ok(StaticInit::Aggregate(values.into_iter()
+        .filter(|(_, (_, value))| *value != StaticInit::Scalar(0))
+        .map(|(offset, (ty, value))| (offset, ty, value))
+        .collect()))
+}
+
+/// Set the value of part of a static aggregate at offset, replacing the values of anything it
+/// overlaps
+fn replace_value(values: &mut BTreeMap<usize, (Type, StaticInit)>, offset: usize, ty: Type, value: StaticInit) {
+    let end = offset + ty.size();
+    let replaced: Vec<usize> = values.range(..end).filter(|(start, (ty, _))| **start + ty.size() > offset).map(|(start, _)| *start).collect();
+    for start in replaced {
+        values.remove(&start);
+    }
+    values.insert(offset, (ty, value));
+}
+
+/// The member of a structure or element of an array of type ty at the given position, as a member
+/// with its offset from the start of the variable, if there is one. An array without a size has
+/// as many elements as its initializer needs.
+fn subobject(ty: &Type, offset: usize, position: usize) -> Option<Member> {
+    match ty {
+        Type::Array { element, size } if size.is_none_or(|size| position < size) => {
+            Some(Member { name: String::new(), ty: (**element).clone(), offset: offset + position * element.size(), bits: None })
+        }
+        Type::Struct(struct_type) => {
+            let mut member = struct_type.definition.borrow().as_ref()?.members.get(position)?.clone();
+            member.offset += offset;
+            Some(member)
+        }
+        _ => None,
+    }
+}
+
+/// Position of the member or element of an aggregate of type ty a designator names
+fn designated_position(ty: &Type, designator: &Designator, line: usize) -> Result<usize, TypeError> {
+    match (ty, designator) {
+        (Type::Array { size, .. }, Designator::Index(index)) if size.is_none_or(|size| *index < size) => Ok(*index),
+        (Type::Array { .. }, Designator::Index(index)) => Err(TypeError::IndexOutOfBounds { index: *index, line }),
+        (Type::Struct(struct_type), Designator::Member(member)) => struct_type.definition.borrow().as_ref()
+            .and_then(|definition| definition.members.iter().position(|candidate| candidate.name == *member))
+            .ok_or_else(|| TypeError::NoMember { ty: ty.clone(), member: member.clone(), line }),
+        _ => Err(designator_error(ty, designator, line)),
+    }
+}
+
+/// Error for a designator naming part of an object of type ty, which doesn't have that kind of part
+fn designator_error(ty: &Type, designator: &Designator, line: usize) -> TypeError {
+    match designator {
+        Designator::Member(member) => TypeError::NotAStruct { ty: ty.clone(), member: member.clone(), line },
+        Designator::Index(_) => TypeError::IndexedNonArray { ty: ty.clone(), line },
+    }
+}
+
+/// The type, if it is complete so has a size and alignment
+fn complete_type(ty: &Type, line: usize) -> Result<&Type, TypeError> {
+    if !ty.is_complete() {
+        return Err(TypeError::IncompleteType { ty: ty.clone(), line });
+    }
+    Ok(ty)
+}
+
+/// Type of a variable declared with two types, which must be the same, other than an array's size
+/// being left out of one of them
+fn composite_type(earlier: &Type, later: &Type) -> Option<Type> {
+    match (earlier, later) {
+        (Type::Array { element, size: None }, Type::Array { element: later_element, size })
+        | (Type::Array { element: later_element, size }, Type::Array { element, size: None }) if element == later_element => {
+            Some(Type::Array { element: element.clone(), size: *size })
+        }
+        _ if earlier == later => Some(earlier.clone()),
+        _ => None,
+    }
+}
+
+/// Check the type of a variable being declared, which must be complete unless the variable is
+/// defined elsewhere, or is an array whose initializer gives its size
+fn check_variable_type(name: &str, ty: &Type, storage: Option<StorageClass>, initialized: bool, line: usize) -> Result<(), TypeError> {
+    if *ty == Type::Void {
+        return Err(TypeError::VoidVariable { name: name.to_string(), line });
+    }
+    let sized_by_initializer = initialized && matches!(ty, Type::Array { size: None, .. });
+    if !ty.is_complete() && storage != Some(StorageClass::Extern) && !sized_by_initializer {
+        return Err(TypeError::IncompleteVariable { name: name.to_string(), ty: ty.clone(), line });
+    }
+    Ok(())
+}
+
+/// Whether an already type checked expression designates a bit-field, which has no address of its
+/// own
+fn is_bit_field(expr: &Expr) -> bool {
+    let ExprKind::Member { expr, member } = &expr.kind else {
+        return false;
+    };
+    let Type::Struct(struct_type) = expr.ty() else {
+        unreachable!("member access has been type checked");
+    };
+    struct_type.member(member).is_some_and(|member| member.bits.is_some())
+}
+
+/// Name of what a type checked call calls, if it is a function called by name or a variable
+/// holding a pointer to one
+fn callee_name(function: &Expr) -> Option<String> {
+    let function = match &function.kind {
+        ExprKind::AddressOf { expr } => expr,
+        _ => function,
+    };
+    match &function.kind {
+        ExprKind::Identifier { value } => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Whether the type is a pointer to an object, rather than void or a function, which pointer
+/// arithmetic needs to know the size of
+fn is_object_pointer(ty: &Type) -> bool {
+    matches!(ty, Type::Pointer(inner) if inner.is_complete())
+}
+
+/// Whether the type is void *, which converts to and from any other object pointer
+fn is_void_pointer(ty: &Type) -> bool {
+    matches!(ty, Type::Pointer(inner) if **inner == Type::Void)
+}
+
+/// Check a binary operator with a pointer operand, converting the operands and returning the type
+/// of the result, or None if the operator doesn't apply to them. Integers can be added to or
+/// subtracted from a pointer, pointers of the same type subtracted from each other, and pointers
+/// compared with a pointer of the same type or a null pointer constant.
+fn pointer_binary(op: BinaryOp, left: &mut Expr, right: &mut Expr) -> Option<Type> {
+    let (left_ty, right_ty) = (left.ty().clone(), right.ty().clone());
+    match op {
+        BinaryOp::Add | BinaryOp::Subtract if is_object_pointer(&left_ty) && right_ty.is_integer() => {
+            convert_to(right, &Type::Long);
+            Some(left_ty)
+        }
+        BinaryOp::Add if left_ty.is_integer() && is_object_pointer(&right_ty) => {
+            convert_to(left, &Type::Long);
+            Some(right_ty)
+        }
+        // The difference is a number of elements
+        BinaryOp::Subtract if left_ty == right_ty && is_object_pointer(&left_ty) => Some(Type::Long),
+        BinaryOp::Equal | BinaryOp::NotEqual => {
+            let ty = common_pointer_type(left, right)?;
+            convert_to(left, &ty);
+            convert_to(right, &ty);
+            Some(Type::Int)
+        }
+        BinaryOp::LessThan | BinaryOp::LessOrEqual | BinaryOp::GreaterThan | BinaryOp::GreaterOrEqual
+            if left_ty == right_ty => Some(Type::Int),
+        _ => None,
+    }
+}
+
+/// Whether the operator only applies to integer operands
+fn integer_operator(op: BinaryOp) -> bool {
+    matches!(op, BinaryOp::Remainder | BinaryOp::BitwiseAnd | BinaryOp::BitwiseOr | BinaryOp::BitwiseXor
+        | BinaryOp::ShiftLeft | BinaryOp::ShiftRight)
+}
+
+/// Check that an operand of an arithmetic operator isn't a pointer
+fn arithmetic_operand(expr: &Expr) -> Result<(), TypeError> {
+    if !expr.ty().is_arithmetic() {
+        return Err(TypeError::InvalidOperand { ty: expr.ty().clone(), line: expr.line });
+    }
+    Ok(())
+}
+
+/// Check that an operand of an operator which only applies to integers isn't floating
+fn integer_operand(expr: &Expr) -> Result<(), TypeError> {
+    if expr.ty().is_floating() {
+        return Err(TypeError::InvalidOperand { ty: expr.ty().clone(), line: expr.line });
+    }
+    Ok(())
+}
+
+/// Wrap an expression which has been type checked in a cast to the given type, unless it already
+/// has that type
+fn convert_to(expr: &mut Expr, ty: &Type) {
+    if expr.ty() == ty {
+        return;
+    }
+    let line = expr.line;
+    let inner = std::mem::replace(expr, Expr::new(ExprKind::Constant { value: Const::Int(0) }, line));
+    *expr = Expr { kind: ExprKind::Cast { target: ty.clone(), expr: Box::new(inner) }, line, ty: Some(ty.clone()) };
+}
+
+/// Whether an expression which has been type checked is a null pointer constant, an integer
+/// constant expression with the value zero, or one cast to void *
+fn is_null_pointer_constant(expr: &Expr) -> bool {
+    if let ExprKind::Cast { target, expr } = &expr.kind {
+        if is_void_pointer(target) {
+            return is_null_pointer_constant(expr);
+        }
+    }
+    expr.ty().is_integer() && evaluate_constant(expr).is_some_and(|value| value.is_zero())
+}
+
+/// Type both operands of a pointer comparison or conditional are converted to, which is their
+/// type if they have the same one, the type of the other operand for a null pointer constant, or
+/// void * if either is a void pointer
+fn common_pointer_type(left: &Expr, right: &Expr) -> Option<Type> {
+    if left.ty() == right.ty() {
+        Some(left.ty().clone())
+    } else if is_null_pointer_constant(left) && right.ty().is_pointer() {
+        Some(right.ty().clone())
+    } else if is_null_pointer_constant(right) && left.ty().is_pointer() {
+        Some(left.ty().clone())
+    } else if (is_void_pointer(left.ty()) && right.ty().is_pointer()) || (is_void_pointer(right.ty()) && left.ty().is_pointer()) {
+        Some(Type::Pointer(Box::new(Type::Void)))
+    } else {
+        None
+    }
+}
+
+/// Convert an expression which has been type checked to the type of an object it is assigned to,
+/// which allows any arithmetic conversion, but only converts to a pointer type from a pointer
+/// of the same type, a null pointer constant, or between void * and another pointer
+fn convert_by_assignment(expr: &mut Expr, ty: &Type) -> Result<(), TypeError> {
+    if *expr.ty() == Type::Void {
+        return Err(TypeError::VoidValue { line: expr.line });
+    }
+    let allowed = expr.ty() == ty || (expr.ty().is_arithmetic() && ty.is_arithmetic())
+        || (ty.is_pointer() && is_null_pointer_constant(expr))
+        || (is_void_pointer(ty) && expr.ty().is_pointer()) || (is_void_pointer(expr.ty()) && ty.is_pointer());
+    if !allowed {
+        return Err(TypeError::IncompatibleTypes { from: expr.ty().clone(), to: ty.clone(), line: expr.line });
+    }
+    convert_to(expr, ty);
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum TypeError {
+    ConflictingTypes{name: String, line: usize},
+    Redefinition{name: String, line: usize},
+    /// The name is that of the function or pointer called, if it is called by name
+    WrongArgumentCount{name: Option<String>, expected: usize, found: usize, line: usize},
+    NotAFunction{ty: Type, line: usize},
+    ConflictingLinkage{name: String, line: usize},
+    NonConstantInitializer{name: String, line: usize},
+    ExternInitializer{name: String, line: usize},
+    InvalidOperand{ty: Type, line: usize},
+    NonIntegerSwitch{ty: Type, line: usize},
+    InvalidOperands{left: Type, right: Type, line: usize},
+    IncompatibleTypes{from: Type, to: Type, line: usize},
+    InvalidCast{from: Type, to: Type, line: usize},
+    InvalidDereference{ty: Type, line: usize},
+    ArrayAssignment{ty: Type, line: usize},
+    FunctionAssignment{ty: Type, line: usize},
+    VoidVariable{name: String, line: usize},
+    VoidValue{line: usize},
+    VoidReturnValue{line: usize},
+    NonScalar{ty: Type, line: usize},
+    IncompleteType{ty: Type, line: usize},
+    IncompleteVariable{name: String, ty: Type, line: usize},
+    NotAStruct{ty: Type, member: String, line: usize},
+    NoMember{ty: Type, member: String, line: usize},
+    AddressOfBitField{line: usize},
+    SizeOfBitField{line: usize},
+    NotAVaList{ty: Type, line: usize},
+    VaStartNotVariadic{line: usize},
+    InvalidVaArgType{ty: Type, line: usize},
+    ExcessInitializers{line: usize},
+    IndexOutOfBounds{index: usize, line: usize},
+    IndexedNonArray{ty: Type, line: usize},
+}
+
+impl TypeError {
+    /// Line of the source the error was found on
+    pub fn line(&self) -> usize {
+        match self {
+            TypeError::ConflictingTypes { line, .. } | TypeError::Redefinition { line, .. }
+            | TypeError::WrongArgumentCount { line, .. } | TypeError::NotAFunction { line, .. }
+            | TypeError::ConflictingLinkage { line, .. }
+            | TypeError::NonConstantInitializer { line, .. } | TypeError::ExternInitializer { line, .. }
+            | TypeError::InvalidOperand { line, .. } | TypeError::NonIntegerSwitch { line, .. }
+            | TypeError::InvalidOperands { line, .. } | TypeError::IncompatibleTypes { line, .. }
+            | TypeError::InvalidCast { line, .. } | TypeError::InvalidDereference { line, .. }
+            | TypeError::ArrayAssignment { line, .. } | TypeError::FunctionAssignment { line, .. }
+            | TypeError::VoidVariable { line, .. }
+            | TypeError::VoidValue { line } | TypeError::VoidReturnValue { line }
+            | TypeError::NonScalar { line, .. } | TypeError::IncompleteType { line, .. }
+            | TypeError::IncompleteVariable { line, .. } | TypeError::NotAStruct { line, .. }
+            | TypeError::NoMember { line, .. } | TypeError::AddressOfBitField { line }
+            | TypeError::SizeOfBitField { line } | TypeError::NotAVaList { line, .. }
+            | TypeError::VaStartNotVariadic { line } | TypeError::InvalidVaArgType { line, .. }
+            | TypeError::ExcessInitializers { line } | TypeError::IndexOutOfBounds { line, .. }
+            | TypeError::IndexedNonArray { line, .. } => *line,
+        }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeError::ConflictingTypes { name, .. } => write!(f, "conflicting types for '{}'", source_name(name)),
+            TypeError::Redefinition { name, .. } => write!(f, "redefinition of '{}'", source_name(name)),
+            TypeError::WrongArgumentCount { name, expected, found, .. } => {
+                let amount = if found > expected {"many"} else {"few"};
+                match name {
+                    Some(name) => write!(f, "too {} arguments to function '{}', expected {} but got {}", amount, source_name(name), expected, found),
+                    None => write!(f, "too {} arguments in function call, expected {} but got {}", amount, expected, found),
+                }
+            }
+            TypeError::NotAFunction { ty, .. } => write!(f, "called object of type '{}' is not a function or function pointer", ty),
+            TypeError::ConflictingLinkage { name, .. } => write!(f, "'{}' redeclared with different linkage", source_name(name)),
+            TypeError::NonConstantInitializer { name, .. } => write!(f, "initializer of '{}' is not constant", source_name(name)),
+            TypeError::ExternInitializer { name, .. } => write!(f, "'{}' has both 'extern' and initializer", source_name(name)),
+            TypeError::InvalidOperand { ty, .. } => write!(f, "invalid operand of type '{}'", ty),
+            TypeError::NonIntegerSwitch { ty, .. } => write!(f, "switch quantity of type '{}' is not an integer", ty),
+            TypeError::InvalidOperands { left, right, .. } => write!(f, "invalid operands of types '{}' and '{}'", left, right),
+            TypeError::IncompatibleTypes { from, to, .. } => write!(f, "cannot convert from '{}' to '{}'", from, to),
+            TypeError::InvalidCast { from, to, .. } => write!(f, "invalid cast from '{}' to '{}'", from, to),
+            TypeError::InvalidDereference { ty, .. } => write!(f, "invalid type argument of unary '*' (have '{}')", ty),
+            TypeError::ArrayAssignment { ty, .. } => write!(f, "assignment to expression with array type '{}'", ty),
+            TypeError::FunctionAssignment { ty, .. } => write!(f, "assignment to expression with function type '{}'", ty),
+            TypeError::VoidVariable { name, .. } => write!(f, "variable '{}' declared void", source_name(name)),
+            TypeError::VoidValue { .. } => write!(f, "void value not ignored as it ought to be"),
+            TypeError::VoidReturnValue { .. } => write!(f, "'return' with a value, in function returning void"),
+            TypeError::NonScalar { ty, .. } => write!(f, "used '{}' where a scalar is required", ty),
+            TypeError::IncompleteType { ty, .. } => write!(f, "invalid use of incomplete type '{}'", ty),
+            TypeError::IncompleteVariable { name, ty, .. } => {
+                write!(f, "storage size of '{}' isn't known, its type '{}' is incomplete", source_name(name), ty)
+            }
+            TypeError::NotAStruct { ty, member, .. } => {
+                write!(f, "request for member '{}' in something not a structure (have '{}')", member, ty)
+            }
+            TypeError::NoMember { ty, member, .. } => write!(f, "'{}' has no member named '{}'", ty, member),
+            TypeError::AddressOfBitField { .. } => write!(f, "cannot take the address of a bit-field"),
+            TypeError::SizeOfBitField { .. } => write!(f, "'sizeof' applied to a bit-field"),
+            TypeError::NotAVaList { ty, .. } => write!(f, "expected 'va_list' but argument is of type '{}'", ty),
+            TypeError::VaStartNotVariadic { .. } => write!(f, "'va_start' used in function with fixed arguments"),
+            TypeError::InvalidVaArgType { ty, .. } => write!(f, "invalid type '{}' for 'va_arg'", ty),
+            TypeError::ExcessInitializers { .. } => write!(f, "excess elements in initializer"),
+            TypeError::IndexOutOfBounds { index, .. } => write!(f, "array index {} in initializer exceeds array bounds", index),
+            TypeError::IndexedNonArray { ty, .. } => write!(f, "array index in initializer of non-array type '{}'", ty),
+        }
+    }
+}
+
+/// Name of a symbol as written in the source, local variables are renamed to the source name
+/// followed by a dot and a number
+fn source_name(name: &str) -> &str {
+    name.split('.').next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex;
+    use crate::parse::parsing::Parser;
+    use crate::validate::resolving::Resolver;
+
+    /// Type check a program, giving it with the type checker which checked it
+    fn run_checker(source: &str) -> Result<(Stmt, TypeChecker), TypeError> {
+        let tokens = lex::Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut ast = Parser::new(tokens).parse().unwrap();
+        Resolver::new().resolve(&mut ast).unwrap();
+        let mut checker = TypeChecker::new();
+        checker.check(&mut ast)?;
+        Ok((ast, checker))
+    }
+
+    fn check(source: &str) -> Result<(), TypeError> {
+        run_checker(source).map(|_| ())
+    }
+
+    /// A program which type checks, with its symbol table
+    fn checked(source: &str) -> (Stmt, HashMap<String, Symbol>) {
+        let (ast, checker) = run_checker(source).unwrap();
+        (ast, checker.into_symbols())
+    }
+
+    #[test]
+    fn test_declarations() {
+        // Any number of matching declarations, before or after the definition
+        let result = check("int add(int a, int b); int add(int x, int y){return x + y;}\n\
+                            int main(void){int add(int a, int b); return add(1, 2);} int add(int c, int d);");
+        assert!(result.is_ok());
+        let result = check("int f(int a);\nint f(void){return 0;}");
+        assert!(matches!(result, Err(TypeError::ConflictingTypes { ref name, line: 2 }) if name == "f"));
+        let result = check("int f(void){return 0;} int f(void){return 1;}");
+        assert!(matches!(result, Err(TypeError::Redefinition { .. })));
+    }
+
+    #[test]
+    fn test_variadic() {
+        let result = check("int sum(int count, ...); int main(void){return sum(1) + sum(3, 1, 2, 3);}");
+        assert!(result.is_ok());
+        let result = check("int sum(int count, ...); int main(void){return sum();}");
+        assert!(matches!(result, Err(TypeError::WrongArgumentCount { expected: 1, found: 0, .. })));
+        let result = check("int sum(int count, ...); int sum(int count);");
+        assert!(matches!(result, Err(TypeError::ConflictingTypes { .. })));
+    }
+
+    #[test]
+    fn test_static_storage() {
+        let (_, symbols) = checked("int a; int a = 3; extern int a; static int b; int f(void){static int c = 2 + 2; extern int d; return c;}");
+        let attrs = |name: &str| symbols.iter().find(|(key, _)| source_name(key) == name).unwrap().1.attrs.clone();
+        assert_eq!(attrs("a"), IdentAttrs::Static { init: InitialValue::Initial(StaticInit::Scalar(3)), global: true });
+        assert_eq!(attrs("b"), IdentAttrs::Static { init: InitialValue::Tentative, global: false });
+        assert_eq!(attrs("c"), IdentAttrs::Static { init: InitialValue::Initial(StaticInit::Scalar(4)), global: false });
+        assert_eq!(attrs("d"), IdentAttrs::Static { init: InitialValue::NoInitializer, global: true });
+    }
+
+    #[test]
+    fn test_invalid_static_storage() {
+        let result = check("static int a; int a;");
+        assert!(matches!(result, Err(TypeError::ConflictingLinkage { .. })));
+        let result = check("int f(void); static int f(void);");
+        assert!(matches!(result, Err(TypeError::ConflictingLinkage { .. })));
+        let result = check("int a = 1; int a = 1;");
+        assert!(matches!(result, Err(TypeError::Redefinition { .. })));
+        let result = check("int b; int a = b;");
+        assert!(matches!(result, Err(TypeError::NonConstantInitializer { .. })));
+        let result = check("int main(void){extern int a = 1; return a;}");
+        assert!(matches!(result, Err(TypeError::ExternInitializer { .. })));
+        let result = check("int a; int a(void);");
+        assert!(matches!(result, Err(TypeError::ConflictingTypes { .. })));
+    }
+
+    #[test]
+    fn test_calls() {
+        let result = check("int f(int a); int main(void){return f(1, 2);}");
+        assert!(matches!(result, Err(TypeError::WrongArgumentCount { expected: 1, found: 2, .. })));
+        // A local variable hides the function
+        let result = check("int f(void); int main(void){int f = 1; return f();}");
+        assert!(matches!(result, Err(TypeError::NotAFunction { .. })));
+        // A function is converted to a pointer, which can't be moved
+        let result = check("int f(void); int main(void){return f + 1;}");
+        assert!(matches!(result, Err(TypeError::InvalidOperands { .. })));
+    }
+
+    #[test]
+    fn test_function_pointers() {
+        let (ast, symbols) = checked("int f(int a); int (*p)(int) = f; int main(void){int (*q)(int) = &f; return q(1) + (*p)(2) + (p == f);}");
+        let Stmt::Program { body } = &ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = &body[2] else { panic!("Expected function") };
+        // Whether called by name or through a pointer, what is called is a pointer
+        let Stmt::Return { value: Some(value) } = &body[1] else { panic!("Expected return") };
+        let ExprKind::Binary { left, .. } = &value.kind else { panic!("Expected addition") };
+        let ExprKind::Binary { left, right, .. } = &left.kind else { panic!("Expected addition") };
+        for call in [left, right] {
+            let ExprKind::FunctionCall { function, .. } = &call.kind else { panic!("Expected call") };
+            assert_eq!(function.ty().to_string(), "int (*)(int)");
+        }
+        assert_eq!(symbols["p"].attrs, IdentAttrs::Static { init: InitialValue::Initial(StaticInit::FunctionAddress("f".to_string())), global: true });
+        let result = check("int f(int a); int main(void){int (*p)(long) = f; return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompatibleTypes { .. })));
+        let result = check("int f(void); int g(void); int main(void){f = g; return 0;}");
+        assert!(matches!(result, Err(TypeError::FunctionAssignment { .. })));
+        let result = check("int main(void){int (*p)(int) = 0; return p(1, 2);}");
+        assert!(matches!(result, Err(TypeError::WrongArgumentCount { name: Some(_), expected: 1, found: 2, .. })));
+        let result = check("int f(void); int main(void){return sizeof f;}");
+        assert!(matches!(result, Err(TypeError::IncompleteType { .. })));
+    }
+
+    #[test]
+    fn test_variadic_definitions() {
+        let result = check("int f(int n, ...){__builtin_va_list ap; __builtin_va_start(ap, n); int x = __builtin_va_arg(ap, int);\n\
+                            double d = __builtin_va_arg(ap, double); __builtin_va_end(ap); return x + d;}\n\
+                            int g(__builtin_va_list ap){__builtin_va_list aq; __builtin_va_copy(aq, ap); return __builtin_va_arg(aq, int);}");
+        assert!(result.is_ok());
+        let result = check("int f(int n){__builtin_va_list ap; __builtin_va_start(ap, n); return 0;}");
+        assert!(matches!(result, Err(TypeError::VaStartNotVariadic { .. })));
+        let result = check("int f(int n, ...){long ap; __builtin_va_start(ap, n); return 0;}");
+        assert!(matches!(result, Err(TypeError::NotAVaList { ty: Type::Long, .. })));
+        let result = check("int f(int n, ...){__builtin_va_list ap; __builtin_va_start(ap, n); return __builtin_va_arg(ap, void);}");
+        assert!(matches!(result, Err(TypeError::IncompleteType { .. })));
+        let result = check("int f(int n, ...){__builtin_va_list ap; __builtin_va_start(ap, n); __builtin_va_arg(ap, char[2]); return 0;}");
+        assert!(matches!(result, Err(TypeError::InvalidVaArgType { .. })));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let (ast, symbols) = checked("long f(long a); unsigned char c = 257; int main(void){char x = 1; return f(x + 1u);}");
+        let Stmt::Program { body } = &ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = &body[2] else { panic!("Expected function") };
+        // The call returns a long, converted to the return type of main
+        let Stmt::Return { value: Some(value) } = &body[1] else { panic!("Expected return") };
+        let ExprKind::Cast { target: Type::Int, expr: call } = &value.kind else { panic!("Expected cast to int") };
+        let ExprKind::FunctionCall { args, .. } = &call.kind else { panic!("Expected call") };
+        // The argument is an unsigned int, from the usual arithmetic conversions of char and
+        // unsigned int, converted to the long parameter
+        let ExprKind::Cast { target: Type::Long, expr: sum } = &args[0].kind else { panic!("Expected cast to long") };
+        assert_eq!(sum.ty(), &Type::UInt);
+        let ExprKind::Binary { left, .. } = &sum.kind else { panic!("Expected addition") };
+        assert!(matches!(&left.kind, ExprKind::Cast { target: Type::UInt, expr } if expr.ty() == &Type::Char));
+        // Static initializers are converted to the type of the variable
+        assert_eq!(symbols["c"].attrs, IdentAttrs::Static { init: InitialValue::Initial(StaticInit::Scalar(1)), global: true });
+        let result = check("int a; long a;");
+        assert!(matches!(result, Err(TypeError::ConflictingTypes { .. })));
+        let result = check("int f(long a); int f(int a);");
+        assert!(matches!(result, Err(TypeError::ConflictingTypes { .. })));
+    }
+
+    #[test]
+    fn test_floating() {
+        let (ast, symbols) = checked("double d = 3; float f = 1.5; unsigned long u = 2.9; int main(void){return d * 2 < f;}");
+        let Stmt::Program { body } = &ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = &body[3] else { panic!("Expected function") };
+        // The comparison is done in double, giving an int
+        let Stmt::Return { value: Some(value) } = &body[0] else { panic!("Expected return") };
+        assert_eq!(value.ty(), &Type::Int);
+        let ExprKind::Binary { right, .. } = &value.kind else { panic!("Expected comparison") };
+        assert!(matches!(&right.kind, ExprKind::Cast { target: Type::Double, expr } if expr.ty() == &Type::Float));
+        // Static initializers hold the bits of floating values, and are truncated when converted
+        // to an integer type
+        assert_eq!(symbols["d"].attrs, IdentAttrs::Static { init: InitialValue::Initial(StaticInit::Scalar(3.0f64.to_bits() as i64)), global: true });
+        assert_eq!(symbols["f"].attrs, IdentAttrs::Static { init: InitialValue::Initial(StaticInit::Scalar(1.5f32.to_bits() as i64)), global: true });
+        assert_eq!(symbols["u"].attrs, IdentAttrs::Static { init: InitialValue::Initial(StaticInit::Scalar(2)), global: true });
+        for source in ["double d; int main(void){return d % 2;}", "int main(void){return ~1.0;}",
+                       "int main(void){int a = 1; a <<= 1.0; return a;}", "int main(void){double d = 1; d &= 1; return 0;}"] {
+            assert!(matches!(check(source), Err(TypeError::InvalidOperand { ty: Type::Double, .. })));
+        }
+        let result = check("int main(void){switch (1.0f) {default: return 0;}}");
+        assert!(matches!(result, Err(TypeError::NonIntegerSwitch { ty: Type::Float, .. })));
+    }
+
+    #[test]
+    fn test_pointers() {
+        // Null pointer constants convert to any pointer type
+        let result = check("int *p = 0; int main(void){int x = 1; int *q = &x; p = q; return *p + (p == 0) + (q != (int *)0L) + !p;}");
+        assert!(result.is_ok());
+        let result = check("int f(long *p); int main(void){int x; return f(&x);}");
+        assert!(matches!(result, Err(TypeError::IncompatibleTypes { .. })));
+        let result = check("int main(void){int *p = 1; return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompatibleTypes { from: Type::Int, .. })));
+        let result = check("int main(void){int x = 0; return *x;}");
+        assert!(matches!(result, Err(TypeError::InvalidDereference { ty: Type::Int, .. })));
+        let result = check("int main(void){int *p = 0; long *q = 0; return p < q;}");
+        assert!(matches!(result, Err(TypeError::InvalidOperands { .. })));
+        let result = check("int main(void){int *p = 0; return p == 1;}");
+        assert!(matches!(result, Err(TypeError::InvalidOperands { .. })));
+        let result = check("int main(void){int *p = 0; return p * 2;}");
+        assert!(matches!(result, Err(TypeError::InvalidOperands { .. })));
+        let result = check("int main(void){int *p = 0; return -p;}");
+        assert!(matches!(result, Err(TypeError::InvalidOperand { .. })));
+        let result = check("int main(void){double *p = 0; return (int)(double)p;}");
+        assert!(matches!(result, Err(TypeError::InvalidCast { .. })));
+        let result = check("int x; int *p = &x;");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_unsized_arrays() {
+        // A declaration of an array without its size is completed by one with it
+        let result = check("extern int a[]; int f(void){extern int a[]; return a[1];} int a[3]; extern int a[];\n\
+                            int main(int argc, char *argv[]){return sizeof a + sizeof argv;}");
+        assert!(result.is_ok());
+        let result = check("extern int a[]; int main(void){return sizeof a;}");
+        assert!(matches!(result, Err(TypeError::IncompleteType { .. })));
+        let result = check("int main(void){int a[]; return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompleteVariable { .. })));
+        let result = check("int a[3]; extern long a[];");
+        assert!(matches!(result, Err(TypeError::ConflictingTypes { .. })));
+        let result = check("int a[3]; extern int a[4];");
+        assert!(matches!(result, Err(TypeError::ConflictingTypes { .. })));
+        let result = check("int main(void){int (*p)[] = 0; return *p[1];}");
+        assert!(matches!(result, Err(TypeError::InvalidOperands { .. })));
+    }
+
+    #[test]
+    fn test_string_sized_arrays() {
+        // A string literal gives an array without a size its length, including the terminator
+        let (ast, symbols) = checked("char g[] = \"hello\";\n\
+                                      int main(void){char s[] = \"abc\"; static char t[] = \"\"; return sizeof s;}");
+        let Stmt::Program { body } = &ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = &body[1] else { panic!("Expected function") };
+        let Stmt::Return { value: Some(value) } = &body[2] else { panic!("Expected return") };
+        let ExprKind::Cast { expr, .. } = &value.kind else { panic!("Expected cast") };
+        assert_eq!(evaluate_constant(expr), Some(Const::ULong(4)));
+        assert_eq!(symbols["g"].ty, Type::Array { element: Box::new(Type::Char), size: Some(6) });
+        assert!(symbols.values().any(|symbol| symbol.ty == Type::Array { element: Box::new(Type::Char), size: Some(1) }));
+        let result = check("int main(void){int a[] = \"abc\"; return 0;}");
+        assert!(result.is_err());
+        let result = check("int main(void){char *p = \"abc\"; char s[] = p; return 0;}");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_list_sized_arrays() {
+        // A list gives an array without a size as many elements as it initializes
+        let (_, symbols) = checked("int a[] = {1, 2, 3}; long d[] = {[5] = 1, 2, [1] = 3}; int m[][2] = {1, 2, 3};\n\
+                                    char t[] = {\"ab\"}; struct s {int x; char c;} v[] = {{1, 'a'}, 2, 'b', 3};\n\
+                                    int main(void){static char u[] = {'a', 'b'}; return sizeof u;}");
+        let sizes: Vec<usize> = ["a", "d", "m", "t", "v"].iter().map(|name| symbols[*name].ty.size()).collect();
+        assert_eq!(sizes, [12, 56, 16, 3, 24]);
+        assert!(symbols.values().any(|symbol| symbol.ty == Type::Array { element: Box::new(Type::Char), size: Some(2) }));
+        let result = check("int main(void){int a[] = {}; return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompleteVariable { .. })));
+        let result = check("int main(void){int a[] = {1, 2}; int b[] = {[3] = 1}; return sizeof a + sizeof b;}");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_shift_warnings() {
+        // The count is checked against the promoted type of the shifted value, wherever the shift is
+        let (_, checker) = run_checker("long a = 1 << 33; long b = 1L << 33;\n\
+                                        int main(void){char c = 1; c <<= 8; c <<= 32; switch (c) {case 2 >> 40: return 1;}\n\
+                                        return (a >> -1) + (c << 31);}").unwrap();
+        assert_eq!(checker.warnings(), [
+            Warning::ShiftCountTooLarge { left: true, line: 1 }, Warning::ShiftCountTooLarge { left: true, line: 2 },
+            Warning::ShiftCountTooLarge { left: false, line: 2 }, Warning::NegativeShiftCount { left: false, line: 3 },
+        ]);
+        let symbols = checker.into_symbols();
+        assert_eq!(symbols["a"].attrs, IdentAttrs::Static { init: InitialValue::Initial(StaticInit::Scalar(0)), global: true });
+    }
+
+    #[test]
+    fn test_address_constants() {
+        let (_, symbols) = checked("struct s {int a; long b[3];} v; extern int e; int x;\n\
+                                    int *p = &x; long *q = &v.b[2]; long *r = v.b + 1; char *c = (char *)&v + 3; int *t = &e - 1;\n\
+                                    long *u[2] = {&v.b[1], 0}; int *f(void){static int *l = &x; static int y; static int *m = &y; return l + (m - l);}");
+        let value = |name: &str| match &symbols[name].attrs {
+            IdentAttrs::Static { init: InitialValue::Initial(value), .. } => value.clone(),
+            attrs => panic!("Expected initial value, found {:?}", attrs),
+        };
+        let address = |name: &str, offset| StaticInit::ObjectAddress(name.to_string(), offset);
+        assert_eq!(value("p"), address("x", 0));
+        assert_eq!(value("q"), address("v", 24));
+        assert_eq!(value("r"), address("v", 16));
+        assert_eq!(value("c"), address("v", 3));
+        assert_eq!(value("t"), address("e", -4));
+        assert_eq!(value("u"), StaticInit::Aggregate(vec![(0, Type::Pointer(Box::new(Type::Long)), address("v", 16))]));
+        // Static locals have addresses known before the program runs, under their unique names
+        let StaticInit::ObjectAddress(name, 0) = symbols.iter().find_map(|(name, _)| name.starts_with("m.").then(|| value(name))).unwrap() else {
+            panic!("Expected address");
+        };
+        assert!(name.starts_with("y."));
+        let result = check("int main(void){int x; static int *p = &x; return 0;}");
+        assert!(matches!(result, Err(TypeError::NonConstantInitializer { .. })));
+        let result = check("int x; int i = 1; int *p = &x + i;");
+        assert!(matches!(result, Err(TypeError::NonConstantInitializer { .. })));
+        let result = check("int *p; int **q = &p; int *r = *q;");
+        assert!(matches!(result, Err(TypeError::NonConstantInitializer { .. })));
+    }
+
+    #[test]
+    fn test_arrays() {
+        let (ast, _) = checked("int main(void){int a[2][3]; int *p = a[1]; long d = p - &a[0][0]; return 1[p];}");
+        let Stmt::Program { body } = &ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = &body[0] else { panic!("Expected function") };
+        // The row decays to a pointer to its first element
+        let Stmt::Declaration { init: Some(Initializer::Single(init)), .. } = &body[1] else { panic!("Expected declaration") };
+        assert!(matches!(init.kind, ExprKind::AddressOf { .. }));
+        assert_eq!(init.ty(), &Type::Pointer(Box::new(Type::Int)));
+        let Stmt::Declaration { init: Some(Initializer::Single(init)), .. } = &body[2] else { panic!("Expected declaration") };
+        assert_eq!(init.ty(), &Type::Long);
+        // The pointer operand of a subscript is moved to the left, and the index is a long
+        let Stmt::Return { value: Some(value) } = &body[3] else { panic!("Expected return") };
+        let ExprKind::Subscript { array, index } = &value.kind else { panic!("Expected subscript") };
+        assert!(array.ty().is_pointer());
+        assert_eq!(index.ty(), &Type::Long);
+        let result = check("int main(void){int a[2]; int b[2]; a = b; return 0;}");
+        assert!(matches!(result, Err(TypeError::ArrayAssignment { .. })));
+        let result = check("int main(void){int a[2]; a++; return 0;}");
+        assert!(matches!(result, Err(TypeError::ArrayAssignment { .. })));
+        let result = check("int main(void){int a[2]; return a[1.0];}");
+        assert!(matches!(result, Err(TypeError::InvalidOperands { .. })));
+        let result = check("int main(void){int a = 1; return a[0];}");
+        assert!(matches!(result, Err(TypeError::InvalidOperands { .. })));
+        let result = check("int main(void){int a[2]; long *p = a; return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompatibleTypes { .. })));
+    }
+
+    #[test]
+    fn test_strings() {
+        let (_, symbols) = checked("char a[4] = \"abc\"; char *p = \"xy\"; static unsigned char b[2] = \"abc\";\n\
+                                    int main(void){char c[3] = \"hi\"; char *q = \"hi\"; return c[0] + q[1] + \"z\"[0];}");
+        assert_eq!(symbols["a"].attrs, IdentAttrs::Static { init: InitialValue::Initial(StaticInit::String(b"abc".to_vec())), global: true });
+        assert_eq!(symbols["p"].attrs, IdentAttrs::Static { init: InitialValue::Initial(StaticInit::StringAddress(b"xy".to_vec())), global: true });
+        assert_eq!(symbols["b"].attrs, IdentAttrs::Static { init: InitialValue::Initial(StaticInit::String(b"abc".to_vec())), global: false });
+        let result = check("int a[3] = \"ab\";");
+        assert!(matches!(result, Err(TypeError::IncompatibleTypes { .. })));
+        let result = check("int main(void){char a[3] = 0; return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompatibleTypes { .. })));
+        let result = check("int main(void){long *p = \"ab\"; return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompatibleTypes { .. })));
+        let result = check("int main(void){char a[3]; a = \"ab\"; return 0;}");
+        assert!(matches!(result, Err(TypeError::ArrayAssignment { .. })));
+        let result = check("char *p = \"ab\" + 1;");
+        assert!(matches!(result, Err(TypeError::NonConstantInitializer { .. })));
+    }
+
+    #[test]
+    fn test_void() {
+        let result = check("void *malloc(unsigned long n); void free(void *p);\n\
+                            int main(void){int *p = malloc(4); void *q = p; free(p); (void)q; return p == q || !q ? 1 : 0;}");
+        assert!(result.is_ok());
+        // Null pointer constants include 0 cast to void *
+        let result = check("int main(void){long *p = (void *)0; char *c = (void *)(1 - 1); return p == (void *)0;}");
+        assert!(result.is_ok());
+        let result = check("void f(void); int main(void){int x = f(); return x;}");
+        assert!(matches!(result, Err(TypeError::VoidValue { .. })));
+        let result = check("void f(void); int main(void){if (f()) return 1; return 0;}");
+        assert!(matches!(result, Err(TypeError::NonScalar { ty: Type::Void, .. })));
+        let result = check("void f(void){return 1;}");
+        assert!(matches!(result, Err(TypeError::VoidReturnValue { .. })));
+        let result = check("int main(void){void x; return 0;}");
+        assert!(matches!(result, Err(TypeError::VoidVariable { .. })));
+        let result = check("int main(void){void *p = 0; return *p;}");
+        assert!(matches!(result, Err(TypeError::InvalidDereference { .. })));
+        let result = check("int main(void){void *p = 0; p++; return 0;}");
+        assert!(matches!(result, Err(TypeError::InvalidOperand { .. })));
+        let result = check("int main(void){void *p = 0; return p + 1 == 0;}");
+        assert!(matches!(result, Err(TypeError::InvalidOperands { .. })));
+        let result = check("void f(void); int main(void){return 1 ? f() : 1;}");
+        assert!(matches!(result, Err(TypeError::InvalidOperands { .. })));
+        let result = check("void f(void); int main(void){return (int)f();}");
+        assert!(matches!(result, Err(TypeError::InvalidCast { .. })));
+    }
+
+    #[test]
+    fn test_sizeof() {
+        let (ast, _) = checked("int main(void){int a[3][5]; int n = 0; return sizeof a + sizeof(n++) + sizeof a[0] + _Alignof(double);}");
+        let Stmt::Program { body } = &ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = &body[0] else { panic!("Expected function") };
+        let Stmt::Return { value: Some(value) } = &body[2] else { panic!("Expected return") };
+        // Each operator is replaced by an unsigned long constant, so the operand isn't evaluated
+        let ExprKind::Cast { expr, .. } = &value.kind else { panic!("Expected cast") };
+        assert_eq!(evaluate_constant(expr), Some(Const::ULong(60 + 4 + 20 + 8)));
+        let result = check("void f(void); int main(void){return sizeof f();}");
+        assert!(matches!(result, Err(TypeError::IncompleteType { ty: Type::Void, .. })));
+        let result = check("int main(void){return sizeof(void);}");
+        assert!(matches!(result, Err(TypeError::IncompleteType { .. })));
+    }
+
+    #[test]
+    fn test_structs() {
+        let result = check("struct s {int a; char name[4];}; struct s g;\n\
+                            int main(void){struct s x; struct s *p = &x; x = g; p->a = 1; return x.a + *p->name + (1 ? x : g).a;}");
+        assert!(result.is_ok());
+        let result = check("struct s; int main(void){struct s x; return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompleteVariable { .. })));
+        // An extern variable can have an incomplete type, but not be used while it does
+        let result = check("struct s; extern struct s x; int main(void){return sizeof x;}");
+        assert!(matches!(result, Err(TypeError::IncompleteType { .. })));
+        let result = check("struct s; int f(struct s *p){return p->a;}");
+        assert!(matches!(result, Err(TypeError::IncompleteType { .. })));
+        let result = check("struct s; int f(struct s *p){return (p + 1) == p;}");
+        assert!(matches!(result, Err(TypeError::InvalidOperands { .. })));
+        let result = check("struct s {int a;}; int main(void){struct s x; return x.b;}");
+        assert!(matches!(result, Err(TypeError::NoMember { ref member, .. }) if member == "b"));
+        let result = check("struct s {int a;}; int main(void){struct s *p = 0; return p.a;}");
+        assert!(matches!(result, Err(TypeError::NotAStruct { ty: Type::Pointer(_), .. })));
+        let result = check("struct s {int a;}; struct t {int a;}; int main(void){struct s x; struct t y; x = y; return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompatibleTypes { .. })));
+        let result = check("struct s {int a;}; int main(void){struct s x; return (long)x;}");
+        assert!(matches!(result, Err(TypeError::InvalidCast { .. })));
+        let result = check("struct s {int a;}; int main(void){struct s x; return x ? 1 : 0;}");
+        assert!(matches!(result, Err(TypeError::NonScalar { .. })));
+        let result = check("struct s {int a;}; int main(void){struct s x; struct s y; return x == y;}");
+        assert!(matches!(result, Err(TypeError::InvalidOperand { .. })));
+        // Structures are passed and returned by value, once they are complete
+        let result = check("struct s {int a;}; struct s f(struct s x){x.a++; return x;} int main(void){struct s x; return f(f(x)).a;}");
+        assert!(result.is_ok());
+        let result = check("struct s; struct s f(void); int main(void){f(); return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompleteType { .. })));
+        let result = check("struct s; int f(struct s x){return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompleteType { .. })));
+    }
+
+    #[test]
+    fn test_bit_fields() {
+        let (ast, _) = checked("struct s {unsigned long a : 3; unsigned b : 32; long c : 33;};\n\
+                                int main(void){struct s x; x.a = 1; return x.a - 2 < 0;}");
+        // Like gcc, a bit-field narrower than int is an int, even if it was declared unsigned long
+        let Stmt::Program { body } = &ast else { panic!("Expected program") };
+        let Stmt::StructDecl { ty: Type::Struct(struct_type) } = &body[0] else { panic!("Expected struct") };
+        let types: Vec<Type> = struct_type.definition.borrow().as_ref().unwrap().members.iter().map(|member| member.value_type()).collect();
+        assert_eq!(types, vec![Type::Int, Type::UInt, Type::Long]);
+        let Stmt::FuncDef { body, .. } = &body[1] else { panic!("Expected function") };
+        let Stmt::Return { value: Some(value) } = &body[2] else { panic!("Expected return") };
+        let ExprKind::Binary { left, .. } = &value.kind else { panic!("Expected comparison") };
+        assert_eq!(left.ty(), &Type::Int);
+        let result = check("struct s {int a : 3;}; int main(void){struct s x; int *p = &x.a; return 0;}");
+        assert!(matches!(result, Err(TypeError::AddressOfBitField { .. })));
+        let result = check("union u {int a : 3; char c;}; int main(void){union u x; return sizeof x.a;}");
+        assert!(matches!(result, Err(TypeError::SizeOfBitField { .. })));
+        let result = check("union u {int a : 3; char c;}; int main(void){union u x; x.a = 3; x.a++; return x.c + sizeof x;}");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_initializer_lists() {
+        let (ast, symbols) = checked("struct p {int x; int y;}; struct f {unsigned a : 3; int b : 5; char c;};\n\
+                                      int g[2][3] = {{1}, 4, [1][2] = 6}; struct p s = {.y = 2, .x = 1, .y = 0};\n\
+                                      struct f b = {1, -1, 'c'}; int z[4] = {0}; char *n[2] = {\"a\"};\n\
+                                      int main(void){int k = 1; struct p l[2] = {k, 2, {.y = k}}; return 0;}");
+        let value = |name: &str| match &symbols[name].attrs {
+            IdentAttrs::Static { init: InitialValue::Initial(StaticInit::Aggregate(values)), .. } => values.clone(),
+            attrs => panic!("Expected aggregate, found {:?}", attrs),
+        };
+        // Braces can be left out, and designators pick where the list carries on from
+        assert_eq!(value("g"), vec![(0, Type::Int, StaticInit::Scalar(1)), (12, Type::Int, StaticInit::Scalar(4)),
+                                    (20, Type::Int, StaticInit::Scalar(6))]);
+        // Later initializers replace earlier ones, and zeros are left out
+        assert_eq!(value("s"), vec![(0, Type::Int, StaticInit::Scalar(1))]);
+        assert_eq!(value("z"), vec![]);
+        // Bit-fields are merged into the bytes they share, which other members can follow
+        assert_eq!(value("b"), vec![(0, Type::UChar, StaticInit::Scalar(1 | 0x1f << 3)), (1, Type::Char, StaticInit::Scalar(99))]);
+        assert_eq!(value("n"), vec![(0, Type::Pointer(Box::new(Type::Char)), StaticInit::StringAddress(b"a".to_vec()))]);
+        // A list initializing a local is flattened into the elements it initializes
+        let Stmt::Program { body } = &ast else { panic!("Expected program") };
+        let Stmt::FuncDef { body, .. } = &body[7] else { panic!("Expected function") };
+        let Stmt::Declaration { init: Some(Initializer::Compound { items, elements }), .. } = &body[1] else { panic!("Expected list") };
+        assert!(items.is_empty());
+        let offsets: Vec<usize> = elements.iter().map(|element| element.offset).collect();
+        assert_eq!(offsets, vec![0, 4, 12]);
+        let result = check("int a[2] = {1, 2, 3};");
+        assert!(matches!(result, Err(TypeError::ExcessInitializers { .. })));
+        let result = check("int main(void){int x = {1, 2}; return x;}");
+        assert!(matches!(result, Err(TypeError::ExcessInitializers { .. })));
+        let result = check("int a[2] = {[2] = 1};");
+        assert!(matches!(result, Err(TypeError::IndexOutOfBounds { index: 2, .. })));
+        let result = check("struct p {int x;}; struct p s = {[0] = 1};");
+        assert!(matches!(result, Err(TypeError::IndexedNonArray { .. })));
+        let result = check("int a[2] = {.x = 1};");
+        assert!(matches!(result, Err(TypeError::NotAStruct { .. })));
+        let result = check("struct p {int x;}; struct p s = {.y = 1};");
+        assert!(matches!(result, Err(TypeError::NoMember { ref member, .. }) if member == "y"));
+        let result = check("int f(void); int a[2] = {1, f()};");
+        assert!(matches!(result, Err(TypeError::NonConstantInitializer { .. })));
+        let result = check("struct p {int x;}; int main(void){struct p s = {\"a\"}; return 0;}");
+        assert!(matches!(result, Err(TypeError::IncompatibleTypes { .. })));
+        // A structure can initialize a member of its type, or a string literal a char array, whole
+        let result = check("struct p {int x;}; struct q {struct p p; char s[3];};\n\
+                            int main(void){struct p a = {1}; struct q b = {a, \"hi\"}; struct q c = {{2}, {\"hi\"}}; return 0;}");
+        assert!(result.is_ok());
+    }
+}